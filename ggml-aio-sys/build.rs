@@ -4,7 +4,54 @@ extern crate bindgen;
 
 use cmake::Config;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+#[path = "build/native_version.rs"]
+mod native_version;
+
+/// A model library that the `llama`, `whisper` and `sensevoice` features build on top of ggml.
+struct ModelLib {
+    /// Whether the library's feature is enabled.
+    enabled: bool,
+    /// Defined for bindgen to include the library's header from wrapper.h.
+    header_define: &'static str,
+    /// Names of the library's functions and types in the bindings.
+    allowlist: &'static str,
+    /// Option of cc/CMakeLists.txt that adds the library's subdirectory.
+    cmake_option: &'static str,
+    /// Sources below `cc/` to rebuild on changes.
+    src_dir: &'static str,
+    /// Name of the static library to link.
+    link_name: &'static str,
+}
+
+/// In link order: the model libraries before ggml, which all of them depend on.
+const MODEL_LIBS: [ModelLib; 3] = [
+    ModelLib {
+        enabled: cfg!(feature = "whisper"),
+        header_define: "GGML_AIO_WHISPER",
+        allowlist: "whisper.*",
+        cmake_option: "GGML_AIO_BUILD_WHISPER",
+        src_dir: "whisper.cpp/src",
+        link_name: "whisper",
+    },
+    ModelLib {
+        enabled: cfg!(feature = "sensevoice"),
+        header_define: "GGML_AIO_SENSE_VOICE",
+        allowlist: "sense_voice.*",
+        cmake_option: "GGML_AIO_BUILD_SENSE_VOICE",
+        src_dir: "sense-voice.cpp/src",
+        link_name: "sense-voice-core",
+    },
+    ModelLib {
+        enabled: cfg!(feature = "llama"),
+        header_define: "GGML_AIO_LLAMA",
+        allowlist: "llama_.*",
+        cmake_option: "GGML_AIO_BUILD_LLAMA",
+        src_dir: "llama.cpp/src",
+        link_name: "llama",
+    },
+];
 
 fn main() {
     // Iterate over all environment variables
@@ -45,15 +92,27 @@ fn main() {
         // Map Rust target to Android ABI
         let android_abi = match arch {
             "aarch64" => "arm64-v8a",
+            "armv7" | "thumbv7neon" => "armeabi-v7a",
+            "x86_64" => "x86_64",
+            "i686" => "x86",
+            // Rather than guessing just fail.
             _ => panic!("Unsupported Android architecture: {}", arch),
         };
 
-        if arch.contains("aarch64") {
-            config.cflag("-march=armv8.7a");
-            config.cxxflag("-march=armv8.7a");
-        }  else {
-            // Rather than guessing just fail.
-            panic!("Unsupported Android target {arch}");
+        match android_abi {
+            "arm64-v8a" => {
+                config.cflag("-march=armv8.7a");
+                config.cxxflag("-march=armv8.7a");
+            }
+            "armeabi-v7a" => {
+                // NEON is mandatory for every armeabi-v7a device the NDK still supports
+                for flag in ["-march=armv7-a", "-mfpu=neon", "-mfloat-abi=softfp"] {
+                    config.cflag(flag);
+                    config.cxxflag(flag);
+                }
+            }
+            // x86 / x86_64 are emulator targets, the NDK defaults are fine
+            _ => {}
         }
 
         let toolchain_cmake = ndk
@@ -135,8 +194,10 @@ fn main() {
         }
     }
 
-    #[cfg(feature = "openmp")]
-    {
+    // Only GNU targets link libgomp directly, so only there a missing runtime breaks the link.
+    let use_openmp =
+        cfg!(feature = "openmp") && (!target.contains("gnu") || gomp_available(&target));
+    if use_openmp {
         if is_android {
             // OpenMP may require additional setup for Android
             config.define("GGML_OPENMP", "ON");
@@ -144,6 +205,11 @@ fn main() {
         } else if target.contains("gnu") {
             println!("cargo:rustc-link-lib=gomp");
         }
+    } else if cfg!(feature = "openmp") {
+        println!(
+            "cargo:warning=The `openmp` feature is enabled but libgomp was not found for {}, building with GGML_OPENMP=OFF. Install libgomp (e.g. `apt install libgomp1` or your distribution's gcc OpenMP package) to use OpenMP.",
+            target
+        );
     }
 
     let mut bindings = bindgen::Builder::default().header("wrapper.h");
@@ -156,7 +222,7 @@ fn main() {
             .to_string(),
     );
 
-    let bindings = bindings
+    let mut bindings = bindings
         .clang_arg(format!("-I{}", cc_root.join("models/").display()))
         .clang_arg(format!("-I{}", cc_root.display()))
         .clang_arg(format!("-I{}", cc_root.join("ggml/include/").display()))
@@ -183,13 +249,14 @@ fn main() {
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
         .derive_partialeq(true)
         .allowlist_function("ggml_.*")
-        .allowlist_type("ggml_.*")
-        .allowlist_function("llama_.*")
-        .allowlist_type("llama_.*")
-        .allowlist_function("whisper.*")
-        .allowlist_type("whisper.*")
-        .allowlist_function("sense_voice.*")
-        .allowlist_type("sense_voice.*")
+        .allowlist_type("ggml_.*");
+    for lib in MODEL_LIBS.iter().filter(|lib| lib.enabled) {
+        bindings = bindings
+            .clang_arg(format!("-D{}", lib.header_define))
+            .allowlist_function(lib.allowlist)
+            .allowlist_type(lib.allowlist);
+    }
+    let bindings = bindings
         .prepend_enum_name(false)
         .generate()
         .expect("Failed to generate bindings");
@@ -199,24 +266,24 @@ fn main() {
         "cargo:rerun-if-changed={}",
         cc_root.join("ggml/src").display()
     );
-    println!(
-        "cargo:rerun-if-changed={}",
-        cc_root.join("models/llama.cpp/src").display()
-    );
-    println!(
-        "cargo:rerun-if-changed={}",
-        cc_root.join("models/whisper.cpp/src").display()
-    );
-    println!(
-        "cargo:rerun-if-changed={}",
-        cc_root.join("models/SenseVoice.cpp/src").display()
-    );
+    for lib in MODEL_LIBS.iter().filter(|lib| lib.enabled) {
+        println!(
+            "cargo:rerun-if-changed={}",
+            cc_root.join(lib.src_dir).display()
+        );
+    }
 
     let bindings_path = out.join("bindings.rs");
     bindings
         .write_to_file(bindings_path)
         .expect("Failed to write bindings");
 
+    write_native_versions(&cc_root, &out.join("native_versions.rs"))
+        .expect("Failed to write native versions");
+
+    // docs.rs has no network access and limited build time, so stop at the bindings. That is
+    // enough for downstream crates as well: rustdoc type-checks against the bindings but never
+    // links, so the missing native libraries only matter for binaries and tests.
     if env::var("DOCS_RS").is_ok() {
         return;
     }
@@ -297,10 +364,64 @@ fn main() {
         config.define("GGML_METAL", "OFF");
     }
 
+    // OpenBLAS for CPU matrix multiplications. Apple targets already use Accelerate above.
+    // Only the CPU backend uses BLAS, so this combines freely with the GPU features.
+    let use_openblas = cfg!(feature = "blas") && !target.contains("apple");
+    if use_openblas {
+        config.define("GGML_BLAS", "ON");
+        config.define("GGML_BLAS_VENDOR", "OpenBLAS");
+        println!("cargo:rerun-if-env-changed=OPENBLAS_PATH");
+        if let Ok(openblas_path) = env::var("OPENBLAS_PATH") {
+            let openblas_path = PathBuf::from(openblas_path);
+            config.define("BLAS_INCLUDE_DIRS", openblas_path.join("include"));
+            println!(
+                "cargo:rustc-link-search=native={}",
+                openblas_path.join("lib").display()
+            );
+        } else if let Ok(output) = std::process::Command::new("pkg-config")
+            .args(["--libs-only-L", "openblas"])
+            .output()
+        {
+            let libs = String::from_utf8_lossy(&output.stdout);
+            for dir in libs.split_whitespace().filter_map(|flag| flag.strip_prefix("-L")) {
+                println!("cargo:rustc-link-search=native={}", dir);
+            }
+        }
+    }
+
     if cfg!(debug_assertions) {
         config.define("CMAKE_BUILD_TYPE", "RelWithDebInfo");
     }
 
+    // AddressSanitizer for debugging memory errors across the FFI boundary, debug builds only
+    println!("cargo:rerun-if-env-changed=GGML_AIO_SANITIZE");
+    let sanitize = cfg!(feature = "sanitize")
+        || env::var("GGML_AIO_SANITIZE").is_ok_and(|value| !value.is_empty() && value != "0");
+    if sanitize {
+        if env::var("PROFILE").as_deref() == Ok("release") {
+            panic!(
+                "AddressSanitizer (the `sanitize` feature or GGML_AIO_SANITIZE) is for debug builds only, build without --release"
+            );
+        }
+        if !target.contains("linux-gnu") {
+            panic!(
+                "AddressSanitizer builds are only supported on linux-gnu targets, not {}",
+                target
+            );
+        }
+        for flag in ["-fsanitize=address", "-fno-omit-frame-pointer"] {
+            config.cflag(flag);
+            config.cxxflag(flag);
+        }
+        // the native libs are static, so the final binary has to pull in the ASan runtime
+        println!("cargo:rustc-link-lib=dylib=asan");
+    }
+
+    // KleidiAI kernels only exist for aarch64, elsewhere the feature does nothing
+    if cfg!(feature = "arm-optimized") && arch == "aarch64" {
+        config.define("GGML_CPU_KLEIDIAI", "ON");
+    }
+
     for (key, value) in env::vars() {
         let is_useful_flag =
             key.starts_with("WHISPER_") || key.starts_with("LLAMA_") || key.starts_with("GGML_");
@@ -310,18 +431,23 @@ fn main() {
         }
     }
 
-    if cfg!(not(feature = "openmp")) {
+    if !use_openmp {
         config.define("GGML_OPENMP", "OFF");
     }
 
+    // after the environment, so the features decide which libraries are built
+    for lib in &MODEL_LIBS {
+        config.define(lib.cmake_option, if lib.enabled { "ON" } else { "OFF" });
+    }
+
     let destination = config.build();
 
     add_link_search_path(&out.join("build")).unwrap();
 
     println!("cargo:rustc-link-search=native={}", destination.display());
-    println!("cargo:rustc-link-lib=static=whisper");
-    println!("cargo:rustc-link-lib=static=sense-voice-core");
-    println!("cargo:rustc-link-lib=static=llama");
+    for lib in MODEL_LIBS.iter().filter(|lib| lib.enabled) {
+        println!("cargo:rustc-link-lib=static={}", lib.link_name);
+    }
     println!("cargo:rustc-link-lib=static=ggml");
     println!("cargo:rustc-link-lib=static=ggml-base");
     println!("cargo:rustc-link-lib=static=ggml-cpu");
@@ -334,6 +460,10 @@ fn main() {
     if cfg!(feature = "cuda") {
         println!("cargo:rustc-link-lib=static=ggml-cuda");
     }
+    if use_openblas {
+        println!("cargo:rustc-link-lib=static=ggml-blas");
+        println!("cargo:rustc-link-lib=openblas");
+    }
 }
 
 fn get_cpp_link_stdlib(target: &str) -> Option<&'static str> {
@@ -348,6 +478,111 @@ fn get_cpp_link_stdlib(target: &str) -> Option<&'static str> {
     }
 }
 
+/// Whether the target's C compiler can find libgomp.
+///
+/// `-print-file-name` echoes the bare name back when the library isn't in any of the
+/// compiler's search directories, so anything but an existing path means it's missing.
+fn gomp_available(target: &str) -> bool {
+    let compiler = cc::Build::new().target(target).get_compiler();
+    ["libgomp.so", "libgomp.a"].iter().any(|lib| {
+        std::process::Command::new(compiler.path())
+            .arg(format!("-print-file-name={}", lib))
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+            .is_some_and(|path| path.is_absolute() && path.exists())
+    })
+}
+
+/// Write the `NATIVE_VERSIONS` constant behind `native_versions()` to `path`, with the
+/// version of each vendored source tree below `cc_root`, see [native_version].
+fn write_native_versions(cc_root: &Path, path: &Path) -> std::io::Result<()> {
+    let mut fields = String::new();
+    for (field, dir) in [
+        ("ggml", "ggml"),
+        ("whisper", "whisper.cpp"),
+        ("sensevoice", "sense-voice.cpp"),
+        ("llama", "llama.cpp"),
+    ] {
+        let dir = cc_root.join(dir);
+        println!(
+            "cargo:rerun-if-changed={}",
+            dir.join("CMakeLists.txt").display()
+        );
+        rerun_if_head_changed(&dir);
+        fields.push_str(&format!("    {}: {:?},\n", field, native_version(&dir)));
+    }
+    std::fs::write(
+        path,
+        format!(
+            "const NATIVE_VERSIONS: NativeVersions = NativeVersions {{\n{}}};\n",
+            fields
+        ),
+    )
+}
+
+/// Version of the vendored source tree in `dir`: the version its CMake project declares and,
+/// if `dir` is a git checkout such as a submodule, the abbreviated commit hash, e.g.
+/// `1.7.5 (a1b2c3d4e5f6)`. Whichever of the two is known, or `unknown` for neither.
+fn native_version(dir: &Path) -> String {
+    let cmake = std::fs::read_to_string(dir.join("CMakeLists.txt")).ok();
+    native_version::native_version(
+        cmake.as_deref().and_then(native_version::cmake_version),
+        git_commit(dir),
+    )
+}
+
+/// Abbreviated commit hash of `dir` if it has its own `.git`, a directory or, for a
+/// submodule, a file. Sources copied into the crate, like in the published package, have
+/// none, and asking git there would report the commit of the enclosing repository.
+fn git_commit(dir: &Path) -> Option<String> {
+    if !dir.join(".git").exists() {
+        return None;
+    }
+    std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|commit| !commit.is_empty())
+}
+
+/// Rerun when the checkout in `dir` moves to another commit, e.g. on `git submodule update`,
+/// which changes the commit hash without touching CMakeLists.txt. Watches `HEAD` in the git
+/// directory and, if `HEAD` is on a branch, the branch's ref.
+fn rerun_if_head_changed(dir: &Path) {
+    if !dir.join(".git").exists() {
+        return;
+    }
+    let Some(git_dir) = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--absolute-git-dir"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+    else {
+        return;
+    };
+    let head = git_dir.join("HEAD");
+    let Ok(head_contents) = std::fs::read_to_string(&head) else {
+        return;
+    };
+    println!("cargo:rerun-if-changed={}", head.display());
+    if let Some(head_ref) = native_version::head_ref(&head_contents) {
+        // a packed ref has no file of its own, and watching a missing path reruns every build
+        let head_ref = git_dir.join(head_ref);
+        if head_ref.exists() {
+            println!("cargo:rerun-if-changed={}", head_ref.display());
+        }
+    }
+}
+
 fn add_link_search_path(dir: &std::path::Path) -> std::io::Result<()> {
     if dir.is_dir() {
         println!("cargo:rustc-link-search={}", dir.display());
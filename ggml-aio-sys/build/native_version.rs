@@ -0,0 +1,107 @@
+//! Parsing for the native library versions the build script records, in a file of its own so
+//! the crate's tests can include it; build scripts don't get a test harness.
+
+/// The version a CMakeLists.txt declares, either as `project(<name> VERSION <version> ...)`
+/// like whisper.cpp and sense-voice.cpp or in `GGML_VERSION_MAJOR`/`_MINOR`/`_PATCH`
+/// variables like ggml. llama.cpp declares neither and only has its commit.
+pub fn cmake_version(cmake: &str) -> Option<String> {
+    let project_version = cmake
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("project("))
+        .find_map(|args| {
+            let mut words = args.split_whitespace();
+            words.find(|&word| word == "VERSION")?;
+            words
+                .next()
+                .map(|version| version.trim_end_matches(')').to_string())
+        });
+    if project_version.is_some() {
+        return project_version;
+    }
+    let part = |name: &str| {
+        cmake.lines().find_map(|line| {
+            line.trim()
+                .strip_prefix("set(")?
+                .strip_prefix(name)?
+                .strip_suffix(')')
+                .map(|value| value.trim().to_string())
+        })
+    };
+    Some(format!(
+        "{}.{}.{}",
+        part("GGML_VERSION_MAJOR ")?,
+        part("GGML_VERSION_MINOR ")?,
+        part("GGML_VERSION_PATCH ")?
+    ))
+}
+
+/// Combine the CMake version and the commit hash into e.g. `1.7.5 (a1b2c3d4e5f6)`, whichever
+/// of the two is known, or `unknown` for neither.
+pub fn native_version(version: Option<String>, commit: Option<String>) -> String {
+    match (version, commit) {
+        (Some(version), Some(commit)) => format!("{} ({})", version, commit),
+        (Some(version), None) => version,
+        (None, Some(commit)) => commit,
+        (None, None) => "unknown".to_string(),
+    }
+}
+
+/// The ref a git `HEAD` file points at, e.g. `refs/heads/master`, or `None` for a detached
+/// `HEAD` holding a commit hash, which is how submodules are checked out.
+pub fn head_ref(head: &str) -> Option<&str> {
+    head.trim().strip_prefix("ref:").map(str::trim)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn project_version() {
+        let cmake = "cmake_minimum_required(VERSION 3.5)\n\
+                     project(\"whisper.cpp\" C CXX)\n\
+                     project(whisper.cpp VERSION 1.7.5)\n";
+        assert_eq!(cmake_version(cmake).as_deref(), Some("1.7.5"));
+    }
+
+    #[test]
+    fn project_version_with_trailing_arguments() {
+        let cmake = "  project(sense-voice VERSION 0.1.0 LANGUAGES C CXX)\n";
+        assert_eq!(cmake_version(cmake).as_deref(), Some("0.1.0"));
+    }
+
+    #[test]
+    fn ggml_version_variables() {
+        let cmake = "project(\"ggml\" C CXX)\n\
+                     set(GGML_VERSION_MAJOR 0)\n\
+                     set(GGML_VERSION_MINOR 9)\n\
+                     set(GGML_VERSION_PATCH 4)\n";
+        assert_eq!(cmake_version(cmake).as_deref(), Some("0.9.4"));
+    }
+
+    #[test]
+    fn missing_version() {
+        assert_eq!(cmake_version("project(\"llama.cpp\" C CXX)\n"), None);
+        let partial = "set(GGML_VERSION_MAJOR 0)\nset(GGML_VERSION_MINOR 9)\n";
+        assert_eq!(cmake_version(partial), None);
+    }
+
+    #[test]
+    fn combined_version() {
+        let version = || Some("1.7.5".to_string());
+        let commit = || Some("a1b2c3d4e5f6".to_string());
+        assert_eq!(native_version(version(), commit()), "1.7.5 (a1b2c3d4e5f6)");
+        assert_eq!(native_version(version(), None), "1.7.5");
+        assert_eq!(native_version(None, commit()), "a1b2c3d4e5f6");
+        assert_eq!(native_version(None, None), "unknown");
+    }
+
+    #[test]
+    fn head_refs() {
+        assert_eq!(
+            head_ref("ref: refs/heads/master\n"),
+            Some("refs/heads/master")
+        );
+        assert_eq!(head_ref("a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2\n"), None);
+    }
+}
@@ -49,6 +49,10 @@ struct sense_voice_context_params {
     int gpu_device;// CUDA device
     ggml_backend_sched_eval_callback cb_eval;
     void *cb_eval_user_data;
+    // registry name of the GPU backend to use with use_gpu ("CUDA", "ROCm", "Metal", "Vulkan"),
+    // nullptr for the first GPU found. When set, context creation fails instead of falling back
+    // to the CPU if no device of that backend initializes
+    const char *gpu_backend;
 };
 
 
@@ -57,6 +61,19 @@ typedef void (*sense_voice_progress_callback)(struct sense_voice_context *ctx,
                                               struct sense_voice_state *state,
                                               int progress, void *user_data);
 
+// Per-frame probability callback: the softmax over the vocabulary (n_vocab floats) of CTC frame
+// `step`, frames 0-3 being the language, emotion, event and itn slots. `probs` is only valid
+// during the call. Not called by sense_voice_batch_full and sense_voice_full_parallel_batch.
+typedef void (*sense_voice_probs_callback)(struct sense_voice_context *ctx,
+                                           int step, const float *probs, int n_vocab,
+                                           void *user_data);
+
+// Format of the lines written through the log callback when print_progress is set
+enum sense_voice_progress_style {
+    SENSE_VOICE_PROGRESS_PERCENT,// only the percentage
+    SENSE_VOICE_PROGRESS_FULL,   // percentage plus the time spent in each stage
+};
+
 // Available sampling strategies
 enum sense_voice_decoding_strategy {
     SENSE_VOICE_SAMPLING_GREEDY,
@@ -72,32 +89,95 @@ struct sense_voice_full_params {
     int offset_ms;     // start offset in ms
     int duration_ms;   // audio duration to process in ms
 
+    bool no_context;      // do not use past transcription (if any) as context for the next window
     bool no_timestamps;   // do not generate timestamps
     bool single_segment;  // force single segment output (useful for streaming)
     bool print_progress;  // print progress information
     bool print_timestamps;// print timestamps for each text segment when
                           // printing realtime
+    enum sense_voice_progress_style progress_style;
 
     bool debug_mode;// enable debug_mode provides extra info (eg. Dump log_mel)
-    int audio_ctx;
+    int audio_ctx;// encoder frames to use, 0 for all; not used yet, the encoder runs on the whole input
 
     struct {
         int best_of;
     } greedy;
 
+    float length_penalty;// length penalty alpha for beam search, -1 to disable; not used by the CTC decoder yet
+    float temperature;   // sample each CTC frame from softmax(logits / temperature), 0 for argmax
+
     struct {
         int beam_size;
+        float patience;// stop once patience * beam_size hypotheses finished, -1 for the default; not used yet
     } beam_search;
 
     // called on each progress update
     sense_voice_progress_callback progress_callback;
     void *progress_callback_user_data;
+
+    // called between stages and between graph nodes on the CPU backend,
+    // returning true stops the computation and makes sense_voice_full_parallel return -7
+    ggml_abort_callback abort_callback;
+    void *abort_callback_user_data;
+
+    // called for every CTC frame after decoding, copies each frame's probabilities off the backend
+    sense_voice_probs_callback probs_callback;
+    void *probs_callback_user_data;
+
+    // only decode the 4 query frames (language, emotion, event, itn) and leave the text empty,
+    // e.g. to route audio by language before transcribing it
+    bool detect_language;
+
+    // stop a segment after this many text tokens, e.g. against repetition loops; 0 for no limit.
+    // With beam search every hypothesis is cut to the limit after the search
+    int max_tokens;
+
+    // n_vocab values added to the CTC logits of every frame before the softmax, e.g. to boost
+    // hotwords; NULL for none. Only read during the call
+    const float *logit_bias;
+};
+
+
+// route all library logs, including progress and timestamp lines, to the given callback
+// (NULL restores the default, which writes to stderr)
+SENSE_VOICE_API void sense_voice_log_set(ggml_log_callback log_callback, void *user_data);
+// time spent in each stage of the last sense_voice_full_parallel call
+struct sense_voice_timings {
+    float feature_ms;
+    float encode_ms;
+    float decode_ms;
 };
 
+// feature extraction settings of a model, read from its frontend.* GGUF keys
+// (models converted without them use the SenseVoiceSmall values: 16 kHz, 80 mels, 25/10 ms, lfr 7/6)
+struct sense_voice_frontend_params {
+    int sample_rate;
+    int n_mels;
+    int frame_length_ms;// fbank window length
+    int frame_shift_ms; // fbank hop
+    int lfr_m;          // frames stacked into one encoder input frame
+    int lfr_n;          // frames advanced between encoder input frames
+};
+
+// description of a model file read from its GGUF header, see sense_voice_model_probe_file
+struct sense_voice_model_probe {
+    int ftype;
+    int n_audio_ctx;
+    struct sense_voice_frontend_params frontend;
+    int n_tensors_of_type[GGML_TYPE_COUNT];
+    // bytes the file needs to hold the header and all tensor data, larger than the actual file
+    // size if the file is truncated
+    uint64_t file_size;
+};
 
 SENSE_VOICE_API int sense_voice_lang_id(const char *lang);
 SENSE_VOICE_API const char *sense_voice_lang_str(int id);
 SENSE_VOICE_API struct sense_voice_context_params sense_voice_context_default_params();
+// layout of struct sense_voice_full_params as compiled into the library, to check bindings generated
+// from this header against it: sizeof the struct for field = NULL, else the offset of the named field
+// (nested ones as "beam_search.patience"); SIZE_MAX for unknown names
+SENSE_VOICE_API size_t sense_voice_full_params_layout(const char *field);
 SENSE_VOICE_API struct sense_voice_context *sense_voice_small_init_from_file_with_params(const char *path_model, struct sense_voice_context_params params);
 SENSE_VOICE_API struct sense_voice_context *sense_voice_small_init_from_file_with_params_no_state(const char *path_model, struct sense_voice_context_params params);
 SENSE_VOICE_API struct sense_voice_context *sense_voice_init_with_params_no_state(const char *path_model, struct sense_voice_context_params params);
@@ -108,8 +188,103 @@ SENSE_VOICE_API int sense_voice_full_parallel(struct sense_voice_context *ctx,
                                               const double *samples,
                                               int n_samples,
                                               int n_processors);
+// number of tokens in the model's vocabulary, the size of every CTC output distribution
+SENSE_VOICE_API int sense_voice_model_n_vocab(struct sense_voice_context *ctx);
+// number of mel bins the model expects per audio frame
+SENSE_VOICE_API int sense_voice_model_n_mels(struct sense_voice_context *ctx);
+// size of one encoder input frame: n_mels * lfr_m (low frame rate stacking of lfr_m frames)
+SENSE_VOICE_API int sense_voice_feature_dim(struct sense_voice_context *ctx);
+// compute the fbank + lfr + cmvn features of the samples without running the model
+// returns the number of feature frames, or -1 without a state or samples
+SENSE_VOICE_API int sense_voice_pcm_to_features(struct sense_voice_context *ctx,
+                                                const double *samples,
+                                                int n_samples,
+                                                int n_threads);
+// copy up to n_max values of the last computed features into out, returns the total number of values
+SENSE_VOICE_API int sense_voice_get_features(struct sense_voice_context *ctx, float *out, int n_max);
+// like sense_voice_full_parallel, but starting from pre-computed features (n_frames rows of n_dim values)
+// returns -8 if n_dim doesn't match sense_voice_feature_dim
+SENSE_VOICE_API int sense_voice_full_parallel_features(struct sense_voice_context *ctx,
+                                                       const struct sense_voice_full_params *params,
+                                                       const float *features,
+                                                       int n_frames,
+                                                       int n_dim);
+// transcribe n_clips clips in one batched forward pass, shorter clips padded with silence to the longest;
+// the results are read per clip after sense_voice_batch_select. Returns the codes of sense_voice_full_parallel
+SENSE_VOICE_API int sense_voice_full_parallel_batch(struct sense_voice_context *ctx,
+                                                    const struct sense_voice_full_params *params,
+                                                    const double *const *samples,
+                                                    const int *n_samples,
+                                                    int n_clips);
+// number of clips of the last sense_voice_full_parallel_batch call
+SENSE_VOICE_API int sense_voice_batch_n_results(struct sense_voice_context *ctx);
+// make clip i of the last batch the current result, read by sense_voice_full_get_text, _get_tokens, ...;
+// returns false if i is out of range
+SENSE_VOICE_API bool sense_voice_batch_select(struct sense_voice_context *ctx, int i);
+// inverse text normalization (punctuation, written-out numbers) is a query frame of the encoder input,
+// so it takes effect on the next encode; initialized from sense_voice_context_params.use_itn
+SENSE_VOICE_API bool sense_voice_use_itn(struct sense_voice_context *ctx);
+SENSE_VOICE_API void sense_voice_set_use_itn(struct sense_voice_context *ctx, bool use_itn);
 SENSE_VOICE_API const char *sense_voice_full_get_text(struct sense_voice_context *ctx, bool need_prefix);
+// number of segments produced by the last sense_voice_full_parallel call (the whole input is one segment)
+SENSE_VOICE_API int sense_voice_full_n_segments(struct sense_voice_context *ctx);
+// write the decoded token ids of the last run (blanks and CTC repeats removed) into ids, at most n_max
+// returns the total number of tokens, so pass ids = NULL first to query the size
+SENSE_VOICE_API int sense_voice_full_get_tokens(struct sense_voice_context *ctx, int *ids, int n_max);
+// start / end in ms of the tokens returned by sense_voice_full_get_tokens, from the CTC frames that emitted them
+// (the language/emotion/event/itn tags come from the query frames and get 0 / 0), returns the number of tokens
+SENSE_VOICE_API int sense_voice_full_get_token_times(struct sense_voice_context *ctx, int64_t *t0, int64_t *t1, int n_max);
+SENSE_VOICE_API const char *sense_voice_token_to_str(struct sense_voice_context *ctx, int id);
+SENSE_VOICE_API struct sense_voice_timings sense_voice_get_timings(struct sense_voice_context *ctx);
+// copy up to n_max values of the last encoder output into out, row-major [n_frames][n_dim] including the
+// 4 query frames, and store its shape in n_frames / n_dim when not NULL
+// returns the total number of values, 0 if nothing was encoded yet
+SENSE_VOICE_API int sense_voice_get_encoder_output(struct sense_voice_context *ctx,
+                                                   float *out,
+                                                   int n_max,
+                                                   int *n_frames,
+                                                   int *n_dim);
+// start / end of a segment of the last run, in ms from the start of the input
+SENSE_VOICE_API int64_t sense_voice_full_get_segment_t0(struct sense_voice_context *ctx, int i_segment);
+SENSE_VOICE_API int64_t sense_voice_full_get_segment_t1(struct sense_voice_context *ctx, int i_segment);
+// probability the model assigned to <|nospeech|> in the language slot of a segment of the last run
+SENSE_VOICE_API float sense_voice_full_get_segment_no_speech_prob(struct sense_voice_context *ctx, int i_segment);
+// average log probability of the tokens emitted in a segment of the last run, 0 if none were emitted
+SENSE_VOICE_API float sense_voice_full_get_segment_avg_logprob(struct sense_voice_context *ctx, int i_segment);
+// n-best list of the last run, best first: up to beam_search.beam_size hypotheses found by a CTC
+// prefix beam search with the beam search strategy, only the decoded path with greedy decoding
+SENSE_VOICE_API int sense_voice_full_n_hypotheses(struct sense_voice_context *ctx);
+// text tokens of hypothesis i (blanks and repeats removed, no prefix tags), returns the total count
+SENSE_VOICE_API int sense_voice_full_get_hypothesis_tokens(struct sense_voice_context *ctx, int i, int *ids, int n_max);
+// natural log probability of hypothesis i, see sense_voice_full_n_hypotheses
+SENSE_VOICE_API float sense_voice_full_get_hypothesis_score(struct sense_voice_context *ctx, int i);
 SENSE_VOICE_API void sense_voice_reset_ctx_state(struct sense_voice_context *ctx);
+// clear the decoded tokens and text; the features and encoder output of the last run are left in place,
+// but every run computes them anew
+SENSE_VOICE_API void sense_voice_reset_decode_state(struct sense_voice_context *ctx);
+SENSE_VOICE_API void sense_voice_free(struct sense_voice_context *ctx);
+// check that the primary backend of the context can run flash attention with the model's head layout
+SENSE_VOICE_API bool sense_voice_flash_attn_supported(struct sense_voice_context *ctx);
+// name of the backend the context's computations run on first (e.g. "CUDA0", "Metal", "CPU"), NULL without state
+SENSE_VOICE_API const char *sense_voice_active_backend(struct sense_voice_context *ctx);
+// file type of the model weights as stored in the GGUF header (see enum ggml_ftype)
+SENSE_VOICE_API int sense_voice_model_ftype(struct sense_voice_context *ctx);
+// maximum number of encoder frames (one per lfr_n * frame_shift = 60 ms of audio), the upper bound for audio_ctx
+SENSE_VOICE_API int sense_voice_model_n_audio_ctx(struct sense_voice_context *ctx);
+// feature extraction settings the model was trained with, used by every feature computation of the context
+SENSE_VOICE_API struct sense_voice_frontend_params sense_voice_model_frontend(struct sense_voice_context *ctx);
+// parse only the GGUF header of a model file (keys and tensor infos), without allocating weights
+// returns 0 on success, -1 if the file is not a readable GGUF file, -2 if a required key or the tensors are missing,
+// -3 if the file type is invalid or not stored as a uint32, -4 if the frontend settings are not supported
+SENSE_VOICE_API int sense_voice_model_probe_file(const char *path_model, struct sense_voice_model_probe *probe);
+// number of model tensors stored with the given ggml type
+SENSE_VOICE_API int sense_voice_model_n_tensors_of_type(struct sense_voice_context *ctx, enum ggml_type type);
+// check that the primary backend of the context can run a matrix multiplication with weights of the given type
+SENSE_VOICE_API bool sense_voice_type_supported(struct sense_voice_context *ctx, enum ggml_type type);
+// scalar key-value pairs from the model's GGUF header (array values such as the vocabulary are skipped)
+SENSE_VOICE_API int sense_voice_model_meta_count(struct sense_voice_context *ctx);
+SENSE_VOICE_API const char *sense_voice_model_meta_key_by_index(struct sense_voice_context *ctx, int i);
+SENSE_VOICE_API const char *sense_voice_model_meta_val_str_by_index(struct sense_voice_context *ctx, int i);
 #ifdef __cplusplus
 }
 #endif
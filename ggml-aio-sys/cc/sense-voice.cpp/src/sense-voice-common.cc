@@ -19,6 +19,13 @@
 #define SENSEVOICE_ATTRIBUTE_FORMAT(...)
 #endif
 
+sense_voice_global g_state;
+
+void sense_voice_log_set(ggml_log_callback log_callback, void *user_data) {
+    g_state.log_callback = log_callback ? log_callback : sense_voice_log_callback_default;
+    g_state.log_callback_user_data = user_data;
+}
+
 void sense_voice_log_callback_default(ggml_log_level level,
                                              const char *text, void *user_data) {
     (void)level;
@@ -59,8 +66,10 @@ struct sense_voice_full_params sense_voice_full_default_params(enum sense_voice_
 
             /*.no_context        =*/ true,
             /*.no_timestamps     =*/ false,
+            /*.single_segment    =*/ false,
             /*.print_progress    =*/ true,
             /*.print_timestamps  =*/ true,
+            /*.progress_style    =*/ SENSE_VOICE_PROGRESS_PERCENT,
 
 
             /*.debug_mode        =*/ false,
@@ -70,13 +79,29 @@ struct sense_voice_full_params sense_voice_full_default_params(enum sense_voice_
                     /*.best_of   =*/ -1,
             },
 
+            /*.length_penalty    =*/ -1.0f,
+            /*.temperature       =*/ 0.0f,
+
             /*.beam_search      =*/ {
                     /*.beam_size =*/ -1,
+                    /*.patience  =*/ -1.0f,
             },
 
             /*.progress_callback           =*/ nullptr,
             /*.progress_callback_user_data =*/ nullptr,
 
+            /*.abort_callback              =*/ nullptr,
+            /*.abort_callback_user_data    =*/ nullptr,
+
+            /*.probs_callback              =*/ nullptr,
+            /*.probs_callback_user_data    =*/ nullptr,
+
+            /*.detect_language             =*/ false,
+
+            /*.max_tokens                  =*/ 0,
+
+            /*.logit_bias                  =*/ nullptr,
+
     };
 
     switch (strategy) {
@@ -89,7 +114,8 @@ struct sense_voice_full_params sense_voice_full_default_params(enum sense_voice_
         case SENSE_VOICE_SAMPLING_BEAM_SEARCH:
         {
             result.beam_search = {
-                    /*.beam_size =*/ 5
+                    /*.beam_size =*/ 5,
+                    /*.patience  =*/ -1.0f,
             };
         } break;
     }
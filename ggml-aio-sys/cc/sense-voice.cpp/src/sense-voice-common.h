@@ -12,6 +12,7 @@
 #include <ggml-cpu.h>
 #include <gguf.h>
 #include <map>
+#include <random>
 #include <set>
 #include <string>
 
@@ -185,7 +186,7 @@ struct sense_voice_global {
     void *log_callback_user_data = nullptr;
 };
 
-static sense_voice_global g_state;
+extern sense_voice_global g_state;
 
 // available whisper models
 enum e_model {
@@ -268,6 +269,7 @@ struct sense_voice_hparams {
     // for auto-detection, set to nullptr, "" or "auto"
     const char *language;
 
+    int sample_rate = 16000;
     int n_mels = 80;// dim of mels
     std::string window = "hamming";
     int frame_length = 25;
@@ -322,10 +324,19 @@ struct sense_voice_segment {
     // std::string text;         // tokens对应的文本
     std::vector<int> tokens;    // 识别后的tokens
     std::vector<double> samples;// 具体音频
+    float no_speech_prob = 0.0f;// probability of <|nospeech|> in the language slot
+    float avg_logprob = 0.0f;   // average log probability of the emitted tokens
+    size_t n_frames = 0;        // encoder frames of the unpadded samples in a batch, 0 for all
     // std::vector<float>
     // bool speaker_turn_next;
 };
 
+// a decoded token sequence and its log probability, see sense_voice_full_get_hypothesis_score
+struct sense_voice_hypothesis {
+    std::vector<int> tokens;// text tokens, CTC blanks and repeats removed
+    float logprob = 0.0f;
+};
+
 struct sense_voice_vocab {
     using id = int32_t;
     using token = std::string;
@@ -419,9 +430,32 @@ struct sense_voice_state {
 
     // decode output (2-dimensional array: [n_tokens][n_vocab])
     std::vector<int> ids;
+    float no_speech_prob = 0.0f;// probability of <|nospeech|> in the language slot of ids
+    float avg_logprob = 0.0f;   // average log probability of the tokens emitted in ids
+
+    // n-best list of the last decode of ids, best first
+    std::vector<sense_voice_hypothesis> hypotheses;
+
+    // sampling temperature of the next decode, 0 picks the argmax of every frame
+    float temperature = 0.0f;
+    // beams of the CTC prefix beam search for hypotheses, 1 keeps only the decoded path
+    int n_beams = 1;
+    std::mt19937 rng;
+    // per-frame probabilities of the next decode, see sense_voice_full_params::probs_callback
+    sense_voice_probs_callback probs_callback = nullptr;
+    void *probs_callback_user_data = nullptr;
+    // keep only the query frames of the next decode, see sense_voice_full_params::detect_language
+    bool detect_language = false;
+    // text tokens to keep per segment of the next decode, see sense_voice_full_params::max_tokens
+    int max_tokens = 0;
+    // added to the CTC logits of every frame of the next decode, empty for none,
+    // see sense_voice_full_params::logit_bias
+    std::vector<float> logit_bias;
     std::vector<sense_voice_segment> result_all;
     std::string full_text;
     std::vector<size_t> segmentIDs;
+    // per-clip results of the last sense_voice_full_parallel_batch call
+    std::vector<sense_voice_segment> batch_results;
     // std::vector<int> prompt_past;
 
     // work container used to avoid memory allocations
@@ -460,6 +494,9 @@ struct sense_voice_model {
     // tensors
     int n_loaded;
     std::map<std::string, struct ggml_tensor *> tensors;
+
+    // scalar gguf key-value pairs, values rendered as strings
+    std::vector<std::pair<std::string, std::string>> metadata;
 };
 
 
@@ -4,6 +4,11 @@
 
 #include "sense-voice-decoder.h"
 
+#include <algorithm>
+#include <cmath>
+#include <map>
+#include <numeric>
+
 #define SENSEVOICE_DECODER_MAX_NODES 16
 
 // faster matrix multiplications for tensors that do not have dimension 0 divisible by "pad"
@@ -73,9 +78,17 @@ struct ggml_cgraph *sense_voice_build_graph_ctc_decoder(sense_voice_context &ctx
         cur = ggml_add(ctx0, cur, model->ctc_out_linear_bias);
         // Reshape back to 3D
         cur = ggml_reshape_3d(ctx0, cur, cur->ne[0], encoder_out->ne[1], encoder_out->ne[2]);
+        if (!state.logit_bias.empty()) {
+            // broadcast over all frames of all items
+            ggml_tensor *logit_bias = ggml_new_tensor_1d(ctx0, GGML_TYPE_F32, cur->ne[0]);
+            ggml_set_name(logit_bias, "logit_bias");
+            ggml_set_input(logit_bias);
+            cur = ggml_add(ctx0, cur, logit_bias);
+        }
     }
     ggml_tensor * probs = ggml_soft_max(ctx0, cur);
     probs = ggml_reshape_2d(ctx0, probs, probs->ne[0], probs->ne[1] * probs->ne[2] * probs->ne[3]);
+    ggml_set_name(probs, "probs");
     ggml_tensor * argmax_logit = ggml_argmax(ctx0, probs);
     argmax_logit = ggml_reshape_3d(ctx0, argmax_logit, cur->ne[1], cur->ne[2], cur->ne[3]);
     ggml_set_output(probs);
@@ -86,6 +99,92 @@ struct ggml_cgraph *sense_voice_build_graph_ctc_decoder(sense_voice_context &ctx
 }
 
 
+static double log_add(double a, double b) {
+    if (a == -INFINITY) return b;
+    if (b == -INFINITY) return a;
+    return std::max(a, b) + std::log1p(std::exp(-std::fabs(a - b)));
+}
+
+// cut the CTC frames ids (query frames first) before the frame that emits text token max_tokens + 1,
+// counting tokens like sense_voice_full_get_tokens; no limit if max_tokens <= 0
+static void sense_voice_cap_tokens(std::vector<int> &ids, int max_tokens) {
+    if (max_tokens <= 0) {
+        return;
+    }
+    int n_emitted = 0;
+    for (size_t i = 4; i < ids.size(); i++) {
+        if (!ids[i] || ids[i - 1] == ids[i]) {
+            continue;
+        }
+        if (n_emitted++ == max_tokens) {
+            ids.resize(i);
+            return;
+        }
+    }
+}
+
+// CTC prefix beam search over the text frames (after the 4 query frames) of one item of probs,
+// expanding each prefix with the beam_size most likely tokens of every frame
+static std::vector<sense_voice_hypothesis> sense_voice_ctc_prefix_beam_search(
+        ggml_tensor *probs, int64_t item, int64_t n_frames, int beam_size) {
+    const int64_t n_vocab = probs->ne[0];
+    // log probability of the prefix ending in a blank / in its last token
+    struct beam_scores {
+        double blank = -INFINITY;
+        double token = -INFINITY;
+        double total() const { return log_add(blank, token); }
+    };
+    std::map<std::vector<int>, beam_scores> beams;
+    beams[{}].blank = 0.0;
+
+    std::vector<float> row(n_vocab);
+    std::vector<int> top(n_vocab);
+    for (int64_t i = 4; i < n_frames; i++) {
+        ggml_backend_tensor_get(probs, row.data(), (item * n_frames + i) * n_vocab * sizeof(float), n_vocab * sizeof(float));
+        std::iota(top.begin(), top.end(), 0);
+        const int k = (int) std::min<int64_t>(beam_size, n_vocab);
+        std::partial_sort(top.begin(), top.begin() + k, top.end(), [&](int a, int b) { return row[a] > row[b]; });
+
+        std::map<std::vector<int>, beam_scores> next;
+        for (const auto &[prefix, scores]: beams) {
+            auto &same = next[prefix];
+            same.blank = log_add(same.blank, scores.total() + std::log(std::max(row[0], 1e-10f)));
+            for (int j = 0; j < k; j++) {
+                const int id = top[j];
+                if (id == 0) {
+                    continue;
+                }
+                const double logp = std::log(std::max(row[id], 1e-10f));
+                std::vector<int> extended = prefix;
+                extended.push_back(id);
+                auto &ext = next[extended];
+                if (!prefix.empty() && prefix.back() == id) {
+                    // a repeat only starts a new token after a blank, otherwise it collapses
+                    ext.token = log_add(ext.token, scores.blank + logp);
+                    auto &collapsed = next[prefix];
+                    collapsed.token = log_add(collapsed.token, scores.token + logp);
+                } else {
+                    ext.token = log_add(ext.token, scores.total() + logp);
+                }
+            }
+        }
+        std::vector<std::pair<std::vector<int>, beam_scores>> ranked(next.begin(), next.end());
+        const size_t n_keep = std::min<size_t>(beam_size, ranked.size());
+        std::partial_sort(ranked.begin(), ranked.begin() + n_keep, ranked.end(),
+                          [](const auto &a, const auto &b) { return a.second.total() > b.second.total(); });
+        ranked.resize(n_keep);
+        beams = std::map<std::vector<int>, beam_scores>(ranked.begin(), ranked.end());
+    }
+
+    std::vector<sense_voice_hypothesis> hypotheses;
+    for (const auto &[prefix, scores]: beams) {
+        hypotheses.push_back({prefix, (float) scores.total()});
+    }
+    std::sort(hypotheses.begin(), hypotheses.end(),
+              [](const auto &a, const auto &b) { return a.logprob > b.logprob; });
+    return hypotheses;
+}
+
 bool sense_voice_decode_internal(sense_voice_context &ctx,
                                  sense_voice_state &state,
                                  const int n_threads) {
@@ -111,6 +210,10 @@ bool sense_voice_decode_internal(sense_voice_context &ctx,
         {
             struct ggml_tensor *encoder_out = ggml_graph_get_tensor(gf, "encoder_out");
             ggml_backend_tensor_copy(state.encoder_out, encoder_out);
+            if (!state.logit_bias.empty()) {
+                struct ggml_tensor *logit_bias = ggml_graph_get_tensor(gf, "logit_bias");
+                ggml_backend_tensor_set(logit_bias, state.logit_bias.data(), 0, ggml_nbytes(logit_bias));
+            }
         }
 
         if (!ggml_graph_compute_helper(sched, gf, n_threads)) {
@@ -119,9 +222,108 @@ bool sense_voice_decode_internal(sense_voice_context &ctx,
         {
             ggml_tensor *argmax_logit = ggml_graph_node(gf, ggml_graph_n_nodes(gf) - 1);
             // TODO 临时处理，建议讨论后取其一
+            // the first frame of every item is the language slot, its <|nospeech|> probability
+            // tells how sure the model is that there is no speech at all
+            struct ggml_tensor *probs = ggml_graph_get_tensor(gf, "probs");
+            auto nospeech = ctx.vocab.token_to_id.find("<|nospeech|>");
+            auto no_speech_prob = [&](int64_t item) {
+                float p = 0.0f;
+                if (probs && nospeech != ctx.vocab.token_to_id.end()) {
+                    const size_t row = item * argmax_logit->ne[0];
+                    ggml_backend_tensor_get(probs, &p, (row * probs->ne[0] + nospeech->second) * sizeof(float), sizeof(float));
+                }
+                return p;
+            };
+            // with a temperature, resample every text frame of an item from softmax(logits / T),
+            // then average the log probabilities of the tokens the item emits (skipping blanks
+            // and repeats, like sense_voice_full_get_text). The 4 query frames keep their argmax
+            // ids, so retries don't change the language, emotion, event and itn tags.
+            // path_logprob gets the log probability of all text frames of the decoded path. ids
+            // may be shorter than the frames of an item, which leaves out the padding of a
+            // batched clip
+            auto sample = [&](int64_t item, std::vector<int> &ids, double *path_logprob = nullptr) {
+                if (!probs) {
+                    return 0.0f;
+                }
+                const int64_t n_vocab = probs->ne[0];
+                const int64_t n_item_frames = argmax_logit->ne[0];
+                std::vector<float> row(state.temperature > 0.0f ? n_vocab : 0);
+                std::vector<double> weights(row.size());
+                double sum_logprob = 0.0;
+                double sum_path = 0.0;
+                int n_emitted = 0;
+                for (size_t i = 4; i < ids.size(); i++) {
+                    const size_t offset = (item * n_item_frames + i) * n_vocab * sizeof(float);
+                    float p = 0.0f;
+                    if (state.temperature > 0.0f) {
+                        ggml_backend_tensor_get(probs, row.data(), offset, n_vocab * sizeof(float));
+                        // p^(1/T) underflows for small T, so work in log space relative to the
+                        // most likely token: exp((log p - log p_max) / T)
+                        const double max_logp = std::log(std::max((double) *std::max_element(row.begin(), row.end()), 1e-30));
+                        for (int64_t j = 0; j < n_vocab; j++) {
+                            const double logp = row[j] > 0.0f ? std::log((double) row[j]) : -INFINITY;
+                            weights[j] = std::exp((logp - max_logp) / state.temperature);
+                        }
+                        std::discrete_distribution<int> dist(weights.begin(), weights.end());
+                        ids[i] = dist(state.rng);
+                        p = row[ids[i]];
+                    } else {
+                        ggml_backend_tensor_get(probs, &p, offset + ids[i] * sizeof(float), sizeof(float));
+                    }
+                    const double logp = std::log(std::max(p, 1e-10f));
+                    sum_path += logp;
+                    if (!ids[i] || ids[i - 1] == ids[i]) {
+                        continue;
+                    }
+                    sum_logprob += logp;
+                    n_emitted++;
+                }
+                if (path_logprob) {
+                    *path_logprob = sum_path;
+                }
+                return n_emitted ? (float) (sum_logprob / n_emitted) : 0.0f;
+            };
             if(state.result_all.empty()) {
                 state.ids.resize(argmax_logit->ne[0]);
                 ggml_backend_tensor_get(argmax_logit, state.ids.data(), 0, sizeof(int) * argmax_logit->ne[0]);
+                state.no_speech_prob = no_speech_prob(0);
+                if (state.detect_language) {
+                    // language, emotion, event and itn only, the text stays empty
+                    state.ids.resize(std::min<size_t>(state.ids.size(), 4));
+                    state.avg_logprob = 0.0f;
+                    state.hypotheses.assign(1, sense_voice_hypothesis{});
+                } else {
+                    if (probs && state.probs_callback) {
+                        const int64_t n_vocab = probs->ne[0];
+                        std::vector<float> row(n_vocab);
+                        for (size_t i = 0; i < state.ids.size(); i++) {
+                            ggml_backend_tensor_get(probs, row.data(), i * n_vocab * sizeof(float), n_vocab * sizeof(float));
+                            state.probs_callback(&ctx, (int) i, row.data(), (int) n_vocab, state.probs_callback_user_data);
+                        }
+                    }
+                    double path_logprob = 0.0;
+                    state.avg_logprob = sample(0, state.ids, &path_logprob);
+                    const size_t n_frames = state.ids.size();
+                    sense_voice_cap_tokens(state.ids, state.max_tokens);
+                    state.hypotheses.clear();
+                    if (probs && state.n_beams > 1 && state.temperature <= 0.0f) {
+                        state.hypotheses = sense_voice_ctc_prefix_beam_search(probs, 0, n_frames, state.n_beams);
+                        if (state.max_tokens > 0) {
+                            for (auto &hypothesis: state.hypotheses) {
+                                hypothesis.tokens.resize(std::min<size_t>(hypothesis.tokens.size(), state.max_tokens));
+                            }
+                        }
+                    } else {
+                        sense_voice_hypothesis decoded;
+                        for (size_t i = 4; i < state.ids.size(); i++) {
+                            if (state.ids[i] && state.ids[i - 1] != state.ids[i]) {
+                                decoded.tokens.push_back(state.ids[i]);
+                            }
+                        }
+                        decoded.logprob = (float) path_logprob;
+                        state.hypotheses.push_back(decoded);
+                    }
+                }
             }
             else {
                 const int32_t n_logits = argmax_logit->ne[0] * argmax_logit->ne[1];
@@ -131,7 +333,19 @@ bool sense_voice_decode_internal(sense_voice_context &ctx,
                 for(int32_t i = 0; i < argmax_logit->ne[1]; i++)
                 {
                     int posL = i * argmax_logit->ne[0];
-                    state.result_all[state.segmentIDs[i]].tokens = std::vector<int>(temp_buffer.begin() + posL, temp_buffer.begin() + posL + argmax_logit->ne[0]);
+                    // only the frames of the clip's own audio, not those decoded from the padding
+                    const auto &clip_frames = state.result_all[state.segmentIDs[i]].n_frames;
+                    const int64_t n_frames = clip_frames > 0 ? std::min<int64_t>(clip_frames, argmax_logit->ne[0]) : argmax_logit->ne[0];
+                    state.result_all[state.segmentIDs[i]].tokens = std::vector<int>(temp_buffer.begin() + posL, temp_buffer.begin() + posL + n_frames);
+                    state.result_all[state.segmentIDs[i]].no_speech_prob = no_speech_prob(i);
+                    if (state.detect_language) {
+                        auto &tokens = state.result_all[state.segmentIDs[i]].tokens;
+                        tokens.resize(std::min<size_t>(tokens.size(), 4));
+                        state.result_all[state.segmentIDs[i]].avg_logprob = 0.0f;
+                        continue;
+                    }
+                    state.result_all[state.segmentIDs[i]].avg_logprob = sample(i, state.result_all[state.segmentIDs[i]].tokens);
+                    sense_voice_cap_tokens(state.result_all[state.segmentIDs[i]].tokens, state.max_tokens);
                 }
             }
         }
@@ -244,7 +244,7 @@ bool load_wav_file(const char *filename, int32_t *sampling_rate,
   std::ifstream is(filename, std::ifstream::binary);
   is.read(reinterpret_cast<char *>(&header), sizeof(header));
   if (!is) {
-    std::cout << "Failed to read " << filename;
+    std::cerr << "Failed to read " << filename << std::endl;
     return false;
   }
 
@@ -269,7 +269,7 @@ bool load_wav_file(const char *filename, int32_t *sampling_rate,
     memset(speech_buff, 0, sizeof(int16_t) * speech_len);
     is.read(reinterpret_cast<char *>(speech_buff), header.subchunk2_size);
     if (!is) {
-      std::cout << "Failed to read " << filename;
+      std::cerr << "Failed to read " << filename << std::endl;
       return false;
     }
 
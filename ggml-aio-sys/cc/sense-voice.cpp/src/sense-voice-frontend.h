@@ -43,32 +43,32 @@ struct WaveHeader {
   bool Validate() const {
     //                 F F I R
     if (chunk_id != 0x46464952) {
-      printf("Expected chunk_id RIFF. Given: 0x%08x\n", chunk_id);
+      fprintf(stderr, "Expected chunk_id RIFF. Given: 0x%08x\n", chunk_id);
       return false;
     }
     //               E V A W
     if (format != 0x45564157) {
-      printf("Expected format WAVE. Given: 0x%08x\n", format);
+      fprintf(stderr, "Expected format WAVE. Given: 0x%08x\n", format);
       return false;
     }
 
     if (subchunk1_id != 0x20746d66) {
-      printf("Expected subchunk1_id 0x20746d66. Given: 0x%08x\n", subchunk1_id);
+      fprintf(stderr, "Expected subchunk1_id 0x20746d66. Given: 0x%08x\n", subchunk1_id);
       return false;
     }
 
     if (subchunk1_size != 16) {  // 16 for PCM
-      printf("Expected subchunk1_size 16. Given: %d\n", subchunk1_size);
+      fprintf(stderr, "Expected subchunk1_size 16. Given: %d\n", subchunk1_size);
       return false;
     }
 
     if (audio_format != 1) {  // 1 for PCM
-      printf("Expected audio_format 1. Given: %d\n", audio_format);
+      fprintf(stderr, "Expected audio_format 1. Given: %d\n", audio_format);
       return false;
     }
 
     if (num_channels != 1) {  // we support only single channel for now
-      printf("Expected single channel. Given: %d\n", num_channels);
+      fprintf(stderr, "Expected single channel. Given: %d\n", num_channels);
       return false;
     }
     if (byte_rate != (sample_rate * num_channels * bits_per_sample / 8)) {
@@ -80,7 +80,7 @@ struct WaveHeader {
     }
 
     if (bits_per_sample != 16) {  // we support only 16 bits per sample
-      printf("Expected bits_per_sample 16. Given: %d\n", bits_per_sample);
+      fprintf(stderr, "Expected bits_per_sample 16. Given: %d\n", bits_per_sample);
       return false;
     }
     return true;
@@ -7,14 +7,17 @@
 #include "sense-voice-decoder.h"
 #include "sense-voice-encoder.h"
 #include "silero-vad.h"
+#include <algorithm>
 #include <cassert>
+#include <cstddef>
+#include <cstdint>
+#include <cstring>
 #include <functional>
 #include <thread>
 
 #define SENSE_VOICE_MAX_NODES 8192
 #define SENSE_VOICE_MAX_DECODERS 8
 #define SENSE_VOICE_CHUNK_SIZE 20
-#define SENSE_VOICE_FEATURES_DIM 560
 
 
 void sense_voice_batch_print_output(struct sense_voice_context *ctx, bool need_prefix, bool use_itn = false, bool refresh_self = false) {
@@ -23,14 +26,16 @@ void sense_voice_batch_print_output(struct sense_voice_context *ctx, bool need_p
     for (size_t i = 0; i < ctx->state->segmentIDs.size(); i++) {
         const int resultID = ctx->state->segmentIDs[i];
         const sense_voice_segment &result = ctx->state->result_all[resultID];
-        printf("[%.2f-%.2f]", result.t0 * 1.0 / SENSE_VOICE_SAMPLE_RATE, result.t1 * 1.0 / SENSE_VOICE_SAMPLE_RATE);
+        // one log line per segment, nothing is written to stdout directly
+        std::string line;
         for (size_t j = (need_prefix ? 0 : 4); j < result.tokens.size(); j++) {
             int id = result.tokens[j];
             if (!id || (j > 0 && result.tokens[j - 1] == id))
                 continue;
-            printf("%s", ctx->vocab.id_to_token[id].c_str());
+            line += ctx->vocab.id_to_token[id];
         }
-        if (!refresh_self) printf("\n");
+        SENSE_VOICE_LOG_INFO("[%.2f-%.2f]%s%s", result.t0 * 1.0 / SENSE_VOICE_SAMPLE_RATE, result.t1 * 1.0 / SENSE_VOICE_SAMPLE_RATE,
+                             line.c_str(), refresh_self ? "" : "\n");
     }
 }
 
@@ -60,39 +65,125 @@ const char *sense_voice_lang_str(int id) {
     return nullptr;
 }
 
-static ggml_backend_buffer_type_t sense_voice_default_buffer_type(const sense_voice_context_params &params) {
+// the first GPU device, of the backend named by params.gpu_backend if set, or nullptr
+static ggml_backend_dev_t sense_voice_gpu_device(const sense_voice_context_params &params) {
     if (!params.use_gpu) {
-        return ggml_backend_cpu_buffer_type();
+        return nullptr;
     }
     for (size_t i = 0; i < ggml_backend_dev_count(); ++i) {
         ggml_backend_dev_t dev = ggml_backend_dev_get(i);
-        if (ggml_backend_dev_type(dev) == GGML_BACKEND_DEVICE_TYPE_GPU) {
-            SENSE_VOICE_LOG_INFO("%s: using device %s (%s)\n", __func__, ggml_backend_dev_name(dev), ggml_backend_dev_description(dev));
-            return ggml_backend_dev_buffer_type(dev);
+        if (ggml_backend_dev_type(dev) != GGML_BACKEND_DEVICE_TYPE_GPU) {
+            continue;
+        }
+        if (params.gpu_backend &&
+            strcmp(ggml_backend_reg_name(ggml_backend_dev_backend_reg(dev)), params.gpu_backend) != 0) {
+            continue;
         }
+        return dev;
+    }
+    if (params.gpu_backend) {
+        SENSE_VOICE_LOG_ERROR("%s: no %s device found\n", __func__, params.gpu_backend);
+    }
+    return nullptr;
+}
+
+static ggml_backend_buffer_type_t sense_voice_default_buffer_type(const sense_voice_context_params &params) {
+    ggml_backend_dev_t dev = sense_voice_gpu_device(params);
+    if (dev) {
+        SENSE_VOICE_LOG_INFO("%s: using device %s (%s)\n", __func__, ggml_backend_dev_name(dev), ggml_backend_dev_description(dev));
+        return ggml_backend_dev_buffer_type(dev);
     }
 
     return ggml_backend_cpu_buffer_type();
 }
 
 static ggml_backend_t sense_voice_backend_init_gpu(const sense_voice_context_params &params) {
-    ggml_backend_t result = nullptr;
-
-    if (params.use_gpu) {
-        for (size_t i = 0; i < ggml_backend_dev_count(); ++i) {
-            ggml_backend_dev_t dev = ggml_backend_dev_get(i);
-            if (ggml_backend_dev_type(dev) == GGML_BACKEND_DEVICE_TYPE_GPU) {
-                SENSE_VOICE_LOG_INFO("%s: using %s backend\n", __func__, ggml_backend_dev_name(dev));
-                ggml_backend_t result = ggml_backend_dev_init(dev, nullptr);
-                if (!result) {
-                    SENSE_VOICE_LOG_ERROR("%s: failed to initialize %s backend\n", __func__, ggml_backend_dev_name(dev));
-                }
-                return result;
-            }
+    ggml_backend_dev_t dev = sense_voice_gpu_device(params);
+    if (!dev) {
+        return nullptr;
+    }
+    SENSE_VOICE_LOG_INFO("%s: using %s backend\n", __func__, ggml_backend_dev_name(dev));
+    ggml_backend_t result = ggml_backend_dev_init(dev, nullptr);
+    if (!result) {
+        SENSE_VOICE_LOG_ERROR("%s: failed to initialize %s backend\n", __func__, ggml_backend_dev_name(dev));
+    }
+    return result;
+}
+
+// render a scalar gguf value as a string, arrays are skipped (returns false)
+static bool sense_voice_gguf_kv_to_str(const struct gguf_context *gguf_ctx, int i, std::string &out) {
+    switch (gguf_get_kv_type(gguf_ctx, i)) {
+        case GGUF_TYPE_STRING:  out = gguf_get_val_str(gguf_ctx, i); return true;
+        case GGUF_TYPE_UINT8:   out = std::to_string(gguf_get_val_u8(gguf_ctx, i)); return true;
+        case GGUF_TYPE_INT8:    out = std::to_string(gguf_get_val_i8(gguf_ctx, i)); return true;
+        case GGUF_TYPE_UINT16:  out = std::to_string(gguf_get_val_u16(gguf_ctx, i)); return true;
+        case GGUF_TYPE_INT16:   out = std::to_string(gguf_get_val_i16(gguf_ctx, i)); return true;
+        case GGUF_TYPE_UINT32:  out = std::to_string(gguf_get_val_u32(gguf_ctx, i)); return true;
+        case GGUF_TYPE_INT32:   out = std::to_string(gguf_get_val_i32(gguf_ctx, i)); return true;
+        case GGUF_TYPE_UINT64:  out = std::to_string(gguf_get_val_u64(gguf_ctx, i)); return true;
+        case GGUF_TYPE_INT64:   out = std::to_string(gguf_get_val_i64(gguf_ctx, i)); return true;
+        case GGUF_TYPE_FLOAT32: out = std::to_string(gguf_get_val_f32(gguf_ctx, i)); return true;
+        case GGUF_TYPE_FLOAT64: out = std::to_string(gguf_get_val_f64(gguf_ctx, i)); return true;
+        case GGUF_TYPE_BOOL:    out = gguf_get_val_bool(gguf_ctx, i) ? "true" : "false"; return true;
+        default:                return false;
+    }
+}
+
+// read the frontend.* keys written by convert-pt-to-gguf.py, keys missing in older
+// conversions keep the SenseVoiceSmall defaults of sense_voice_hparams
+static void sense_voice_load_frontend_hparams(const struct gguf_context *gguf_ctx, sense_voice_hparams &hparams) {
+    const auto read_i32 = [&](const char *key, int &out) {
+        const int idx = gguf_find_key(gguf_ctx, key);
+        if (idx != -1) {
+            out = gguf_get_val_i32(gguf_ctx, idx);
         }
+    };
+    read_i32("frontend.sample_rate", hparams.sample_rate);
+    read_i32("frontend.num_mels", hparams.n_mels);
+    read_i32("frontend.frame_length", hparams.frame_length);
+    read_i32("frontend.frame_shift", hparams.frame_shift);
+    read_i32("frontend.lfr_m", hparams.lfr_m);
+    read_i32("frontend.lfr_n", hparams.lfr_n);
+    const int window_idx = gguf_find_key(gguf_ctx, "frontend.window");
+    if (window_idx != -1) {
+        hparams.window = gguf_get_val_str(gguf_ctx, window_idx);
     }
+}
 
-    return result;
+// the fbank frontend runs at SENSE_VOICE_SAMPLE_RATE with a hamming window, and the CMVN
+// statistics are compiled in for cmvn_length = n_mels * lfr_m values per encoder frame
+static bool sense_voice_frontend_supported(const sense_voice_hparams &hparams) {
+    if (hparams.sample_rate != SENSE_VOICE_SAMPLE_RATE) {
+        SENSE_VOICE_LOG_ERROR("%s: unsupported frontend sample rate %d, expected %d\n", __func__,
+                              hparams.sample_rate, SENSE_VOICE_SAMPLE_RATE);
+        return false;
+    }
+    if (hparams.window != "hamming") {
+        SENSE_VOICE_LOG_ERROR("%s: unsupported frontend window '%s'\n", __func__, hparams.window.c_str());
+        return false;
+    }
+    if (hparams.n_mels <= 0 || hparams.lfr_m <= 0 || hparams.lfr_n <= 0 ||
+        hparams.frame_length <= 0 || hparams.frame_shift <= 0) {
+        SENSE_VOICE_LOG_ERROR("%s: invalid frontend settings\n", __func__);
+        return false;
+    }
+    if (hparams.n_mels * hparams.lfr_m != cmvn_length) {
+        SENSE_VOICE_LOG_ERROR("%s: frontend produces %d values per frame (n_mels %d * lfr_m %d), the CMVN statistics have %d\n",
+                              __func__, hparams.n_mels * hparams.lfr_m, hparams.n_mels, hparams.lfr_m, cmvn_length);
+        return false;
+    }
+    return true;
+}
+
+static struct sense_voice_frontend_params sense_voice_frontend_params_of(const sense_voice_hparams &hparams) {
+    return {
+            /*.sample_rate     = */ hparams.sample_rate,
+            /*.n_mels          = */ hparams.n_mels,
+            /*.frame_length_ms = */ hparams.frame_length,
+            /*.frame_shift_ms  = */ hparams.frame_shift,
+            /*.lfr_m           = */ hparams.lfr_m,
+            /*.lfr_n           = */ hparams.lfr_n,
+    };
 }
 
 // load the model from a gguf file
@@ -117,6 +208,14 @@ bool sense_voice_model_load(const char *path_model, sense_voice_context &sctx) {
         const int n_kv = gguf_get_n_kv(gguf_ctx);
 
         SENSE_VOICE_LOG_DEBUG("%s: n_kv: %d\n", __func__, n_kv);
+
+        sctx.model.metadata.clear();
+        for (int i = 0; i < n_kv; i++) {
+            std::string value;
+            if (sense_voice_gguf_kv_to_str(gguf_ctx, i, value)) {
+                sctx.model.metadata.emplace_back(gguf_get_key(gguf_ctx, i), value);
+            }
+        }
     }
 
     SENSE_VOICE_LOG_INFO("%s: loading model\n", __func__);
@@ -149,6 +248,11 @@ bool sense_voice_model_load(const char *path_model, sense_voice_context &sctx) {
         hparams.n_tp_encoder_layers = gguf_get_val_i32(
                 gguf_ctx, gguf_find_key(gguf_ctx, "encoder.tp_blocks"));
 
+        sense_voice_load_frontend_hparams(gguf_ctx, hparams);
+        if (!sense_voice_frontend_supported(hparams)) {
+            return false;
+        }
+
         if (sense_voice.model_type == "SenseVoiceLarge") {
             hparams.n_decoder_hidden_state =
                     gguf_get_val_i32(gguf_ctx, gguf_find_key(gguf_ctx, "model.inner_dim"));
@@ -191,6 +295,8 @@ bool sense_voice_model_load(const char *path_model, sense_voice_context &sctx) {
                                  hparams.n_decoder_layers);
         }
         SENSE_VOICE_LOG_INFO("%s: n_mels  = %d\n", __func__, hparams.n_mels);
+        SENSE_VOICE_LOG_INFO("%s: frame  = %d ms, shift %d ms, lfr_m %d, lfr_n %d\n", __func__,
+                             hparams.frame_length, hparams.frame_shift, hparams.lfr_m, hparams.lfr_n);
         SENSE_VOICE_LOG_INFO("%s: ftype  = %d\n", __func__,
                              sense_voice.hparams.ftype);
     }
@@ -418,6 +524,9 @@ static std::vector<ggml_backend_t> sense_voice_backend_init(
 
     if (backend_gpu) {
         result.push_back(backend_gpu);
+    } else if (params.use_gpu && params.gpu_backend) {
+        // an explicitly requested backend doesn't fall back to the CPU
+        return result;
     }
 
     for (size_t i = 0; i < ggml_backend_dev_count(); ++i) {
@@ -555,6 +664,24 @@ void sense_voice_free_state(struct sense_voice_state *state) {
     }
 }
 
+void sense_voice_free(struct sense_voice_context *ctx) {
+    if (ctx) {
+        sense_voice_free_state(ctx->state);
+        ctx->state = nullptr;
+
+        ggml_free(ctx->model.ctx);
+        ggml_backend_buffer_free(ctx->model.buffer);
+
+        if (ctx->model.model) {
+            delete ctx->model.model->encoder;
+            delete ctx->model.model;
+        }
+        delete ctx->vad_model.model;
+
+        delete ctx;
+    }
+}
+
 static size_t sense_voice_sched_size(struct sense_voice_sched &sched) {
     size_t size = sched.meta.size();
     for (int i = 0; i < ggml_backend_sched_get_n_backends(sched.sched); ++i) {
@@ -590,12 +717,18 @@ struct sense_voice_state *sense_voice_init_state(sense_voice_context *ctx) {
 
     // set input
     {
-        // init features
+        // init features, with the frontend settings the model was trained with
+        const auto &hparams = ctx->model.hparams;
+        state->feature.n_mel = hparams.n_mels;
+        state->feature.frame_size = hparams.frame_length;
+        state->feature.frame_step = hparams.frame_shift;
+        state->feature.lfr_m = hparams.lfr_m;
+        state->feature.lfr_n = hparams.lfr_n;
         state->feature.n_len = SENSE_VOICE_CHUNK_SIZE;
         state->feature.ctx = ggml_init({ggml_tensor_overhead(), nullptr, true});
         state->feature.tensor = ggml_new_tensor_2d(state->feature.ctx,
                                                    GGML_TYPE_F32,
-                                                   SENSE_VOICE_FEATURES_DIM,
+                                                   hparams.n_mels * hparams.lfr_m,
                                                    state->feature.n_len);
     }
 
@@ -677,15 +810,47 @@ struct sense_voice_context *sense_voice_small_init_from_file_with_params(const c
     }
 
     ctx->state = sense_voice_init_state(ctx);
-    //    if (!ctx->state) {
-    //        sense_voice_small_free(ctx);
-    //        return nullptr;
-    //    }
+    if (!ctx->state) {
+        sense_voice_free(ctx);
+        return nullptr;
+    }
 
     return ctx;
 }
 
 
+// upload state->feature.data as the encoder input tensor, replacing the previous one
+static void sense_voice_set_feature_input(struct sense_voice_context *ctx,
+                                          struct sense_voice_state *state) {
+    if (state->feature.buffer) {
+        ggml_backend_buffer_free(state->feature.buffer);
+        state->feature.buffer = nullptr;
+    }
+    if (state->feature.ctx) {
+        ggml_free(state->feature.ctx);
+        state->feature.ctx = nullptr;
+    }
+
+    // init features
+    state->feature.n_len = state->feature.data.size() / (state->feature.n_mel * state->feature.lfr_m);
+    state->feature.ctx = ggml_init({ggml_tensor_overhead(), nullptr, true});
+    state->feature.tensor = ggml_new_tensor_2d(state->feature.ctx,
+                                               GGML_TYPE_F32,
+                                               state->feature.lfr_m * state->feature.n_mel,
+                                               state->feature.n_len);
+    state->feature.buffer = ggml_backend_alloc_buffer(state->backends[0],
+                                                      ggml_nbytes(state->feature.tensor) + ggml_backend_get_alignment(state->backends[0]));
+    auto alloc = ggml_tallocr_new(state->feature.buffer);
+    ggml_tallocr_alloc(&alloc, state->feature.tensor);
+
+    auto &feature = state->feature.tensor;
+
+    assert(state->feature.n_mel == ctx->model.hparams.n_mels);
+
+    ggml_backend_tensor_set(feature, state->feature.data.data(), 0,
+                            ggml_nbytes(feature));
+}
+
 int sense_voice_pcm_to_feature_with_state(struct sense_voice_context *ctx,
                                           struct sense_voice_state *state,
                                           std::vector<double> &pcmf32,
@@ -704,44 +869,39 @@ int sense_voice_pcm_to_feature_with_state(struct sense_voice_context *ctx,
 
     state->t_feature_us = ggml_time_us() - t_start_us;
 
-    // set input
-    {
-        // init features
-        state->feature.n_len = state->feature.data.size() / (state->feature.n_mel * state->feature.lfr_m);
-        state->feature.ctx = ggml_init({ggml_tensor_overhead(), nullptr, true});
-        state->feature.tensor = ggml_new_tensor_2d(state->feature.ctx,
-                                                   GGML_TYPE_F32,
-                                                   state->feature.lfr_m * state->feature.n_mel,
-                                                   state->feature.n_len);
-        state->feature.buffer = ggml_backend_alloc_buffer(state->backends[0],
-                                                          ggml_nbytes(state->feature.tensor) + ggml_backend_get_alignment(state->backends[0]));
-        auto alloc = ggml_tallocr_new(state->feature.buffer);
-        ggml_tallocr_alloc(&alloc, state->feature.tensor);
-
-        auto &feature = state->feature.tensor;
-
-        assert(state->feature.n_mel == ctx->model.hparams.n_mels);
-
-        ggml_backend_tensor_set(feature, state->feature.data.data(), 0,
-                                ggml_nbytes(feature));
-
-        //        state->feature.tensor = ggml_transpose(state->feature.ctx, state->feature.tensor);
-    }
+    sense_voice_set_feature_input(ctx, state);
     SENSE_VOICE_LOG_DEBUG("%s: calculate fbank and cmvn takes %.3f ms\n", __func__,
                           state->t_feature_us / 1000.0);
     return 0;
 }
 
-int sense_voice_full_with_state(
+static void sense_voice_report_progress(struct sense_voice_context *ctx,
+                                        struct sense_voice_state *state,
+                                        const struct sense_voice_full_params &params,
+                                        int progress,
+                                        const char *stage,
+                                        int64_t t_stage_us) {
+    if (params.print_progress) {
+        if (params.progress_style == SENSE_VOICE_PROGRESS_FULL) {
+            SENSE_VOICE_LOG_INFO("%s: progress = %3d%% (%s took %.2f ms)\n", __func__, progress, stage, t_stage_us / 1000.0);
+        } else {
+            SENSE_VOICE_LOG_INFO("%s: progress = %3d%%\n", __func__, progress);
+        }
+    }
+    if (params.progress_callback) {
+        params.progress_callback(ctx, state, progress, params.progress_callback_user_data);
+    }
+}
+
+static bool sense_voice_aborted(const struct sense_voice_full_params &params) {
+    return params.abort_callback && params.abort_callback(params.abort_callback_user_data);
+}
+
+// run the encoder and the CTC decoder on the features already set in the state
+static int sense_voice_encode_decode(
         struct sense_voice_context *ctx,
         struct sense_voice_state *state,
-        struct sense_voice_full_params params,
-        std::vector<double> pcmf32,
-        int n_samples) {
-    // compute features (fbank + cmvn)
-    if (n_samples > 0) {
-        sense_voice_pcm_to_feature_with_state(ctx, state, pcmf32, params.debug_mode, params.n_threads);
-    }
+        const struct sense_voice_full_params &params) {
     // initialize the decoders
     int n_decoders = 1;
 
@@ -766,16 +926,66 @@ int sense_voice_full_with_state(
     }
     state->exp_n_audio_ctx = params.audio_ctx;
 
+    // let the CPU backend check the abort callback between graph nodes
+    for (auto &backend: state->backends) {
+        if (ggml_backend_is_cpu(backend)) {
+            ggml_backend_cpu_set_abort_callback(backend, params.abort_callback, params.abort_callback_user_data);
+        }
+    }
+    // and remove it again on every return: its user data usually lives on the caller's stack,
+    // and later graphs on these backends, e.g. the VAD's, must not call into it
+    struct abort_callback_reset {
+        std::vector<ggml_backend_t> &backends;
+        ~abort_callback_reset() {
+            for (auto &backend: backends) {
+                if (ggml_backend_is_cpu(backend)) {
+                    ggml_backend_cpu_set_abort_callback(backend, nullptr, nullptr);
+                }
+            }
+        }
+    } reset_abort_callback{state->backends};
+
     // encode audio features starting at offset seek
+    int64_t t_stage_us = ggml_time_us();
     if (!sense_voice_encode_internal(*ctx, *state, params.n_threads)) {
+        if (sense_voice_aborted(params)) {
+            SENSE_VOICE_LOG_ERROR("%s: aborted while encoding\n", __func__);
+            return -7;
+        }
         SENSE_VOICE_LOG_ERROR("%s: failed to encode\n", __func__);
         return -6;
     }
+    sense_voice_report_progress(ctx, state, params, 90, "encode", ggml_time_us() - t_stage_us);
+    if (sense_voice_aborted(params)) {
+        SENSE_VOICE_LOG_ERROR("%s: aborted after encoding\n", __func__);
+        return -7;
+    }
     // encode audio features starting at offset seek
+    t_stage_us = ggml_time_us();
+    state->temperature = params.temperature;
+    state->n_beams = params.strategy == SENSE_VOICE_SAMPLING_BEAM_SEARCH ? std::max(1, params.beam_search.beam_size) : 1;
+    state->probs_callback = params.probs_callback;
+    state->probs_callback_user_data = params.probs_callback_user_data;
+    state->detect_language = params.detect_language;
+    state->max_tokens = params.max_tokens;
+    if (params.logit_bias) {
+        state->logit_bias.assign(params.logit_bias, params.logit_bias + ctx->model.hparams.n_vocab);
+    } else {
+        state->logit_bias.clear();
+    }
     if (!sense_voice_decode_internal(*ctx, *state, params.n_threads)) {
+        if (sense_voice_aborted(params)) {
+            SENSE_VOICE_LOG_ERROR("%s: aborted while decoding\n", __func__);
+            return -7;
+        }
         SENSE_VOICE_LOG_ERROR("%s: failed to decode\n", __func__);
         return -6;
     }
+    sense_voice_report_progress(ctx, state, params, 100, "decode", ggml_time_us() - t_stage_us);
+
+    if (params.print_timestamps) {
+        SENSE_VOICE_LOG_INFO("[%.2f --> %.2f] %s\n", 0.0, state->duration, sense_voice_full_get_text(ctx, false));
+    }
 
     SENSE_VOICE_LOG_DEBUG("\n%s: decoder audio use %f s, rtf is %f. \n\n",
                           __func__,
@@ -785,13 +995,149 @@ int sense_voice_full_with_state(
     return 0;
 }
 
+int sense_voice_full_with_state(
+        struct sense_voice_context *ctx,
+        struct sense_voice_state *state,
+        struct sense_voice_full_params params,
+        std::vector<double> pcmf32,
+        int n_samples) {
+    int64_t t_stage_us = ggml_time_us();
+    state->duration = (float) n_samples / SENSE_VOICE_SAMPLE_RATE;
+    state->t_encode_us = 0;
+    state->t_decode_us = 0;
+    // compute features (fbank + cmvn)
+    if (n_samples > 0) {
+        sense_voice_pcm_to_feature_with_state(ctx, state, pcmf32, params.debug_mode, params.n_threads);
+    }
+    sense_voice_report_progress(ctx, state, params, 10, "features", ggml_time_us() - t_stage_us);
+    if (sense_voice_aborted(params)) {
+        SENSE_VOICE_LOG_ERROR("%s: aborted after computing features\n", __func__);
+        return -7;
+    }
+    return sense_voice_encode_decode(ctx, state, params);
+}
+
 int sense_voice_full_parallel(struct sense_voice_context *ctx,
                               const sense_voice_full_params *params,
                               const double *samples,
                               int n_samples,
                               int n_processors) {
     std::vector<double> pcmf32_vec(samples, samples + n_samples);
-    return sense_voice_full_with_state(ctx, ctx->state, *params, pcmf32_vec, n_samples);
+    return sense_voice_full_with_state(ctx, ctx->state, *params, std::move(pcmf32_vec), n_samples);
+}
+
+size_t sense_voice_full_params_layout(const char *field) {
+    if (field == nullptr) {
+        return sizeof(sense_voice_full_params);
+    }
+#define SENSE_VOICE_FIELD(name)                        \
+    if (strcmp(field, #name) == 0) {                   \
+        return offsetof(sense_voice_full_params, name); \
+    }
+    SENSE_VOICE_FIELD(strategy)
+    SENSE_VOICE_FIELD(n_threads)
+    SENSE_VOICE_FIELD(language)
+    SENSE_VOICE_FIELD(n_max_text_ctx)
+    SENSE_VOICE_FIELD(offset_ms)
+    SENSE_VOICE_FIELD(duration_ms)
+    SENSE_VOICE_FIELD(no_context)
+    SENSE_VOICE_FIELD(no_timestamps)
+    SENSE_VOICE_FIELD(single_segment)
+    SENSE_VOICE_FIELD(print_progress)
+    SENSE_VOICE_FIELD(print_timestamps)
+    SENSE_VOICE_FIELD(progress_style)
+    SENSE_VOICE_FIELD(debug_mode)
+    SENSE_VOICE_FIELD(audio_ctx)
+    SENSE_VOICE_FIELD(greedy.best_of)
+    SENSE_VOICE_FIELD(length_penalty)
+    SENSE_VOICE_FIELD(temperature)
+    SENSE_VOICE_FIELD(beam_search.beam_size)
+    SENSE_VOICE_FIELD(beam_search.patience)
+    SENSE_VOICE_FIELD(progress_callback)
+    SENSE_VOICE_FIELD(progress_callback_user_data)
+    SENSE_VOICE_FIELD(abort_callback)
+    SENSE_VOICE_FIELD(abort_callback_user_data)
+    SENSE_VOICE_FIELD(probs_callback)
+    SENSE_VOICE_FIELD(probs_callback_user_data)
+    SENSE_VOICE_FIELD(detect_language)
+    SENSE_VOICE_FIELD(max_tokens)
+    SENSE_VOICE_FIELD(logit_bias)
+#undef SENSE_VOICE_FIELD
+    return SIZE_MAX;
+}
+
+int sense_voice_model_n_vocab(struct sense_voice_context *ctx) {
+    return ctx->model.hparams.n_vocab;
+}
+
+int sense_voice_model_n_mels(struct sense_voice_context *ctx) {
+    return ctx->model.hparams.n_mels;
+}
+
+int sense_voice_feature_dim(struct sense_voice_context *ctx) {
+    return ctx->model.hparams.n_mels * ctx->model.hparams.lfr_m;
+}
+
+bool sense_voice_use_itn(struct sense_voice_context *ctx) {
+    return ctx->params.use_itn;
+}
+
+void sense_voice_set_use_itn(struct sense_voice_context *ctx, bool use_itn) {
+    ctx->params.use_itn = use_itn;
+}
+
+struct sense_voice_frontend_params sense_voice_model_frontend(struct sense_voice_context *ctx) {
+    return sense_voice_frontend_params_of(ctx->model.hparams);
+}
+
+int sense_voice_pcm_to_features(struct sense_voice_context *ctx,
+                                const double *samples,
+                                int n_samples,
+                                int n_threads) {
+    if (!ctx->state || n_samples <= 0) {
+        return -1;
+    }
+    std::vector<double> pcmf32(samples, samples + n_samples);
+    sense_voice_pcm_to_feature_with_state(ctx, ctx->state, pcmf32, false, n_threads);
+    ctx->state->duration = (float) n_samples / SENSE_VOICE_SAMPLE_RATE;
+    const auto &feature = ctx->state->feature;
+    return (int) (feature.data.size() / (feature.n_mel * feature.lfr_m));
+}
+
+int sense_voice_get_features(struct sense_voice_context *ctx, float *out, int n_max) {
+    if (!ctx->state) {
+        return 0;
+    }
+    const std::vector<float> &data = ctx->state->feature.data;
+    const int n = std::min((int) data.size(), n_max);
+    if (out && n > 0) {
+        std::copy(data.begin(), data.begin() + n, out);
+    }
+    return (int) data.size();
+}
+
+int sense_voice_full_parallel_features(struct sense_voice_context *ctx,
+                                       const struct sense_voice_full_params *params,
+                                       const float *features,
+                                       int n_frames,
+                                       int n_dim) {
+    sense_voice_state *state = ctx->state;
+    if (!state || n_frames <= 0) {
+        return -1;
+    }
+    if (n_dim != sense_voice_feature_dim(ctx)) {
+        SENSE_VOICE_LOG_ERROR("%s: feature dimension %d doesn't match the model's %d\n", __func__, n_dim, sense_voice_feature_dim(ctx));
+        return -8;
+    }
+    const auto &hparams = ctx->model.hparams;
+    state->feature.data.assign(features, features + (size_t) n_frames * n_dim);
+    state->duration = (float) n_frames * hparams.lfr_n * hparams.frame_shift / 1000.0f;
+    state->t_feature_us = 0;
+    state->t_encode_us = 0;
+    state->t_decode_us = 0;
+    sense_voice_set_feature_input(ctx, state);
+    sense_voice_report_progress(ctx, state, *params, 10, "features", 0);
+    return sense_voice_encode_decode(ctx, state, *params);
 }
 
 // void sense_voice_print_output(struct sense_voice_context *ctx, bool need_prefix, bool use_itn, bool refresh_self) {
@@ -813,6 +1159,14 @@ int sense_voice_batch_pcm_to_feature_with_state(struct sense_voice_context *ctx,
     struct sense_voice_cmvn cmvn;
     cmvn.cmvn_means = std::vector<float>(CMVN_MEANS, CMVN_MEANS + cmvn_length);
     cmvn.cmvn_vars = std::vector<float>(CMVN_VARS, CMVN_VARS + cmvn_length);
+    if (state->feature.buffer) {
+        ggml_backend_buffer_free(state->feature.buffer);
+        state->feature.buffer = nullptr;
+    }
+    if (state->feature.ctx) {
+        ggml_free(state->feature.ctx);
+        state->feature.ctx = nullptr;
+    }
     state->feature.input_data.clear();
     size_t max_len = 0;
     for (size_t segmentID: state->segmentIDs)
@@ -898,6 +1252,17 @@ int sense_voice_batch_full(struct sense_voice_context *ctx, const sense_voice_fu
     //
     //
     //    // encode audio features starting at offset seek
+    state->temperature = params.temperature;
+    state->n_beams = params.strategy == SENSE_VOICE_SAMPLING_BEAM_SEARCH ? std::max(1, params.beam_search.beam_size) : 1;
+    state->probs_callback = params.probs_callback;
+    state->probs_callback_user_data = params.probs_callback_user_data;
+    state->detect_language = params.detect_language;
+    state->max_tokens = params.max_tokens;
+    if (params.logit_bias) {
+        state->logit_bias.assign(params.logit_bias, params.logit_bias + ctx->model.hparams.n_vocab);
+    } else {
+        state->logit_bias.clear();
+    }
     if (!sense_voice_decode_internal(*ctx, *state, params.n_threads)) {
         SENSE_VOICE_LOG_ERROR("%s: failed to decode\n", __func__);
         return -6;
@@ -954,7 +1319,97 @@ int sense_voice_batch_pcmf(struct sense_voice_context *ctx, const sense_voice_fu
     return 0;
 }
 
+// number of encoder frames, the 4 query frames included, fbank_lfr_cmvn_feature produces for n_samples samples
+static size_t sense_voice_n_encoder_frames(const sense_voice_feature &feature, size_t n_samples) {
+    const int64_t n_per_ms = SENSE_VOICE_SAMPLE_RATE / 1000;
+    const int64_t n_len = 1 + ((int64_t) n_samples - feature.frame_size * n_per_ms) / (feature.frame_step * n_per_ms);
+    return 4 + (size_t) ((std::max<int64_t>(n_len, 0) + feature.lfr_n - 1) / feature.lfr_n);
+}
+
+int sense_voice_full_parallel_batch(struct sense_voice_context *ctx,
+                                    const struct sense_voice_full_params *params,
+                                    const double *const *samples,
+                                    const int *n_samples,
+                                    int n_clips) {
+    sense_voice_state *state = ctx->state;
+    if (!state || n_clips <= 0) {
+        return -1;
+    }
+    int64_t t_stage_us = ggml_time_us();
+    state->result_all.clear();
+    state->segmentIDs.clear();
+    state->batch_results.clear();
+    size_t max_len = 0;
+    for (int i = 0; i < n_clips; i++) {
+        if (n_samples[i] <= 0) {
+            return -1;
+        }
+        sense_voice_segment clip;
+        clip.t0 = 0;
+        clip.t1 = n_samples[i];
+        clip.samples.assign(samples[i], samples[i] + n_samples[i]);
+        state->result_all.push_back(std::move(clip));
+        state->segmentIDs.push_back(i);
+        max_len = std::max(max_len, (size_t) n_samples[i]);
+    }
+    state->duration = (float) max_len / SENSE_VOICE_SAMPLE_RATE;
+    state->t_feature_us = 0;
+    state->t_encode_us = 0;
+    state->t_decode_us = 0;
+    // pads every clip with silence to the longest one
+    sense_voice_batch_pcm_to_feature_with_state(ctx, state, params->debug_mode, params->n_threads);
+    for (auto &clip: state->result_all) {
+        // the decoder ignores the frames decoded from the padding
+        clip.n_frames = sense_voice_n_encoder_frames(state->feature, clip.t1);
+    }
+    sense_voice_report_progress(ctx, state, *params, 10, "features", ggml_time_us() - t_stage_us);
+
+    int ret = -7;
+    if (!sense_voice_aborted(*params)) {
+        // the per-clip lines are printed below, state->ids doesn't hold a result
+        sense_voice_full_params batch_params = *params;
+        batch_params.print_timestamps = false;
+        state->ids.clear();
+        ret = sense_voice_encode_decode(ctx, state, batch_params);
+    } else {
+        SENSE_VOICE_LOG_ERROR("%s: aborted after computing features\n", __func__);
+    }
+    if (ret == 0) {
+        if (params->print_timestamps) {
+            sense_voice_batch_print_output(ctx, false, ctx->params.use_itn);
+        }
+        for (auto &clip: state->result_all) {
+            clip.samples.clear();
+            clip.samples.shrink_to_fit();
+        }
+        state->batch_results = std::move(state->result_all);
+    }
+    // an empty result_all selects the single input path of the decoder again
+    state->result_all.clear();
+    state->segmentIDs.clear();
+    return ret;
+}
+
+int sense_voice_batch_n_results(struct sense_voice_context *ctx) {
+    return ctx->state ? (int) ctx->state->batch_results.size() : 0;
+}
+
+bool sense_voice_batch_select(struct sense_voice_context *ctx, int i) {
+    sense_voice_state *state = ctx->state;
+    if (!state || i < 0 || (size_t) i >= state->batch_results.size()) {
+        return false;
+    }
+    const sense_voice_segment &clip = state->batch_results[i];
+    state->ids = clip.tokens;
+    state->no_speech_prob = clip.no_speech_prob;
+    state->avg_logprob = clip.avg_logprob;
+    state->hypotheses.clear();
+    state->duration = (float) clip.t1 / SENSE_VOICE_SAMPLE_RATE;
+    return true;
+}
+
 const char *sense_voice_full_get_text(struct sense_voice_context *ctx, bool need_prefix) {
+    ctx->state->full_text.clear();
     for (size_t i = (need_prefix ? 0 : 4); i < ctx->state->ids.size(); i++) {
         int id = ctx->state->ids[i];
         if (i > 0 && ctx->state->ids[i - 1] == ctx->state->ids[i])
@@ -965,7 +1420,352 @@ const char *sense_voice_full_get_text(struct sense_voice_context *ctx, bool need
     return ctx->state->full_text.c_str();
 }
 
+int sense_voice_full_n_segments(struct sense_voice_context *ctx) {
+    if (!ctx->state) {
+        return 0;
+    }
+    // CTC decoding has no segmentation of its own, the whole input is decoded as one segment
+    return ctx->state->ids.empty() ? 0 : 1;
+}
+
+int sense_voice_full_get_tokens(struct sense_voice_context *ctx, int *ids, int n_max) {
+    if (!ctx->state) {
+        return 0;
+    }
+    const auto &state_ids = ctx->state->ids;
+    int n = 0;
+    for (size_t i = 0; i < state_ids.size(); i++) {
+        if (!state_ids[i] || (i > 0 && state_ids[i - 1] == state_ids[i]))
+            continue;
+        if (ids && n < n_max) {
+            ids[n] = state_ids[i];
+        }
+        n++;
+    }
+    return n;
+}
+
+int sense_voice_full_get_token_times(struct sense_voice_context *ctx, int64_t *t0, int64_t *t1, int n_max) {
+    if (!ctx->state) {
+        return 0;
+    }
+    // the encoder input starts with 4 query frames (language, event, emotion, itn),
+    // every following frame covers lfr_n feature frames of frame_shift ms each
+    const int n_query = 4;
+    const auto &hparams = ctx->model.hparams;
+    const int64_t frame_ms = (int64_t) hparams.lfr_n * hparams.frame_shift;
+    auto frame_to_ms = [&](size_t frame) -> int64_t {
+        return frame < n_query ? 0 : (int64_t) (frame - n_query) * frame_ms;
+    };
+
+    const auto &state_ids = ctx->state->ids;
+    int n = 0;
+    for (size_t i = 0; i < state_ids.size(); i++) {
+        if (!state_ids[i] || (i > 0 && state_ids[i - 1] == state_ids[i]))
+            continue;
+        size_t end = i + 1;
+        while (end < state_ids.size() && state_ids[end] == state_ids[i]) {
+            end++;
+        }
+        if (n < n_max) {
+            if (t0) t0[n] = frame_to_ms(i);
+            if (t1) t1[n] = frame_to_ms(end);
+        }
+        n++;
+    }
+    return n;
+}
+
+const char *sense_voice_token_to_str(struct sense_voice_context *ctx, int id) {
+    auto it = ctx->vocab.id_to_token.find(id);
+    if (it == ctx->vocab.id_to_token.end()) {
+        return nullptr;
+    }
+    return it->second.c_str();
+}
+
+struct sense_voice_timings sense_voice_get_timings(struct sense_voice_context *ctx) {
+    struct sense_voice_timings timings = {0.0f, 0.0f, 0.0f};
+    if (ctx->state) {
+        timings.feature_ms = ctx->state->t_feature_us / 1000.0f;
+        timings.encode_ms = ctx->state->t_encode_us / 1000.0f;
+        timings.decode_ms = ctx->state->t_decode_us / 1000.0f;
+    }
+    return timings;
+}
+
+int sense_voice_get_encoder_output(struct sense_voice_context *ctx,
+                                   float *out,
+                                   int n_max,
+                                   int *n_frames,
+                                   int *n_dim) {
+    const ggml_tensor *encoder_out = ctx->state ? ctx->state->encoder_out : nullptr;
+    if (!encoder_out || !encoder_out->buffer || encoder_out->type != GGML_TYPE_F32) {
+        return 0;
+    }
+    const int dim = (int) encoder_out->ne[0];
+    const int frames = (int) (encoder_out->ne[1] * encoder_out->ne[2]);
+    if (n_frames) *n_frames = frames;
+    if (n_dim) *n_dim = dim;
+    const int n = std::min(dim * frames, n_max);
+    if (out && n > 0) {
+        ggml_backend_tensor_get(encoder_out, out, 0, (size_t) n * sizeof(float));
+    }
+    return dim * frames;
+}
+
+float sense_voice_full_get_segment_no_speech_prob(struct sense_voice_context *ctx, int i_segment) {
+    (void) i_segment;
+    return ctx->state ? ctx->state->no_speech_prob : 0.0f;
+}
+
+int sense_voice_full_n_hypotheses(struct sense_voice_context *ctx) {
+    return ctx->state ? (int) ctx->state->hypotheses.size() : 0;
+}
+
+int sense_voice_full_get_hypothesis_tokens(struct sense_voice_context *ctx, int i, int *ids, int n_max) {
+    if (!ctx->state || i < 0 || i >= (int) ctx->state->hypotheses.size()) {
+        return 0;
+    }
+    const auto &tokens = ctx->state->hypotheses[i].tokens;
+    if (ids) {
+        std::copy_n(tokens.begin(), std::min((int) tokens.size(), std::max(n_max, 0)), ids);
+    }
+    return (int) tokens.size();
+}
+
+float sense_voice_full_get_hypothesis_score(struct sense_voice_context *ctx, int i) {
+    if (!ctx->state || i < 0 || i >= (int) ctx->state->hypotheses.size()) {
+        return 0.0f;
+    }
+    return ctx->state->hypotheses[i].logprob;
+}
+
+float sense_voice_full_get_segment_avg_logprob(struct sense_voice_context *ctx, int i_segment) {
+    (void) i_segment;
+    return ctx->state ? ctx->state->avg_logprob : 0.0f;
+}
+
+int64_t sense_voice_full_get_segment_t0(struct sense_voice_context *ctx, int i_segment) {
+    (void) ctx;
+    (void) i_segment;
+    return 0;
+}
+
+int64_t sense_voice_full_get_segment_t1(struct sense_voice_context *ctx, int i_segment) {
+    (void) i_segment;
+    return ctx->state ? (int64_t) (ctx->state->duration * 1000.0) : 0;
+}
+
 void sense_voice_reset_ctx_state(struct sense_voice_context *ctx) {
     sense_voice_free_state(ctx->state);
     ctx->state = sense_voice_init_state(ctx);
-}
\ No newline at end of file
+}
+
+void sense_voice_reset_decode_state(struct sense_voice_context *ctx) {
+    sense_voice_state *state = ctx->state;
+    if (!state) {
+        return;
+    }
+    state->ids.clear();
+    state->no_speech_prob = 0.0f;
+    state->avg_logprob = 0.0f;
+    state->hypotheses.clear();
+    state->full_text.clear();
+    for (auto &result: state->result_all) {
+        result.tokens.clear();
+    }
+    state->t_decode_us = 0;
+}
+
+const char *sense_voice_active_backend(struct sense_voice_context *ctx) {
+    if (!ctx->state || ctx->state->backends.empty()) {
+        return nullptr;
+    }
+    return ggml_backend_name(ctx->state->backends[0]);
+}
+
+bool sense_voice_flash_attn_supported(struct sense_voice_context *ctx) {
+    if (!ctx->params.flash_attn) {
+        return true;
+    }
+
+    const auto &hparams = ctx->model.hparams;
+    const int n_state = hparams.n_encoder_hidden_state;
+    const int n_head = hparams.n_encoder_attention_heads;
+
+    if (n_head <= 0 || n_state % n_head != 0) {
+        SENSE_VOICE_LOG_ERROR("%s: flash attention needs n_state (%d) to be divisible by n_head (%d)\n", __func__, n_state, n_head);
+        return false;
+    }
+
+    if (!ctx->state || ctx->state->backends.empty()) {
+        SENSE_VOICE_LOG_ERROR("%s: context has no initialized backend\n", __func__);
+        return false;
+    }
+
+    ggml_backend_dev_t dev = ggml_backend_get_device(ctx->state->backends[0]);
+    if (!dev) {
+        return false;
+    }
+
+    // build the same flash attention node the encoder uses, without allocating it
+    struct ggml_init_params params = {
+            /*.mem_size   =*/4 * ggml_tensor_overhead(),
+            /*.mem_buffer =*/nullptr,
+            /*.no_alloc   =*/true,
+    };
+    struct ggml_context *ctx0 = ggml_init(params);
+
+    const int n_state_head = n_state / n_head;
+    const int n_ctx_pad = GGML_PAD(hparams.n_audio_ctx, 256);
+
+    struct ggml_tensor *Q = ggml_new_tensor_4d(ctx0, GGML_TYPE_F32, n_state_head, hparams.n_audio_ctx, n_head, 1);
+    struct ggml_tensor *K = ggml_new_tensor_4d(ctx0, ctx->itype, n_state_head, n_ctx_pad, n_head, 1);
+    struct ggml_tensor *V = ggml_new_tensor_4d(ctx0, ctx->itype, n_state_head, n_ctx_pad, n_head, 1);
+    struct ggml_tensor *KQV = ggml_flash_attn_ext(ctx0, Q, K, V, nullptr, 1.0f, 0.0f, 0.0f);
+
+    const bool supported = ggml_backend_dev_supports_op(dev, KQV);
+    ggml_free(ctx0);
+
+    if (!supported) {
+        SENSE_VOICE_LOG_ERROR("%s: backend %s does not support flash attention with head size %d\n", __func__, ggml_backend_dev_name(dev), n_state_head);
+    }
+
+    return supported;
+}
+
+int sense_voice_model_ftype(struct sense_voice_context *ctx) {
+    return ctx->model.hparams.ftype;
+}
+
+int sense_voice_model_n_audio_ctx(struct sense_voice_context *ctx) {
+    return ctx->model.hparams.n_audio_ctx;
+}
+
+int sense_voice_model_probe_file(const char *path_model, struct sense_voice_model_probe *probe) {
+    struct gguf_init_params gguf_params = {
+            /*.no_alloc = */ true,
+            /*.ctx      = */ nullptr,
+    };
+    struct gguf_context *gguf_ctx = gguf_init_from_file(path_model, gguf_params);
+    if (!gguf_ctx) {
+        SENSE_VOICE_LOG_ERROR("%s: failed to read GGUF header of '%s'\n", __func__, path_model);
+        return -1;
+    }
+
+    int ret = 0;
+    for (const char *key: {"tokenizer.vocab_size", "tokenizer.ggml.tokens", "encoder.output_size",
+                           "encoder.linear_units", "encoder.attention_heads", "encoder.num_blocks"}) {
+        if (gguf_find_key(gguf_ctx, key) == -1) {
+            SENSE_VOICE_LOG_ERROR("%s: missing key '%s'\n", __func__, key);
+            ret = -2;
+        }
+    }
+    if (ret == 0 && gguf_get_n_tensors(gguf_ctx) == 0) {
+        SENSE_VOICE_LOG_ERROR("%s: model has no tensors\n", __func__);
+        ret = -2;
+    }
+
+    sense_voice_hparams hparams;
+    const int ftype_idx = gguf_find_key(gguf_ctx, "general.file_type");
+    if (ftype_idx != -1) {
+        if (gguf_get_kv_type(gguf_ctx, ftype_idx) == GGUF_TYPE_UINT32) {
+            hparams.ftype = gguf_get_val_u32(gguf_ctx, ftype_idx);
+        } else if (ret == 0) {
+            SENSE_VOICE_LOG_ERROR("%s: invalid model (general.file_type is not a uint32)\n", __func__);
+            ret = -3;
+        }
+    }
+    if (ret == 0 && ggml_ftype_to_ggml_type((ggml_ftype) hparams.ftype) == GGML_TYPE_COUNT) {
+        SENSE_VOICE_LOG_ERROR("%s: invalid model (bad ftype value %d)\n", __func__, hparams.ftype);
+        ret = -3;
+    }
+    sense_voice_load_frontend_hparams(gguf_ctx, hparams);
+    if (ret == 0 && !sense_voice_frontend_supported(hparams)) {
+        ret = -4;
+    }
+
+    if (ret == 0) {
+        probe->ftype = hparams.ftype;
+        probe->n_audio_ctx = hparams.n_audio_ctx;
+        probe->frontend = sense_voice_frontend_params_of(hparams);
+        std::fill(std::begin(probe->n_tensors_of_type), std::end(probe->n_tensors_of_type), 0);
+        for (int64_t i = 0; i < gguf_get_n_tensors(gguf_ctx); ++i) {
+            const enum ggml_type type = gguf_get_tensor_type(gguf_ctx, i);
+            if (type >= 0 && type < GGML_TYPE_COUNT) {
+                probe->n_tensors_of_type[type]++;
+            }
+        }
+        size_t data_end = 0;
+        for (int64_t i = 0; i < gguf_get_n_tensors(gguf_ctx); ++i) {
+            data_end = std::max(data_end, gguf_get_tensor_offset(gguf_ctx, i) + gguf_get_tensor_size(gguf_ctx, i));
+        }
+        probe->file_size = gguf_get_data_offset(gguf_ctx) + data_end;
+    }
+
+    gguf_free(gguf_ctx);
+    return ret;
+}
+
+int sense_voice_model_n_tensors_of_type(struct sense_voice_context *ctx, enum ggml_type type) {
+    int n = 0;
+    for (const auto &it: ctx->model.tensors) {
+        if (it.second && it.second->type == type) {
+            n++;
+        }
+    }
+    return n;
+}
+
+bool sense_voice_type_supported(struct sense_voice_context *ctx, enum ggml_type type) {
+    if (!ctx->state || ctx->state->backends.empty()) {
+        SENSE_VOICE_LOG_ERROR("%s: context has no initialized backend\n", __func__);
+        return false;
+    }
+
+    ggml_backend_dev_t dev = ggml_backend_get_device(ctx->state->backends[0]);
+    if (!dev) {
+        return false;
+    }
+
+    // a weight-times-activation product like the encoder's linear layers, without allocating it
+    struct ggml_init_params params = {
+            /*.mem_size   =*/3 * ggml_tensor_overhead(),
+            /*.mem_buffer =*/nullptr,
+            /*.no_alloc   =*/true,
+    };
+    struct ggml_context *ctx0 = ggml_init(params);
+
+    // 256 is a multiple of every block size, including the k-quants
+    struct ggml_tensor *W = ggml_new_tensor_2d(ctx0, type, 256, 16);
+    struct ggml_tensor *x = ggml_new_tensor_2d(ctx0, GGML_TYPE_F32, 256, 1);
+    struct ggml_tensor *y = ggml_mul_mat(ctx0, W, x);
+
+    const bool supported = ggml_backend_dev_supports_op(dev, y);
+    ggml_free(ctx0);
+
+    if (!supported) {
+        SENSE_VOICE_LOG_ERROR("%s: backend %s does not support weights of type %s\n", __func__, ggml_backend_dev_name(dev), ggml_type_name(type));
+    }
+
+    return supported;
+}
+
+int sense_voice_model_meta_count(struct sense_voice_context *ctx) {
+    return (int) ctx->model.metadata.size();
+}
+
+const char *sense_voice_model_meta_key_by_index(struct sense_voice_context *ctx, int i) {
+    if (i < 0 || i >= (int) ctx->model.metadata.size()) {
+        return nullptr;
+    }
+    return ctx->model.metadata[i].first.c_str();
+}
+
+const char *sense_voice_model_meta_val_str_by_index(struct sense_voice_context *ctx, int i) {
+    if (i < 0 || i >= (int) ctx->model.metadata.size()) {
+        return nullptr;
+    }
+    return ctx->model.metadata[i].second.c_str();
+}
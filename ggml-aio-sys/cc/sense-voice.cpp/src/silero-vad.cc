@@ -167,8 +167,13 @@ bool silero_vad_encode_internal(sense_voice_context &ctx,
         // set the input
         {
 
+            // the chunk tensor is f32: convert the first VAD_CHUNK_SIZE samples as they are (no
+            // scaling or normalization) and zero-pad shorter input instead of copying the raw
+            // doubles, which reinterpreted their bytes and read past short buffers
+            std::vector<float> chunk(VAD_CHUNK_SIZE, 0.0f);
+            std::copy_n(samples, std::min(std::max(n_samples, 0), VAD_CHUNK_SIZE), chunk.begin());
             struct ggml_tensor *data = ggml_graph_get_tensor(gf, "audio_chunk");
-            ggml_backend_tensor_set(data, samples, 0, ggml_nbytes(data));
+            ggml_backend_tensor_set(data, chunk.data(), 0, ggml_nbytes(data));
 
             struct ggml_tensor *in_lstm_context = ggml_graph_get_tensor(gf, "in_lstm_context");
             struct ggml_tensor *in_lstm_hidden_state = ggml_graph_get_tensor(gf, "in_lstm_hidden_state");
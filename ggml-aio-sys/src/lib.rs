@@ -1,4 +1,8 @@
 //! See [llama-cpp-2](https://crates.io/crates/llama-cpp-2) for a documented and safe API.
+//!
+//! Only the model libraries selected with the `llama`, `sensevoice` and `whisper` features (all
+//! on by default) are built and bound; the `ggml_*` bindings are always there. See the feature
+//! table in this crate's Cargo.toml.
 
 #![allow(non_upper_case_globals)]
 #![allow(non_camel_case_types)]
@@ -6,3 +10,31 @@
 #![allow(unpredictable_function_pointer_comparisons)]
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+/// Versions of the native sources this crate was built from, for bug reports and to compare
+/// builds, see [native_versions].
+///
+/// Each field holds the version the library's CMake project declares and, when the sources
+/// were a git checkout at build time (e.g. the submodules of a repository clone), the
+/// abbreviated commit hash, e.g. `1.7.5 (a1b2c3d4e5f6)`. Builds from the published crate
+/// only have the version; llama.cpp declares none, so it is `unknown` there. The versions
+/// describe the vendored sources whether or not the library's feature is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NativeVersions {
+    pub ggml: &'static str,
+    pub whisper: &'static str,
+    pub sensevoice: &'static str,
+    pub llama: &'static str,
+}
+
+include!(concat!(env!("OUT_DIR"), "/native_versions.rs"));
+
+/// The versions of the vendored native libraries, captured by the build script.
+pub fn native_versions() -> NativeVersions {
+    NATIVE_VERSIONS
+}
+
+// the build script's version parsing, included here to run its tests
+#[cfg(test)]
+#[path = "../build/native_version.rs"]
+mod native_version;
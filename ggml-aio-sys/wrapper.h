@@ -1,4 +1,11 @@
+// GGML_AIO_* are defined by build.rs for the enabled model features
+#ifdef GGML_AIO_LLAMA
 #include "llama.cpp/include/llama.h"
+#endif
+#ifdef GGML_AIO_WHISPER
 #include "whisper.cpp/include/whisper.h"
+#endif
+#ifdef GGML_AIO_SENSE_VOICE
 #include "sense-voice.cpp/include/sense-voice.h"
+#endif
 #include "ggml/include/ggml.h"
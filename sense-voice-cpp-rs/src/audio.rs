@@ -0,0 +1,1005 @@
+//! Helpers for getting audio into the shape SenseVoice expects: mono samples at
+//! [crate::SENSE_VOICE_SAMPLE_RATE].
+//!
+//! # Amplitude
+//! The native front end computes its features from samples in the 16-bit integer range,
+//! -32768.0..=32767.0, the range the model's feature statistics were computed in, and
+//! [crate::full_parallel] passes samples on as they are. The decoders in this module produce
+//! samples with full scale at ±1.0 instead: transcribe those with
+//! [crate::SenseVoiceFullParamsBuilder::normalized_input], which multiplies them by
+//! [FRONTEND_SCALE] on their way into the front end. The helpers reading audio themselves,
+//! such as [crate::source::transcribe_source], [crate::stream::transcribe_file_streaming] and
+//! [crate::stream::VadGatedStream], switch it on for their ±1.0 samples.
+//!
+//! Neither the crate nor the front end normalizes the level: the front end only removes the
+//! DC offset and applies pre-emphasis per frame before computing log-mel energies, which are
+//! then standardized with fixed statistics from the model's training data.
+//!
+//! Loudness therefore matters: halving the amplitude shifts every log-mel energy by
+//! `ln(0.25)`, and very quiet input moves the features away from what the model saw in
+//! training. Boost quiet recordings, e.g. with [normalize_peak], but don't push them into
+//! clipping, see [clipping_ratio]. [crate::get_speech_prob] reads the samples unscaled, as the
+//! voice activity detector was trained on -1.0..=1.0 audio, so its probabilities depend on the
+//! level as well; see its docs for how it prepares the samples.
+//!
+//! # Alignment
+//! Sample buffers have no alignment requirement beyond that of their element type, and aligning
+//! them doesn't save a copy: the samples are scaled into a new buffer on their way to the front
+//! end, which copies them once more into its own `std::vector<double>` and computes the
+//! features frame by frame from windowed copies. Only the features reach ggml, uploaded with
+//! `ggml_backend_tensor_set` into a tensor the backend allocated with its own alignment
+//! (`GGML_MEM_ALIGN` for host memory), so the alignment of the caller's buffer never reaches a
+//! CPU kernel.
+
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use crate::error::SenseVoiceError;
+
+/// Check that `interleaved` holds whole frames of `channels` samples and that
+/// `channel_index` names one of them.
+///
+/// # Errors
+/// [SenseVoiceError::InvalidAudioBuffer] describing the first problem found.
+pub fn validate_interleaved(
+    interleaved: &[f32],
+    channels: usize,
+    channel_index: usize,
+) -> Result<(), SenseVoiceError> {
+    if channels == 0 {
+        return Err(SenseVoiceError::InvalidAudioBuffer(
+            "channel count must be at least 1",
+        ));
+    }
+    if channel_index >= channels {
+        return Err(SenseVoiceError::InvalidAudioBuffer(
+            "channel index must be below the channel count",
+        ));
+    }
+    if !interleaved.len().is_multiple_of(channels) {
+        return Err(SenseVoiceError::InvalidAudioBuffer(
+            "buffer length must be a multiple of the channel count",
+        ));
+    }
+    Ok(())
+}
+
+/// Extract one channel of an interleaved multi-channel buffer as mono samples.
+///
+/// # Arguments
+/// * `interleaved` - Samples ordered frame by frame, `channels` samples per frame.
+/// * `channels` - Number of channels in the buffer.
+/// * `channel_index` - The channel to keep, starting at 0.
+///
+/// # Errors
+/// See [validate_interleaved].
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::audio::extract_channel;
+/// // mic on channel 0, echo reference on channel 1
+/// let interleaved = [0.1f32, 0.9, 0.2, 0.8];
+/// assert_eq!(extract_channel(&interleaved, 2, 0).unwrap(), vec![0.1f32 as f64, 0.2f32 as f64]);
+/// ```
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "sense_voice.audio.extract_channel",
+        level = "debug",
+        skip(interleaved),
+        fields(n_samples = interleaved.len()),
+        err
+    )
+)]
+pub fn extract_channel(
+    interleaved: &[f32],
+    channels: usize,
+    channel_index: usize,
+) -> Result<Vec<f64>, SenseVoiceError> {
+    validate_interleaved(interleaved, channels, channel_index)?;
+    Ok(interleaved
+        .chunks_exact(channels)
+        .map(|frame| f64::from(frame[channel_index]))
+        .collect())
+}
+
+/// Append silence (zeros) to `samples` until it is at least `min_samples` long.
+///
+/// Padding very short clips avoids feeding the encoder too few frames, but the model may
+/// hallucinate trailing tokens over the added silence; trimming such output is up to the
+/// caller. Works for `f32` and `f64` samples alike.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::audio::pad_to_min;
+/// let padded = pad_to_min(&[0.5f32, -0.5], 4);
+/// assert_eq!(padded, vec![0.5, -0.5, 0.0, 0.0]);
+/// ```
+pub fn pad_to_min<T: Copy + Default>(samples: &[T], min_samples: usize) -> Vec<T> {
+    let mut padded = Vec::with_capacity(samples.len().max(min_samples));
+    padded.extend_from_slice(samples);
+    if padded.len() < min_samples {
+        padded.resize(min_samples, T::default());
+    }
+    padded
+}
+
+/// Resample mono audio from `from_rate` to `to_rate` Hz by linear interpolation.
+///
+/// Good enough for speech recognition, but there's no anti-aliasing filter: when downsampling
+/// from rates well above 16 kHz, content above the new Nyquist frequency folds back into the
+/// signal. Use a dedicated resampler if that matters for your audio.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::audio::resample;
+/// assert_eq!(resample(&[0.0, 1.0, 0.0, -1.0], 32000, 16000), vec![0.0, 0.0]);
+/// assert_eq!(resample(&[0.0, 1.0], 8000, 16000), vec![0.0, 0.5, 1.0, 1.0]);
+/// ```
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "sense_voice.audio.resample",
+        level = "debug",
+        skip(samples),
+        fields(n_samples = samples.len())
+    )
+)]
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let n_out = (samples.len() as u64 * u64::from(to_rate) / u64::from(from_rate)) as usize;
+    let step = f64::from(from_rate) / f64::from(to_rate);
+    (0..n_out)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let index = pos as usize;
+            let frac = (pos - index as f64) as f32;
+            let a = samples[index];
+            let b = samples.get(index + 1).copied().unwrap_or(a);
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+/// [high_band_energy_db] levels below this suggest audio that was recorded at 8 kHz and
+/// upsampled, see [is_likely_narrowband].
+pub const NARROWBAND_THRESHOLD_DB: f32 = -35.0;
+
+/// Samples per analysis frame of [high_band_energy_db], 32 ms at 16 kHz.
+const BANDWIDTH_FRAME: usize = 512;
+
+/// Energy above 4 kHz relative to the total energy of 16 kHz `samples`, in dB.
+///
+/// The samples are cut into frames of 512 (32 ms), each Hann-windowed and transformed;
+/// the energy of the bins above 4 kHz is summed over all frames and divided by the energy
+/// of all bins. Wideband speech typically lands between -25 and -10 dB, audio that only ever
+/// had 8 kHz bandwidth far below [NARROWBAND_THRESHOLD_DB].
+///
+/// # Returns
+/// None if `samples` is shorter than one frame or completely silent.
+pub fn high_band_energy_db(samples: &[f64]) -> Option<f32> {
+    let mut high = 0.0f64;
+    let mut total = 0.0f64;
+    let mut re = [0.0f64; BANDWIDTH_FRAME];
+    let mut im = [0.0f64; BANDWIDTH_FRAME];
+    for frame in samples.chunks_exact(BANDWIDTH_FRAME) {
+        for (i, &sample) in frame.iter().enumerate() {
+            let window =
+                0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / BANDWIDTH_FRAME as f64).cos();
+            re[i] = sample * window;
+        }
+        im.fill(0.0);
+        fft(&mut re, &mut im);
+        // bin k is k * 16000 / 512 Hz, so 4 kHz is bin 128; skip DC
+        for k in 1..=BANDWIDTH_FRAME / 2 {
+            let power = re[k] * re[k] + im[k] * im[k];
+            total += power;
+            if k > BANDWIDTH_FRAME / 4 {
+                high += power;
+            }
+        }
+    }
+    if total <= 0.0 {
+        return None;
+    }
+    // floor at -120 dB so digital silence above 4 kHz stays finite
+    Some((10.0 * (high / total).max(1e-12).log10()) as f32)
+}
+
+/// Whether 16 kHz `samples` look like narrowband (8 kHz telephony) audio that was upsampled.
+///
+/// SenseVoice was trained on 16 kHz audio and transcribes upsampled telephony audio noticeably
+/// worse, because the 4-8 kHz band it relies on for fricatives is empty. This flags input whose
+/// [high_band_energy_db] is below [NARROWBAND_THRESHOLD_DB]; see
+/// [crate::SenseVoiceFullParamsBuilder::reject_narrowband] to check it on every transcription.
+///
+/// Limitations of the heuristic:
+/// * upsampling without a proper low-pass filter, e.g. with [resample], leaves attenuated
+///   images of the 0-4 kHz band above 4 kHz, which may lift such audio over the threshold;
+/// * genuinely wideband recordings can fall below it when they contain almost only vowels,
+///   hum or low-pass filtered music, so it is a hint, not proof;
+/// * clips shorter than 32 ms and silence are never flagged.
+pub fn is_likely_narrowband(samples: &[f64]) -> bool {
+    high_band_energy_db(samples).is_some_and(|db| db < NARROWBAND_THRESHOLD_DB)
+}
+
+/// Samples whose magnitude exceeds this count as clipped in [clipping_ratio].
+///
+/// Just below full scale rather than at 1.0: clipped audio that went through a lossy codec,
+/// a resampler or a gain stage rarely sits exactly at ±1.0, but its flattened peaks stay
+/// within a percent of it.
+pub const CLIPPING_LEVEL: f32 = 0.99;
+
+/// [clipping_ratio] above which [crate::SenseVoiceFullParamsBuilder::warn_on_clipping] logs a
+/// warning, one sample in a thousand.
+pub const CLIPPING_WARN_RATIO: f32 = 0.001;
+
+/// Fraction of `samples` whose magnitude exceeds [CLIPPING_LEVEL], between 0.0 and 1.0.
+///
+/// Clipped input, e.g. from a microphone with too much gain, adds harmonics that SenseVoice
+/// tends to hear as noise or extra syllables. A clean recording has practically no samples
+/// that close to full scale; a few per thousand already mean audible distortion. Takes `f32`
+/// and `f64` samples normalized to -1.0..=1.0.
+///
+/// # Returns
+/// 0.0 for empty input.
+pub fn clipping_ratio<T: Copy + Into<f64>>(samples: &[T]) -> f32 {
+    clipping_ratio_at(samples, 1.0)
+}
+
+/// [clipping_ratio] of samples with full scale at ±`full_scale`.
+pub(crate) fn clipping_ratio_at<T: Copy + Into<f64>>(samples: &[T], full_scale: f64) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let level = f64::from(CLIPPING_LEVEL) * full_scale;
+    let clipped = samples
+        .iter()
+        .filter(|&&sample| sample.into().abs() > level)
+        .count();
+    (clipped as f64 / samples.len() as f64) as f32
+}
+
+/// Index of the first NaN or infinite sample in `samples`, e.g. from a broken decoder or a
+/// division by zero while resampling. Takes `f32` and `f64` samples.
+///
+/// A single pass that stops at the first hit; see
+/// [crate::SenseVoiceFullParamsBuilder::reject_non_finite] to check it on every
+/// transcription and [sanitize_audio] to repair the samples instead.
+pub fn first_non_finite<T: Copy + Into<f64>>(samples: &[T]) -> Option<usize> {
+    samples
+        .iter()
+        .position(|&sample| !sample.into().is_finite())
+}
+
+/// `samples` with every NaN or infinite value replaced by 0.0.
+///
+/// The front end spreads a single non-finite sample over the features of every frame that
+/// contains it, and the encoder's attention then over the whole transcript, so it's better
+/// to silence the sample than to pass it on. Silencing leaves a click where a burst of bad
+/// samples was; if [first_non_finite] finds any, the source is worth fixing.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::audio::sanitize_audio;
+/// assert_eq!(
+///     sanitize_audio(&[0.5, f32::NAN, f32::INFINITY, -0.25]),
+///     vec![0.5, 0.0, 0.0, -0.25]
+/// );
+/// ```
+pub fn sanitize_audio(samples: &[f32]) -> Vec<f32> {
+    samples
+        .iter()
+        .map(|&sample| if sample.is_finite() { sample } else { 0.0 })
+        .collect()
+}
+
+/// Magnitude of a full-scale sample in the native front end, which computes its features from
+/// samples in the 16-bit integer range, see the [module docs](self#amplitude).
+pub const FRONTEND_SCALE: f64 = 32768.0;
+
+/// `samples` scaled from -1.0..=1.0 to the range the native front end expects.
+pub(crate) fn to_frontend_scale(samples: &[f64]) -> Vec<f64> {
+    samples
+        .iter()
+        .map(|&sample| sample * FRONTEND_SCALE)
+        .collect()
+}
+
+/// Scale `samples` so the loudest one sits at full scale, ±1.0.
+///
+/// A single gain is applied to the whole buffer, so the dynamics are preserved and a
+/// transient much louder than the speech limits how much the speech is boosted. Silent input
+/// (all zeros) and empty input are returned unchanged.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::audio::normalize_peak;
+/// assert_eq!(normalize_peak(&[0.25, -0.5, 0.125]), vec![0.5, -1.0, 0.25]);
+/// assert_eq!(normalize_peak(&[0.0, 0.0]), vec![0.0, 0.0]);
+/// ```
+pub fn normalize_peak(samples: &[f32]) -> Vec<f32> {
+    let peak = samples
+        .iter()
+        .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    if peak == 0.0 || !peak.is_finite() {
+        return samples.to_vec();
+    }
+    samples.iter().map(|&sample| sample / peak).collect()
+}
+
+/// Widen half-precision samples to `f32`, losslessly.
+///
+/// # Precision
+/// `f16` has an 11-bit significand, so at any level a sample is stored with a relative error
+/// of at most 2^-11, a quantization noise floor about 66 dB below the signal. That's worse
+/// than 16-bit PCM (96 dB for a full-scale signal) but, unlike integer formats, it doesn't
+/// degrade for quiet audio until it falls below 2^-14 (-84 dBFS), where `f16` turns
+/// subnormal. Speech recognition doesn't suffer from that noise floor; avoid `f16` for audio
+/// you want to archive or process further.
+///
+/// # Examples
+/// ```
+/// # use half::f16;
+/// # use sense_voice_cpp_rs::audio::f16_to_f32;
+/// let samples = [f16::from_f32(0.5), f16::from_f32(-1.0)];
+/// assert_eq!(f16_to_f32(&samples), vec![0.5, -1.0]);
+/// ```
+#[cfg(feature = "half")]
+pub fn f16_to_f32(samples: &[half::f16]) -> Vec<f32> {
+    samples.iter().map(|&sample| sample.to_f32()).collect()
+}
+
+/// Narrow `f32` samples to half precision, rounding to the nearest `f16`, see [f16_to_f32]
+/// for what that costs.
+#[cfg(feature = "half")]
+pub fn f32_to_f16(samples: &[f32]) -> Vec<half::f16> {
+    samples
+        .iter()
+        .map(|&sample| half::f16::from_f32(sample))
+        .collect()
+}
+
+/// In-place iterative radix-2 FFT, `re.len()` must be a power of two.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f64::consts::PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_im, w_re) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Audio in one of the layouts [crate::transcriber::Transcriber::transcribe_any] accepts.
+///
+/// Everything is converted to mono `f32` samples at [crate::SENSE_VOICE_SAMPLE_RATE] by
+/// [AudioInput::to_mono_16k]:
+/// * [AudioInput::Mono16k] is used as-is.
+/// * [AudioInput::I16] is scaled to `-1.0..1.0` (divided by 32768) and resampled.
+/// * [AudioInput::F32] is resampled.
+/// * [AudioInput::File] is read with [WavReader], down-mixed to mono and resampled.
+///
+/// Resampling uses [resample]. Multi-channel buffers have to be split first, see
+/// [extract_channel].
+#[derive(Debug, Clone)]
+pub enum AudioInput<'a> {
+    /// Mono `f32` samples at 16 kHz. Built from `&[f32]`.
+    Mono16k(&'a [f32]),
+    /// Mono 16-bit PCM samples at the given rate in Hz. Built from `(&[i16], rate)`.
+    I16 {
+        samples: &'a [i16],
+        sample_rate: u32,
+    },
+    /// Mono `f32` samples at the given rate in Hz. Built from `(&[f32], rate)`.
+    F32 {
+        samples: &'a [f32],
+        sample_rate: u32,
+    },
+    /// A WAV file, see [WavReader]. Built from `&Path` or `PathBuf`.
+    File(PathBuf),
+}
+
+impl AudioInput<'_> {
+    /// Convert the input to mono `f32` samples at [crate::SENSE_VOICE_SAMPLE_RATE].
+    ///
+    /// # Errors
+    /// [SenseVoiceError::InvalidAudioBuffer] for a sample rate of 0, and the errors of
+    /// [WavReader] for files.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "sense_voice.audio.convert", level = "debug", skip(self), err)
+    )]
+    pub fn to_mono_16k(&self) -> Result<Vec<f32>, SenseVoiceError> {
+        const RATE: u32 = crate::SENSE_VOICE_SAMPLE_RATE;
+        match self {
+            AudioInput::Mono16k(samples) => Ok(samples.to_vec()),
+            AudioInput::I16 {
+                samples,
+                sample_rate,
+            } => {
+                check_rate(*sample_rate)?;
+                let samples: Vec<f32> = samples.iter().map(|&s| f32::from(s) / 32768.0).collect();
+                Ok(resample(&samples, *sample_rate, RATE))
+            }
+            AudioInput::F32 {
+                samples,
+                sample_rate,
+            } => {
+                check_rate(*sample_rate)?;
+                Ok(resample(samples, *sample_rate, RATE))
+            }
+            AudioInput::File(path) => {
+                let mut reader = WavReader::open(path)?;
+                let samples: Vec<f32> = reader
+                    .read_frames(reader.n_frames())?
+                    .into_iter()
+                    .map(|s| s as f32)
+                    .collect();
+                Ok(resample(&samples, reader.sample_rate(), RATE))
+            }
+        }
+    }
+}
+
+fn check_rate(sample_rate: u32) -> Result<(), SenseVoiceError> {
+    if sample_rate == 0 {
+        return Err(SenseVoiceError::InvalidAudioBuffer(
+            "sample rate must not be 0",
+        ));
+    }
+    Ok(())
+}
+
+impl<'a> From<&'a [f32]> for AudioInput<'a> {
+    fn from(samples: &'a [f32]) -> Self {
+        AudioInput::Mono16k(samples)
+    }
+}
+
+impl<'a> From<(&'a [i16], u32)> for AudioInput<'a> {
+    fn from((samples, sample_rate): (&'a [i16], u32)) -> Self {
+        AudioInput::I16 {
+            samples,
+            sample_rate,
+        }
+    }
+}
+
+impl<'a> From<(&'a [f32], u32)> for AudioInput<'a> {
+    fn from((samples, sample_rate): (&'a [f32], u32)) -> Self {
+        AudioInput::F32 {
+            samples,
+            sample_rate,
+        }
+    }
+}
+
+impl From<&Path> for AudioInput<'_> {
+    fn from(path: &Path) -> Self {
+        AudioInput::File(path.to_path_buf())
+    }
+}
+
+impl From<PathBuf> for AudioInput<'_> {
+    fn from(path: PathBuf) -> Self {
+        AudioInput::File(path)
+    }
+}
+
+fn io_error(e: std::io::Error) -> SenseVoiceError {
+    SenseVoiceError::AudioFileIo(e.kind())
+}
+
+/// Sample encodings [WavReader] can decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WavEncoding {
+    UInt8,
+    Int16,
+    /// Packed, 3 bytes per sample.
+    Int24,
+    Int32,
+    Float32,
+    Float64,
+}
+
+/// Incremental reader for WAV files, for transcribing files that shouldn't be held in memory
+/// at once.
+///
+/// Only the header is parsed up front; samples are read window by window and down-mixed to
+/// mono. Supports 8-bit unsigned, 16-, 24- (packed) and 32-bit integer PCM as well as 32- and
+/// 64-bit float data, all scaled to `-1.0..1.0`. The samples are returned at the
+/// file's [WavReader::sample_rate], use [resample] for other rates than
+/// [crate::SENSE_VOICE_SAMPLE_RATE].
+pub struct WavReader {
+    reader: BufReader<File>,
+    encoding: WavEncoding,
+    channels: usize,
+    sample_rate: u32,
+    n_frames: usize,
+    frames_read: usize,
+}
+
+impl WavReader {
+    /// Open a WAV file and locate its data chunk.
+    ///
+    /// # Errors
+    /// [SenseVoiceError::AudioFileIo] if the file can't be read, [SenseVoiceError::UnsupportedEncoding]
+    /// for sample formats other than the ones listed above (e.g. A-law, ADPCM or 12-bit PCM),
+    /// [SenseVoiceError::InvalidAudioBuffer] if it isn't a valid WAV file.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "sense_voice.audio.open_wav",
+            level = "debug",
+            skip(path),
+            fields(path = %path.as_ref().display()),
+            err
+        )
+    )]
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SenseVoiceError> {
+        let mut reader = BufReader::new(File::open(path).map_err(io_error)?);
+
+        let mut riff = [0u8; 12];
+        reader.read_exact(&mut riff).map_err(io_error)?;
+        if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+            return Err(SenseVoiceError::InvalidAudioBuffer("not a RIFF/WAVE file"));
+        }
+
+        let mut format = None;
+        loop {
+            let mut header = [0u8; 8];
+            reader.read_exact(&mut header).map_err(io_error)?;
+            let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+            match &header[0..4] {
+                b"fmt " => {
+                    let mut fmt = vec![0u8; size as usize];
+                    reader.read_exact(&mut fmt).map_err(io_error)?;
+                    if fmt.len() < 16 {
+                        return Err(SenseVoiceError::InvalidAudioBuffer("truncated fmt chunk"));
+                    }
+                    let tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+                    let channels = u16::from_le_bytes([fmt[2], fmt[3]]) as usize;
+                    let rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+                    let bits = u16::from_le_bytes([fmt[14], fmt[15]]);
+                    // 0xFFFE is WAVE_FORMAT_EXTENSIBLE, the sub-format follows in the extension
+                    let tag = if tag == 0xFFFE && fmt.len() >= 26 {
+                        u16::from_le_bytes([fmt[24], fmt[25]])
+                    } else {
+                        tag
+                    };
+                    let encoding = match (tag, bits) {
+                        (1, 8) => WavEncoding::UInt8,
+                        (1, 16) => WavEncoding::Int16,
+                        (1, 24) => WavEncoding::Int24,
+                        (1, 32) => WavEncoding::Int32,
+                        (3, 32) => WavEncoding::Float32,
+                        (3, 64) => WavEncoding::Float64,
+                        _ => {
+                            return Err(SenseVoiceError::UnsupportedEncoding {
+                                format_tag: tag,
+                                bits_per_sample: bits,
+                            });
+                        }
+                    };
+                    if rate == 0 {
+                        return Err(SenseVoiceError::InvalidAudioBuffer(
+                            "WAV sample rate must not be 0",
+                        ));
+                    }
+                    if channels == 0 {
+                        return Err(SenseVoiceError::InvalidAudioBuffer(
+                            "WAV file has no channels",
+                        ));
+                    }
+                    format = Some((encoding, channels, rate));
+                    if size % 2 == 1 {
+                        reader.seek(SeekFrom::Current(1)).map_err(io_error)?;
+                    }
+                }
+                b"data" => {
+                    let Some((encoding, channels, sample_rate)) = format else {
+                        return Err(SenseVoiceError::InvalidAudioBuffer(
+                            "data chunk before fmt chunk",
+                        ));
+                    };
+                    let bytes_per_frame = channels * encoding.bytes_per_sample();
+                    return Ok(Self {
+                        reader,
+                        encoding,
+                        channels,
+                        sample_rate,
+                        n_frames: size as usize / bytes_per_frame,
+                        frames_read: 0,
+                    });
+                }
+                _ => {
+                    // chunks are padded to an even size
+                    reader
+                        .seek(SeekFrom::Current((size + size % 2) as i64))
+                        .map_err(io_error)?;
+                }
+            }
+        }
+    }
+
+    /// Total number of frames (samples per channel) in the file.
+    pub fn n_frames(&self) -> usize {
+        self.n_frames
+    }
+
+    /// Number of channels in the file. [WavReader::read_frames] down-mixes them to mono.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Sample rate of the file, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Read up to `n` frames as mono samples, averaging all channels.
+    ///
+    /// # Returns
+    /// An empty vector once the end of the data chunk was reached.
+    pub fn read_frames(&mut self, n: usize) -> Result<Vec<f64>, SenseVoiceError> {
+        let n = n.min(self.n_frames - self.frames_read);
+        let sample_size = self.encoding.bytes_per_sample();
+        let mut bytes = vec![0u8; n * self.channels * sample_size];
+        self.reader.read_exact(&mut bytes).map_err(io_error)?;
+        self.frames_read += n;
+
+        let encoding = self.encoding;
+        let samples = bytes.chunks_exact(sample_size).map(|b| encoding.decode(b));
+        let channels = self.channels;
+        let mut mono = Vec::with_capacity(n);
+        let mut sum = 0.0;
+        for (i, sample) in samples.enumerate() {
+            sum += sample;
+            if (i + 1) % channels == 0 {
+                mono.push(sum / channels as f64);
+                sum = 0.0;
+            }
+        }
+        Ok(mono)
+    }
+}
+
+impl WavEncoding {
+    fn bytes_per_sample(self) -> usize {
+        match self {
+            WavEncoding::UInt8 => 1,
+            WavEncoding::Int16 => 2,
+            WavEncoding::Int24 => 3,
+            WavEncoding::Int32 | WavEncoding::Float32 => 4,
+            WavEncoding::Float64 => 8,
+        }
+    }
+
+    /// Decode one little-endian sample of [Self::bytes_per_sample] bytes.
+    fn decode(self, b: &[u8]) -> f64 {
+        match self {
+            // 8-bit PCM is unsigned, centered on 128
+            WavEncoding::UInt8 => (f64::from(b[0]) - 128.0) / 128.0,
+            WavEncoding::Int16 => f64::from(i16::from_le_bytes([b[0], b[1]])) / 32768.0,
+            // place the 3 bytes in the upper part of an i32, the arithmetic shift back down
+            // extends the sign bit
+            WavEncoding::Int24 => {
+                f64::from(i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) / 8_388_608.0
+            }
+            WavEncoding::Int32 => {
+                f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])) / 2_147_483_648.0
+            }
+            WavEncoding::Float32 => f64::from(f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            WavEncoding::Float64 => f64::from_le_bytes(b.try_into().expect("8 bytes")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write_wav(path: &Path, channels: u16, frames: &[i16]) {
+        write_wav_at(path, channels, crate::SENSE_VOICE_SAMPLE_RATE, frames);
+    }
+
+    fn write_wav_at(path: &Path, channels: u16, sample_rate: u32, frames: &[i16]) {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for &sample in frames {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn pad_to_min_keeps_long_input() {
+        let samples = [1.0f64; 8];
+        assert_eq!(pad_to_min(&samples, 4), samples.to_vec());
+        assert_eq!(pad_to_min::<f64>(&[], 2), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn wav_reader_reads_windows() {
+        let path = std::env::temp_dir().join(format!("sense-voice-wav-{}.wav", std::process::id()));
+        // two channels, 5 frames
+        write_wav(
+            &path,
+            2,
+            &[0, 16384, 16384, 16384, 0, 0, -16384, -16384, 8192, 8192],
+        );
+
+        let mut reader = WavReader::open(&path).unwrap();
+        assert_eq!(reader.n_frames(), 5);
+        assert_eq!(reader.channels(), 2);
+        assert_eq!(reader.read_frames(2).unwrap(), vec![0.25, 0.5]);
+        assert_eq!(reader.read_frames(10).unwrap(), vec![0.0, -0.5, 0.25]);
+        assert!(reader.read_frames(10).unwrap().is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    fn write_wav_spec<S: hound::Sample + Copy>(
+        path: &Path,
+        bits_per_sample: u16,
+        sample_format: hound::SampleFormat,
+        samples: &[S],
+    ) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: crate::SENSE_VOICE_SAMPLE_RATE,
+            bits_per_sample,
+            sample_format,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn wav_reader_decodes_24_bit_and_float() {
+        let path =
+            std::env::temp_dir().join(format!("sense-voice-wav24-{}.wav", std::process::id()));
+        // full scale, half scale negative (sign extension) and the most negative sample
+        write_wav_spec(
+            &path,
+            24,
+            hound::SampleFormat::Int,
+            &[8_388_607i32, -4_194_304, -8_388_608, 1],
+        );
+        let mut reader = WavReader::open(&path).unwrap();
+        assert_eq!(reader.n_frames(), 4);
+        let samples = reader.read_frames(4).unwrap();
+        assert!((samples[0] - 1.0).abs() < 1e-6);
+        assert_eq!(samples[1..3], [-0.5, -1.0]);
+        assert!(samples[3] > 0.0 && samples[3] < 1e-6);
+
+        write_wav_spec(&path, 32, hound::SampleFormat::Float, &[0.5f32, -0.25, 1.0]);
+        let mut reader = WavReader::open(&path).unwrap();
+        assert_eq!(reader.read_frames(3).unwrap(), vec![0.5, -0.25, 1.0]);
+
+        write_wav_spec(&path, 8, hound::SampleFormat::Int, &[64i8, -128]);
+        let mut reader = WavReader::open(&path).unwrap();
+        assert_eq!(reader.read_frames(2).unwrap(), vec![0.5, -1.0]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn wav_reader_rejects_unsupported_encoding() {
+        let path =
+            std::env::temp_dir().join(format!("sense-voice-alaw-{}.wav", std::process::id()));
+        // RIFF header and a fmt chunk for 8-bit A-law (format tag 6)
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&28u32.to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        for field in [6u16, 1] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        bytes.extend_from_slice(&8_000u32.to_le_bytes());
+        bytes.extend_from_slice(&8_000u32.to_le_bytes());
+        for field in [1u16, 8] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        std::fs::write(&path, bytes).unwrap();
+
+        assert!(matches!(
+            WavReader::open(&path),
+            Err(SenseVoiceError::UnsupportedEncoding {
+                format_tag: 6,
+                bits_per_sample: 8
+            })
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn detects_narrowband_audio() {
+        let tone = |hz: f64| {
+            (0..16_000).map(move |i| (2.0 * std::f64::consts::PI * hz * i as f64 / 16_000.0).sin())
+        };
+        // tones below 4 kHz only, like band-limited telephony audio
+        let narrow: Vec<f64> = tone(300.0).zip(tone(2_500.0)).map(|(a, b)| a + b).collect();
+        assert!(high_band_energy_db(&narrow).unwrap() < NARROWBAND_THRESHOLD_DB);
+        assert!(is_likely_narrowband(&narrow));
+
+        let wide: Vec<f64> = tone(300.0)
+            .zip(tone(6_000.0))
+            .map(|(a, b)| a + 0.3 * b)
+            .collect();
+        assert!(high_band_energy_db(&wide).unwrap() > -15.0);
+        assert!(!is_likely_narrowband(&wide));
+
+        assert_eq!(high_band_energy_db(&[0.0; 2048]), None);
+        assert!(!is_likely_narrowband(&narrow[..100]));
+    }
+
+    #[test]
+    fn finds_non_finite_samples() {
+        assert_eq!(first_non_finite::<f32>(&[]), None);
+        assert_eq!(first_non_finite(&[0.0f32, 1.0, -1.0]), None);
+        assert_eq!(
+            first_non_finite(&[0.0f32, f32::NAN, f32::INFINITY]),
+            Some(1)
+        );
+        assert_eq!(first_non_finite(&[0.0f64, 0.5, f64::NEG_INFINITY]), Some(2));
+        assert_eq!(first_non_finite(&sanitize_audio(&[f32::NAN, 0.5])), None);
+    }
+
+    #[test]
+    fn measures_clipping() {
+        assert_eq!(clipping_ratio::<f32>(&[]), 0.0);
+        assert_eq!(clipping_ratio(&[0.5f32, -0.98, 0.99]), 0.0);
+        assert_eq!(clipping_ratio(&[1.0f32, -1.0, 0.995, 0.0]), 0.75);
+        // a sine driven 2x over full scale and hard-limited spends two thirds of the time at
+        // the rails
+        let clipped: Vec<f64> = (0..16_000)
+            .map(|i| 2.0 * (2.0 * std::f64::consts::PI * 440.0 * i as f64 / 16_000.0).sin())
+            .map(|s| s.clamp(-1.0, 1.0))
+            .collect();
+        let ratio = clipping_ratio(&clipped);
+        assert!((0.64..0.7).contains(&ratio), "{ratio}");
+    }
+
+    #[test]
+    fn normalizes_to_full_scale() {
+        assert!(normalize_peak(&[]).is_empty());
+        assert_eq!(normalize_peak(&[0.0; 3]), vec![0.0; 3]);
+        // the negative peak counts as well, and already normalized input stays as it is
+        assert_eq!(normalize_peak(&[0.1, -0.4, 0.2]), vec![0.25, -1.0, 0.5]);
+        assert_eq!(normalize_peak(&[1.0, -0.5]), vec![1.0, -0.5]);
+        // overdriven input is scaled down
+        assert_eq!(normalize_peak(&[4.0, -2.0]), vec![1.0, -0.5]);
+        assert_eq!(to_frontend_scale(&[1.0, -0.5]), vec![32768.0, -16384.0]);
+        assert_eq!(
+            clipping_ratio_at(&[32767.0, 1.0, -32768.0, 0.0], FRONTEND_SCALE),
+            0.5
+        );
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn converts_half_precision() {
+        let samples = [0.0f32, 1.0, -1.0, 0.25, 1e-3];
+        let round_trip = f16_to_f32(&f32_to_f16(&samples));
+        for (original, converted) in samples.iter().zip(&round_trip) {
+            assert!(
+                (original - converted).abs() <= original.abs() / 2048.0,
+                "{converted}"
+            );
+        }
+        // powers of two survive exactly
+        assert_eq!(&round_trip[..4], &samples[..4]);
+    }
+
+    #[test]
+    fn resample_changes_length_by_rate() {
+        let samples = vec![0.25f32; 48_000];
+        let resampled = resample(&samples, 48_000, 16_000);
+        assert_eq!(resampled.len(), 16_000);
+        assert!(resampled.iter().all(|&s| s == 0.25));
+        assert_eq!(resample(&samples, 16_000, 16_000).len(), 48_000);
+    }
+
+    #[test]
+    fn audio_input_conversions() {
+        let mono: &[f32] = &[0.5, -0.5];
+        assert_eq!(
+            AudioInput::from(mono).to_mono_16k().unwrap(),
+            vec![0.5, -0.5]
+        );
+
+        let pcm: &[i16] = &[16384, 16384, -16384, -16384];
+        assert_eq!(
+            AudioInput::from((pcm, 32_000)).to_mono_16k().unwrap(),
+            vec![0.5, -0.5]
+        );
+
+        let samples: &[f32] = &[0.0, 1.0];
+        assert_eq!(
+            AudioInput::from((samples, 8_000)).to_mono_16k().unwrap(),
+            vec![0.0, 0.5, 1.0, 1.0]
+        );
+        assert!(matches!(
+            AudioInput::from((samples, 0)).to_mono_16k(),
+            Err(SenseVoiceError::InvalidAudioBuffer(_))
+        ));
+    }
+
+    #[test]
+    fn audio_input_from_file_resamples() {
+        let path =
+            std::env::temp_dir().join(format!("sense-voice-input-{}.wav", std::process::id()));
+        // stereo at 8 kHz, 2 frames
+        write_wav_at(&path, 2, 8_000, &[16384, 16384, 0, 0]);
+
+        let samples = AudioInput::from(path.as_path()).to_mono_16k().unwrap();
+        assert_eq!(samples, vec![0.5, 0.25, 0.0, 0.0]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn extracts_requested_channel() {
+        let interleaved = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0];
+        assert_eq!(extract_channel(&interleaved, 3, 2).unwrap(), vec![3.0, 6.0]);
+        assert_eq!(extract_channel(&interleaved, 1, 0).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn rejects_invalid_layout() {
+        let interleaved = [0.0f32; 5];
+        assert!(matches!(
+            extract_channel(&interleaved, 2, 0),
+            Err(SenseVoiceError::InvalidAudioBuffer(_))
+        ));
+        assert!(matches!(
+            extract_channel(&interleaved, 5, 5),
+            Err(SenseVoiceError::InvalidAudioBuffer(_))
+        ));
+        assert!(matches!(
+            extract_channel(&interleaved, 0, 0),
+            Err(SenseVoiceError::InvalidAudioBuffer(_))
+        ));
+    }
+}
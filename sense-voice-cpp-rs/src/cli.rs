@@ -0,0 +1,189 @@
+//! Command line flags for [SenseVoiceFullParams], for tools built with clap.
+//!
+//! Flatten [TranscribeArgs] into a tool's own parser so every tool exposes the same flags:
+//!
+//! | Flag | Params field |
+//! |------|--------------|
+//! | `-t`, `--threads <N>` | [SenseVoiceFullParams::n_threads] |
+//! | `-l`, `--language <CODE>` | [SenseVoiceFullParams::language], see [SenseVoiceFullParamsBuilder::for_language] |
+//! | `--beam-size <N>` | [crate::BeamSearchParams::beam_size], switches to beam search |
+//! | `--best-of <N>` | [crate::GreedyParams::best_of] |
+//! | `--beam-patience <P>` | [crate::BeamSearchParams::patience] |
+//! | `--length-penalty <ALPHA>` | [SenseVoiceFullParams::length_penalty] |
+//! | `--temperature <T>` | [SenseVoiceFullParams::temperature] |
+//! | `--offset-ms <MS>` | [SenseVoiceFullParams::offset_ms] |
+//! | `--duration-ms <MS>` | [SenseVoiceFullParams::duration_ms] |
+//! | `--audio-ctx <N>` | [SenseVoiceFullParams::audio_ctx] |
+//! | `--max-tokens <N>` | [SenseVoiceFullParams::max_tokens] |
+//! | `--no-timestamps` | [SenseVoiceFullParams::no_timestamps] |
+//! | `--timeout-ms <MS>` | [SenseVoiceFullParams::timeout] |
+//! | `-q`, `--quiet` | [SenseVoiceFullParamsBuilder::quiet] |
+//!
+//! # Examples
+//! ```no_run
+//! use clap::Parser;
+//! use sense_voice_cpp_rs::cli::TranscribeArgs;
+//!
+//! #[derive(Parser)]
+//! struct Cli {
+//!     model: String,
+//!     #[command(flatten)]
+//!     transcribe: TranscribeArgs,
+//! }
+//!
+//! let cli = Cli::parse();
+//! let params = cli.transcribe.to_params()?;
+//! # Ok::<(), sense_voice_cpp_rs::error::SenseVoiceError>(())
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    SenseVoiceDecodingStrategy, SenseVoiceFullParams, SenseVoiceFullParamsBuilder,
+    error::SenseVoiceError,
+};
+
+/// Transcription flags, see the [module docs](self) for the flag names.
+#[derive(Debug, Clone, PartialEq, clap::Args)]
+pub struct TranscribeArgs {
+    /// Number of threads to use [default: up to 4]
+    #[arg(short = 't', long = "threads", value_name = "N")]
+    pub n_threads: Option<i32>,
+    /// Language code (zh, en, yue, ja, ko) or auto to detect it
+    #[arg(short = 'l', long, value_name = "CODE", default_value = "auto")]
+    pub language: String,
+    /// Use beam search with this many beams instead of greedy decoding
+    #[arg(long, value_name = "N")]
+    pub beam_size: Option<i32>,
+    /// Number of candidates for greedy decoding
+    #[arg(long, value_name = "N")]
+    pub best_of: Option<i32>,
+    /// Beam search patience, at least 1.0
+    #[arg(long, value_name = "P")]
+    pub beam_patience: Option<f32>,
+    /// Beam search length penalty, between 0.0 and 1.0
+    #[arg(long, value_name = "ALPHA")]
+    pub length_penalty: Option<f32>,
+    /// Sample tokens at this temperature instead of taking the most likely one
+    #[arg(long, value_name = "T", default_value_t = 0.0)]
+    pub temperature: f32,
+    /// Start transcribing this far into the audio
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    pub offset_ms: i32,
+    /// Transcribe only this much audio, 0 for all of it
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    pub duration_ms: i32,
+    /// Number of encoder frames to use, 0 for the model's full context (checked, but not used
+    /// by the encoder yet)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub audio_ctx: i32,
+    /// Stop each segment after this many tokens, 0 for no limit
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub max_tokens: i32,
+    /// Don't compute timestamps
+    #[arg(long)]
+    pub no_timestamps: bool,
+    /// Abort transcriptions that take longer than this
+    #[arg(long, value_name = "MS")]
+    pub timeout_ms: Option<u64>,
+    /// Don't log progress and the transcribed text
+    #[arg(short = 'q', long)]
+    pub quiet: bool,
+}
+
+impl TranscribeArgs {
+    /// Convert the flags into params.
+    ///
+    /// Starts from [SenseVoiceFullParamsBuilder::for_language] for the given language, or from
+    /// beam search defaults when `--beam-size` is given, and applies the other flags on top.
+    ///
+    /// # Returns
+    /// The params, or the error of [SenseVoiceFullParamsBuilder::try_build] for out of range
+    /// values or beam search flags combined with greedy decoding.
+    pub fn to_params(&self) -> Result<SenseVoiceFullParams, SenseVoiceError> {
+        let mut builder = match self.beam_size {
+            Some(beam_size) => {
+                SenseVoiceFullParamsBuilder::new(SenseVoiceDecodingStrategy::SamplingBeamSearch)
+                    .beam_search_beam_size(beam_size)
+            }
+            None => SenseVoiceFullParamsBuilder::for_language(&self.language),
+        }
+        .language(&self.language)
+        .offset_ms(self.offset_ms)
+        .duration_ms(self.duration_ms)
+        .audio_ctx(self.audio_ctx)
+        .max_tokens(self.max_tokens)
+        .temperature(self.temperature)
+        .no_timestamps(self.no_timestamps);
+        if let Some(n_threads) = self.n_threads {
+            builder = builder.n_threads(n_threads);
+        }
+        if let Some(best_of) = self.best_of {
+            builder = builder.greedy_best_of(best_of);
+        }
+        if let Some(patience) = self.beam_patience {
+            builder = builder.beam_patience(patience);
+        }
+        if let Some(length_penalty) = self.length_penalty {
+            builder = builder.length_penalty(length_penalty);
+        }
+        if let Some(timeout_ms) = self.timeout_ms {
+            builder = builder.timeout(Duration::from_millis(timeout_ms));
+        }
+        if self.quiet {
+            builder = builder.quiet();
+        }
+        builder.try_build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        transcribe: TranscribeArgs,
+    }
+
+    fn parse(args: &[&str]) -> Result<SenseVoiceFullParams, SenseVoiceError> {
+        let cli = Cli::try_parse_from(std::iter::once("tool").chain(args.iter().copied()))
+            .expect("valid flags");
+        cli.transcribe.to_params()
+    }
+
+    #[test]
+    fn flags_map_to_params() {
+        let params = parse(&["-t", "2", "-l", "en", "--no-timestamps", "-q"]).unwrap();
+        assert_eq!(params.n_threads, 2);
+        assert_eq!(params.language, "en");
+        assert!(params.no_timestamps);
+        assert!(!params.print_progress && !params.print_timestamps);
+        assert!(params.strategy == SenseVoiceDecodingStrategy::SamplingGreedy);
+
+        let params = parse(&["--beam-size", "3", "--beam-patience", "1.5"]).unwrap();
+        assert!(params.strategy == SenseVoiceDecodingStrategy::SamplingBeamSearch);
+        assert_eq!(params.beam_search.beam_size, 3);
+        assert_eq!(params.language, "auto");
+
+        let params = parse(&["--timeout-ms", "1500"]).unwrap();
+        assert_eq!(params.timeout, Some(Duration::from_millis(1500)));
+
+        let params = parse(&["--temperature", "0.4"]).unwrap();
+        assert_eq!(params.temperature, 0.4);
+
+        let params = parse(&["--max-tokens", "200"]).unwrap();
+        assert_eq!(params.max_tokens, 200);
+    }
+
+    #[test]
+    fn invalid_flags_are_rejected() {
+        assert!(matches!(
+            parse(&["-l", "en", "--beam-patience", "1.5"]),
+            Err(SenseVoiceError::IncompatibleParams(_))
+        ));
+    }
+}
@@ -0,0 +1,355 @@
+//! Download model files into a local cache, verified against a known SHA-256 digest.
+//!
+//! [download_model] fetches a model once and returns the cached path on later calls, ready for
+//! [crate::SenseVoiceContext::new_with_params]. Progress is reported to a callback as
+//! `(downloaded, total)` bytes, `total` being None when the server doesn't send a length.
+//!
+//! # Cache location
+//! [cache_dir] is, in this order:
+//! * the directory in the `SENSE_VOICE_CACHE_DIR` environment variable, see [CACHE_DIR_ENV],
+//! * `$XDG_CACHE_HOME/sense-voice-cpp-rs`,
+//! * `$HOME/.cache/sense-voice-cpp-rs`,
+//! * `%LOCALAPPDATA%\sense-voice-cpp-rs`,
+//! * `sense-voice-cpp-rs` in [std::env::temp_dir].
+//!
+//! Set the environment variable to move the cache, or download to a path of your choosing
+//! with [download_model_to]. Cached files are stored as `<sha256>/<file name>`, the digest in
+//! lowercase hex and the file name being the last segment of the URL, so a cached file always
+//! has the requested digest, and models of the same name with different digests don't
+//! collide.
+//!
+//! # Crash safety
+//! A download is written to `<file name>.<pid>.<n>.part` next to its destination, `n` counting
+//! the downloads of the process, and renamed into place only once its checksum matched, so a
+//! crashed or interrupted download never leaves a corrupt model at the destination, and
+//! concurrent downloads of the same file don't write to the same `.part` file. Leftover
+//! `.part` files of crashed processes can be deleted.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::SenseVoiceError;
+
+/// Environment variable overriding [cache_dir].
+pub const CACHE_DIR_ENV: &str = "SENSE_VOICE_CACHE_DIR";
+
+/// Bytes read from the network per write and progress report.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Downloads started by this process, numbering their `.part` files.
+static PART_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// The directory [download_model] caches models in, see the [module docs](self). Not
+/// created until the first download.
+pub fn cache_dir() -> PathBuf {
+    let env_dir = |var: &str| std::env::var_os(var).filter(|dir| !dir.is_empty());
+    if let Some(dir) = env_dir(CACHE_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    let base = env_dir("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env_dir("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| env_dir("LOCALAPPDATA").map(PathBuf::from))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("sense-voice-cpp-rs")
+}
+
+/// The path of the model at `url` in [cache_dir], downloading it first if it isn't cached.
+///
+/// `sha256` is the expected digest of the file as 64 hex digits, and part of the cache path.
+/// Only verified downloads are ever moved into the cache, so a cached file is returned
+/// without hashing it again.
+/// `progress` is called after every chunk with the bytes downloaded so far and the total, if
+/// known; it isn't called for a cached file.
+///
+/// # Errors
+/// See [download_model_to]. [SenseVoiceError::IncompatibleParams] if the URL doesn't end in a
+/// file name or `sha256` isn't 64 hex digits.
+pub fn download_model(
+    url: &str,
+    sha256: &str,
+    progress: impl FnMut(u64, Option<u64>),
+) -> Result<PathBuf, SenseVoiceError> {
+    let dest = cache_path(&cache_dir(), url, sha256)?;
+    if !dest.is_file() {
+        download_model_to(url, sha256, &dest, progress)?;
+    }
+    Ok(dest)
+}
+
+/// Download the model at `url` to `dest`, replacing any file there, see [download_model].
+///
+/// # Errors
+/// * [SenseVoiceError::IncompatibleParams] if `sha256` isn't 64 hex digits.
+/// * [SenseVoiceError::DownloadFailed] if the request fails or the connection breaks off.
+/// * [SenseVoiceError::ChecksumMismatch] if the downloaded file has another digest.
+/// * [SenseVoiceError::DiskFull] if the file doesn't fit on the disk.
+/// * [SenseVoiceError::ModelFileIo] for other errors creating or writing the file.
+///
+/// `dest` is left untouched on any error.
+pub fn download_model_to(
+    url: &str,
+    sha256: &str,
+    dest: &Path,
+    progress: impl FnMut(u64, Option<u64>),
+) -> Result<(), SenseVoiceError> {
+    let expected = parse_sha256(sha256)?;
+    let response = ureq::get(url).call().map_err(|e| match e {
+        ureq::Error::Status(status, _) => SenseVoiceError::DownloadFailed {
+            status: Some(status),
+        },
+        ureq::Error::Transport(_) => SenseVoiceError::DownloadFailed { status: None },
+    })?;
+    let total = response
+        .header("Content-Length")
+        .and_then(|len| len.parse().ok());
+    write_verified(response.into_reader(), total, &expected, dest, progress)
+}
+
+/// Copy `reader` to a `.part` file next to `dest`, and rename it to `dest` if its digest is
+/// `expected`. The `.part` file is removed again on errors.
+fn write_verified(
+    reader: impl Read,
+    total: Option<u64>,
+    expected: &[u8; 32],
+    dest: &Path,
+    progress: impl FnMut(u64, Option<u64>),
+) -> Result<(), SenseVoiceError> {
+    if let Some(dir) = dest.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(io_error)?;
+    }
+    let mut part = dest.as_os_str().to_owned();
+    part.push(format!(
+        ".{}.{}.part",
+        std::process::id(),
+        PART_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let part = PathBuf::from(part);
+
+    let result = copy_hashed(reader, total, &part, progress).and_then(|actual| {
+        if actual != *expected {
+            return Err(SenseVoiceError::ChecksumMismatch {
+                expected: *expected,
+                actual,
+            });
+        }
+        fs::rename(&part, dest).map_err(io_error)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&part);
+    }
+    result
+}
+
+/// Copy `reader` to a new file at `path`, returning the SHA-256 digest of the bytes.
+fn copy_hashed(
+    mut reader: impl Read,
+    total: Option<u64>,
+    path: &Path,
+    mut progress: impl FnMut(u64, Option<u64>),
+) -> Result<[u8; 32], SenseVoiceError> {
+    let file = File::options()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(io_error)?;
+    let mut file = BufWriter::new(file);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut downloaded = 0u64;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return Err(SenseVoiceError::DownloadFailed { status: None }),
+        };
+        hasher.update(&buf[..n]);
+        file.write_all(&buf[..n]).map_err(io_error)?;
+        downloaded += n as u64;
+        progress(downloaded, total);
+    }
+    if total.is_some_and(|total| downloaded != total) {
+        // the connection closed early, report that rather than a checksum mismatch
+        return Err(SenseVoiceError::DownloadFailed { status: None });
+    }
+    let file = file.into_inner().map_err(|e| io_error(e.into_error()))?;
+    file.sync_all().map_err(io_error)?;
+    Ok(hasher.finalize().into())
+}
+
+fn io_error(e: io::Error) -> SenseVoiceError {
+    match e.kind() {
+        io::ErrorKind::StorageFull => SenseVoiceError::DiskFull,
+        kind => SenseVoiceError::ModelFileIo(kind),
+    }
+}
+
+fn parse_sha256(hex: &str) -> Result<[u8; 32], SenseVoiceError> {
+    const INVALID: SenseVoiceError =
+        SenseVoiceError::IncompatibleParams("sha256 must be 64 hex digits");
+    if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(INVALID);
+    }
+    let mut digest = [0; 32];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| INVALID)?;
+    }
+    Ok(digest)
+}
+
+/// Where [download_model] caches the model at `url` with the digest `sha256` in `cache`.
+fn cache_path(cache: &Path, url: &str, sha256: &str) -> Result<PathBuf, SenseVoiceError> {
+    parse_sha256(sha256)?;
+    Ok(cache
+        .join(sha256.to_ascii_lowercase())
+        .join(file_name(url)?))
+}
+
+/// The last path segment of `url`, without query or fragment.
+fn file_name(url: &str) -> Result<&str, SenseVoiceError> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    path.split_once('/')
+        .and_then(|(_, path)| path.rsplit('/').next())
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .ok_or(SenseVoiceError::IncompatibleParams(
+            "the model URL doesn't end in a file name",
+        ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// SHA-256 of "abc".
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "sense-voice-download-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Reads `data`, then fails.
+    struct BrokenConnection<'a>(&'a [u8]);
+
+    impl Read for BrokenConnection<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::ErrorKind::ConnectionReset.into());
+            }
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn verified_downloads_are_renamed_into_place() {
+        let dir = test_dir("ok");
+        let dest = dir.join("model.gguf");
+        let mut reports = Vec::new();
+        let expected = parse_sha256(ABC_SHA256).unwrap();
+        write_verified(&b"abc"[..], Some(3), &expected, &dest, |done, total| {
+            reports.push((done, total))
+        })
+        .unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"abc");
+        assert_eq!(reports, [(3, Some(3))]);
+        // nothing but the model is left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_downloads_leave_no_file() {
+        let dir = test_dir("fail");
+        let dest = dir.join("model.gguf");
+        let expected = parse_sha256(ABC_SHA256).unwrap();
+
+        let result = write_verified(&b"abd"[..], None, &expected, &dest, |_, _| {});
+        match result {
+            Err(SenseVoiceError::ChecksumMismatch {
+                expected: e,
+                actual,
+            }) => {
+                assert_eq!(e, expected);
+                assert_ne!(actual, expected);
+            }
+            other => panic!("expected a checksum mismatch, got {:?}", other),
+        }
+        let result = write_verified(BrokenConnection(b"ab"), None, &expected, &dest, |_, _| {});
+        assert!(matches!(
+            result,
+            Err(SenseVoiceError::DownloadFailed { status: None })
+        ));
+        // a body shorter than announced is a network failure too
+        let result = write_verified(&b"abc"[..], Some(4), &expected, &dest, |_, _| {});
+        assert!(matches!(
+            result,
+            Err(SenseVoiceError::DownloadFailed { status: None })
+        ));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn checksums_and_file_names_are_parsed() {
+        assert_eq!(
+            parse_sha256(&ABC_SHA256.to_uppercase()).unwrap(),
+            parse_sha256(ABC_SHA256).unwrap()
+        );
+        assert!(parse_sha256(&ABC_SHA256[1..]).is_err());
+        assert!(parse_sha256(&ABC_SHA256.replace('b', "g")).is_err());
+
+        assert_eq!(
+            file_name("https://example.com/models/sense-voice-small-q8_0.gguf?download=true")
+                .unwrap(),
+            "sense-voice-small-q8_0.gguf"
+        );
+        assert!(file_name("https://example.com").is_err());
+        assert!(file_name("https://example.com/models/").is_err());
+    }
+
+    #[test]
+    fn cache_paths_are_keyed_by_digest() {
+        let cache = Path::new("cache");
+        let url = "https://example.com/a/model.gguf";
+        assert_eq!(
+            cache_path(cache, url, &ABC_SHA256.to_uppercase()).unwrap(),
+            cache.join(ABC_SHA256).join("model.gguf")
+        );
+        // the same name with another digest doesn't hit the same file
+        assert_ne!(
+            cache_path(cache, url, &ABC_SHA256.replace('b', "c")).unwrap(),
+            cache_path(cache, url, ABC_SHA256).unwrap()
+        );
+        assert!(cache_path(cache, url, "abc").is_err());
+    }
+
+    #[test]
+    fn concurrent_downloads_use_separate_part_files() {
+        let dir = test_dir("concurrent");
+        let dest = dir.join("model.gguf");
+        let expected = parse_sha256(ABC_SHA256).unwrap();
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    write_verified(&b"abc"[..], Some(3), &expected, &dest, |_, _| {}).unwrap()
+                });
+            }
+        });
+        assert_eq!(fs::read(&dest).unwrap(), b"abc");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
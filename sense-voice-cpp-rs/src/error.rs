@@ -1,9 +1,8 @@
-use std::ffi::{c_int, NulError};
+use std::ffi::{NulError, c_int};
 use std::str::Utf8Error;
 
-/// If you have not configured a logging trampoline with [crate::whisper_sys_log::install_whisper_log_trampoline] or
-/// [crate::whisper_sys_tracing::install_whisper_tracing_trampoline],
-/// then `whisper.cpp`'s errors will be output to stderr,
+/// If you have not installed the logging hooks with [crate::install_logging_hooks],
+/// then `sense-voice.cpp`'s errors will be output to stderr,
 /// so you can check there for more information upon receiving a `SenseVoiceError`.
 #[derive(Debug, Copy, Clone)]
 pub enum SenseVoiceError {
@@ -25,7 +24,7 @@ pub enum SenseVoiceError {
     FailedToDecode,
     /// Invalid number of mel bands.
     InvalidMelBands,
-    /// Invalid thread count
+    /// Invalid thread count, see [crate::max_threads].
     InvalidThreadCount,
     /// Invalid UTF-8 detected in a string from SenseVoice.
     InvalidUtf8 {
@@ -48,6 +47,125 @@ pub enum SenseVoiceError {
     InputOutputLengthMismatch { input_len: usize, output_len: usize },
     /// Input slice was not an even number of samples.
     HalfSampleMissing(usize),
+    /// Flash attention was requested but the backend can't run it for this model.
+    FlashAttnUnsupported,
+    /// Writing an in-memory model to a temporary file failed.
+    ModelBufferIo(std::io::ErrorKind),
+    /// The model contains weights of a type the compiled backend can't run.
+    UnsupportedQuantization(crate::model::TensorType),
+    /// An audio buffer doesn't have the expected layout.
+    InvalidAudioBuffer(&'static str),
+    /// Reading an audio file failed.
+    AudioFileIo(std::io::ErrorKind),
+    /// A WAV file stores samples in a format the reader can't decode, see
+    /// [crate::audio::WavReader::open].
+    UnsupportedEncoding {
+        format_tag: u16,
+        bits_per_sample: u16,
+    },
+    /// Full params combine options that don't work together or are out of range.
+    IncompatibleParams(&'static str),
+    /// Transcription took longer than [crate::SenseVoiceFullParams::timeout].
+    Timeout,
+    /// Transcription was cancelled, e.g. because the consumer of a stream went away, or the
+    /// runtime shut down during `SenseVoiceContext::new_with_params_async`.
+    Cancelled,
+    /// [crate::SenseVoiceFullParams::audio_ctx] exceeds the model's limit, see
+    /// [crate::SenseVoiceContext::max_audio_ctx].
+    AudioCtxTooLarge { audio_ctx: i32, max_audio_ctx: i32 },
+    /// Opening a model file failed, see [crate::model::validate_model].
+    ModelFileIo(std::io::ErrorKind),
+    /// A model file's header is malformed or doesn't describe a SenseVoice model.
+    InvalidModel(&'static str),
+    /// Pre-computed features don't have the number of mel bins the model was trained on, see
+    /// [crate::mel::full_parallel_mel].
+    MelDimensionMismatch { expected: usize, got: usize },
+    /// The context hasn't run the encoder yet, see [crate::full_get_encoder_embeddings].
+    NoEncoderOutput,
+    /// No model was registered under the requested name, see [crate::registry::ModelRegistry].
+    UnknownModel,
+    /// The input looks like upsampled 8 kHz audio, see
+    /// [crate::SenseVoiceFullParamsBuilder::reject_narrowband]. `high_band_db` is the measured
+    /// [crate::audio::high_band_energy_db].
+    LikelyNarrowbandAudio { high_band_db: f32 },
+    /// [crate::SenseVoiceFullParams::offset_ms] is at or past the end of the input, which is
+    /// `audio_ms` long.
+    OffsetBeyondAudio { offset_ms: i32, audio_ms: i64 },
+    /// [crate::shutdown] was called while this many contexts were still alive.
+    ContextsAlive(usize),
+    /// The input has more samples than the C API can address, about 37 hours at 16 kHz.
+    AudioTooLong { samples: usize, max: i32 },
+    /// Writing a spectrogram image failed, see `mel::save_mel_png`.
+    MelImageIo(std::io::ErrorKind),
+    /// A model file is shorter than its header says, usually an incomplete download, see
+    /// [crate::model::validate_model].
+    TruncatedModel {
+        expected_bytes: u64,
+        actual_bytes: u64,
+    },
+    /// Sample `index` of the input is NaN or infinite, see
+    /// [crate::SenseVoiceFullParamsBuilder::reject_non_finite].
+    NonFiniteAudio { index: usize },
+    /// [crate::SenseVoiceFullParams::language] names a language the loaded model's vocabulary
+    /// has no tag for, see [crate::SenseVoiceContext::special_tokens].
+    UnsupportedLanguageForModel { language: LanguageCode },
+    /// Downloading a model failed, with the HTTP status if the server answered with an error,
+    /// see [crate::download::download_model].
+    DownloadFailed { status: Option<u16> },
+    /// A downloaded model doesn't have the expected SHA-256 digest.
+    ChecksumMismatch {
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    /// The disk ran full while writing a downloaded model.
+    DiskFull,
+}
+
+/// A language code carried by [SenseVoiceError::UnsupportedLanguageForModel], stored inline so
+/// the error stays `Copy`. Codes longer than [Self::CAPACITY] bytes are cut at a character
+/// boundary.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LanguageCode {
+    bytes: [u8; Self::CAPACITY],
+    len: u8,
+}
+
+impl LanguageCode {
+    /// Longest code kept in full, in bytes.
+    pub const CAPACITY: usize = 16;
+
+    /// Wrap any code as given, e.g. `en` or `yue`; nothing is rejected, codes longer than
+    /// [Self::CAPACITY] bytes are cut.
+    pub fn new(code: &str) -> Self {
+        let mut len = code.len().min(Self::CAPACITY);
+        while !code.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0; Self::CAPACITY];
+        bytes[..len].copy_from_slice(&code.as_bytes()[..len]);
+        Self {
+            bytes,
+            len: len as u8,
+        }
+    }
+
+    /// The code as passed to [Self::new], cut to at most [Self::CAPACITY] bytes.
+    pub fn as_str(&self) -> &str {
+        // always cut at a character boundary in new
+        std::str::from_utf8(&self.bytes[..usize::from(self.len)]).unwrap_or_default()
+    }
+}
+
+impl std::fmt::Debug for LanguageCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl std::fmt::Display for LanguageCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 impl From<Utf8Error> for SenseVoiceError {
@@ -134,6 +252,105 @@ impl std::fmt::Display for SenseVoiceError {
                     size + 1
                 )
             }
+            FlashAttnUnsupported => write!(
+                f,
+                "Flash attention is not supported by the selected backend for this model."
+            ),
+            ModelBufferIo(kind) => write!(
+                f,
+                "Failed to write the in-memory model to a temporary file: {}",
+                kind
+            ),
+            UnsupportedQuantization(ty) => write!(
+                f,
+                "The model contains {} weights, which the selected backend can't run.",
+                ty
+            ),
+            InvalidAudioBuffer(reason) => write!(f, "Invalid audio buffer: {}.", reason),
+            AudioFileIo(kind) => write!(f, "Failed to read the audio file: {}", kind),
+            UnsupportedEncoding {
+                format_tag,
+                bits_per_sample,
+            } => write!(
+                f,
+                "Unsupported WAV encoding: format tag {:#06x} with {} bits per sample.",
+                format_tag, bits_per_sample
+            ),
+            IncompatibleParams(reason) => write!(f, "Incompatible parameters: {}.", reason),
+            Timeout => write!(f, "Transcription exceeded the configured timeout."),
+            Cancelled => write!(f, "Transcription was cancelled."),
+            AudioCtxTooLarge {
+                audio_ctx,
+                max_audio_ctx,
+            } => write!(
+                f,
+                "audio_ctx {} exceeds the model's maximum of {} frames.",
+                audio_ctx, max_audio_ctx
+            ),
+            ModelFileIo(kind) => write!(f, "Failed to open the model file: {}", kind),
+            InvalidModel(reason) => write!(f, "Invalid model file: {}.", reason),
+            MelDimensionMismatch { expected, got } => write!(
+                f,
+                "The model expects {} mel bins per frame, got {}.",
+                expected, got
+            ),
+            NoEncoderOutput => write!(f, "The context hasn't encoded any audio yet."),
+            UnknownModel => write!(f, "No model is registered under that name."),
+            LikelyNarrowbandAudio { high_band_db } => write!(
+                f,
+                "The audio has almost no energy above 4 kHz ({:.1} dB), it looks like 8 kHz \
+                 telephony audio upsampled to 16 kHz, which SenseVoice transcribes poorly.",
+                high_band_db
+            ),
+            OffsetBeyondAudio {
+                offset_ms,
+                audio_ms,
+            } => write!(
+                f,
+                "offset_ms {} is beyond the end of the {} ms of audio.",
+                offset_ms, audio_ms
+            ),
+            ContextsAlive(count) => write!(
+                f,
+                "Can't shut down while {} contexts are still alive.",
+                count
+            ),
+            AudioTooLong { samples, max } => write!(
+                f,
+                "The input has {} samples, more than the maximum of {}.",
+                samples, max
+            ),
+            MelImageIo(kind) => write!(f, "Failed to write the spectrogram image: {}", kind),
+            TruncatedModel {
+                expected_bytes,
+                actual_bytes,
+            } => write!(
+                f,
+                "The model file is truncated, it has {} of {} bytes. Download it again.",
+                actual_bytes, expected_bytes
+            ),
+            NonFiniteAudio { index } => write!(
+                f,
+                "Sample {} of the input is NaN or infinite, see audio::sanitize_audio.",
+                index
+            ),
+            UnsupportedLanguageForModel { language } => write!(
+                f,
+                "The loaded model has no tag for language '{}'.",
+                language
+            ),
+            DownloadFailed {
+                status: Some(status),
+            } => write!(f, "Model download failed with HTTP status {}.", status),
+            DownloadFailed { status: None } => write!(f, "Model download failed."),
+            ChecksumMismatch { expected, actual } => {
+                write!(f, "Downloaded model has SHA-256 ")?;
+                actual.iter().try_for_each(|b| write!(f, "{:02x}", b))?;
+                write!(f, ", expected ")?;
+                expected.iter().try_for_each(|b| write!(f, "{:02x}", b))?;
+                write!(f, ".")
+            }
+            DiskFull => write!(f, "Not enough disk space for the downloaded model."),
         }
     }
 }
@@ -0,0 +1,120 @@
+//! Word and character error rates, for measuring transcriptions against a reference.
+//!
+//! Both rates are the Levenshtein distance between reference and hypothesis (the minimum number
+//! of substitutions, deletions and insertions turning one into the other) divided by the length
+//! of the reference. They can exceed 1.0 when the hypothesis has many insertions.
+//!
+//! # Tokenization
+//! * [wer] splits both texts on Unicode whitespace and compares the words exactly, so case and
+//!   punctuation count as errors. Normalize both sides first if they shouldn't, e.g. by
+//!   lowercasing, stripping punctuation or with `text::normalize_text`.
+//! * [cer] compares Unicode scalar values with all whitespace removed, so spacing differences,
+//!   like the spaces SenseVoice's tokenizer leaves between CJK characters, don't count.
+//!
+//! Chinese and Japanese aren't written with spaces between words, so [wer] would treat a whole
+//! run of characters as one word. Use [cer] for them; it is the metric usually reported for
+//! Mandarin and Japanese test sets.
+
+/// Word error rate of `hypothesis` against `reference`, see the [module docs](self) for the
+/// tokenization.
+///
+/// # Returns
+/// The number of word edits divided by the number of reference words. With an empty
+/// reference, 0.0 if the hypothesis is empty as well and 1.0 otherwise.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::evaluate::wer;
+/// // one substitution ("sat" -> "sit") and one deletion ("the") out of six words
+/// let rate = wer("the cat sat on the mat", "the cat sit on mat");
+/// assert!((rate - 2.0 / 6.0).abs() < 1e-6);
+/// ```
+pub fn wer(reference: &str, hypothesis: &str) -> f32 {
+    let reference: Vec<&str> = reference.split_whitespace().collect();
+    let hypothesis: Vec<&str> = hypothesis.split_whitespace().collect();
+    error_rate(&reference, &hypothesis)
+}
+
+/// Character error rate of `hypothesis` against `reference`, see the [module docs](self) for
+/// the tokenization. Recommended over [wer] for Chinese and Japanese.
+///
+/// # Returns
+/// The number of character edits divided by the number of reference characters, whitespace
+/// not counted. With an empty reference, 0.0 if the hypothesis is empty as well and 1.0
+/// otherwise.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::evaluate::cer;
+/// // one substitution ("天" -> "田") out of five characters, spaces ignored
+/// assert_eq!(cer("今天天气好", "今 天 田 气 好"), 0.2);
+/// ```
+pub fn cer(reference: &str, hypothesis: &str) -> f32 {
+    let chars = |text: &str| -> Vec<char> { text.chars().filter(|c| !c.is_whitespace()).collect() };
+    error_rate(&chars(reference), &chars(hypothesis))
+}
+
+/// Levenshtein distance between `a` and `b`: the minimum number of substitutions, deletions
+/// and insertions of single elements that turn `a` into `b`.
+pub fn edit_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    // one row of the DP matrix, `row[j]` is the distance between the prefixes of length i and j
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(x != y);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn error_rate<T: PartialEq>(reference: &[T], hypothesis: &[T]) -> f32 {
+    if reference.is_empty() {
+        return if hypothesis.is_empty() { 0.0 } else { 1.0 };
+    }
+    edit_distance(reference, hypothesis) as f32 / reference.len() as f32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance(b"kitten", b"sitting"), 3);
+        assert_eq!(edit_distance(b"flaw", b"lawn"), 2);
+        assert_eq!(edit_distance(b"", b"abc"), 3);
+        assert_eq!(edit_distance(b"abc", b""), 3);
+        assert_eq!(edit_distance(b"same", b"same"), 0);
+    }
+
+    #[test]
+    fn word_error_rates() {
+        assert_eq!(wer("hello world", "hello world"), 0.0);
+        // whitespace differences don't matter
+        assert_eq!(wer(" hello\tworld ", "hello  world"), 0.0);
+        // case does: one substitution out of two words
+        assert_eq!(wer("hello world", "Hello world"), 0.5);
+        // "a b c d" -> "a x c d e": one substitution and one insertion out of four
+        assert_eq!(wer("a b c d", "a x c d e"), 0.5);
+        // insertions can push the rate over 1.0
+        assert_eq!(wer("yes", "yes yes yes"), 2.0);
+        assert_eq!(wer("", ""), 0.0);
+        assert_eq!(wer("", "noise"), 1.0);
+        assert_eq!(wer("speech", ""), 1.0);
+    }
+
+    #[test]
+    fn character_error_rates() {
+        // "我们去吃饭" -> "我门去吃": one substitution and one deletion out of five
+        assert_eq!(cer("我们去吃饭", "我 门 去 吃"), 0.4);
+        // as one unsegmented word, the same hypothesis is simply wrong
+        assert_eq!(wer("我们去吃饭", "我门去吃"), 1.0);
+        assert_eq!(cer("abc", "abc"), 0.0);
+        assert_eq!(cer("abcd", "abxde"), 0.5);
+        assert_eq!(cer(" ", "x"), 1.0);
+    }
+}
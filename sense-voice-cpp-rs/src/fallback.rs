@@ -0,0 +1,179 @@
+//! Temperature fallback: retry low-confidence transcriptions, as whisper does.
+//!
+//! [full_with_fallback] transcribes with each temperature in turn until an attempt passes the
+//! confidence check, and returns the best attempt otherwise. The policy is whisper's:
+//!
+//! * an attempt passes if the average log probability of its tokens, see
+//!   [crate::full_get_segment_avg_logprob], is at least [LOGPROB_THRESHOLD];
+//! * an attempt that fails that check but whose `no_speech_prob` is above
+//!   [NO_SPEECH_THRESHOLD] is accepted as silence instead of retried. The `<|nospeech|>`
+//!   probability comes from the model, not from sampling, so a higher temperature can't
+//!   change it;
+//! * an attempt whose text is stuck in a repetition loop, see [text::detect_repetition], is
+//!   retried however confident it is. Whisper checks the compression ratio of the text for
+//!   this; sampling at a higher temperature often breaks the loop;
+//! * if no attempt passes, the one with the highest average log probability wins among those
+//!   without a loop, the earliest one on ties.
+//!
+//! Both thresholds are whisper's defaults. SenseVoice's CTC output is usually more confident
+//! than whisper's autoregressive decoder, so tune them on your own audio.
+
+use crate::{
+    SenseVoiceContext, SenseVoiceFullParams,
+    error::SenseVoiceError,
+    full_get_segment_avg_logprob, full_get_segment_no_speech_prob, full_get_transcription,
+    full_parallel,
+    segment::Transcription,
+    text::{self, RepetitionInfo},
+};
+
+/// Attempts with a lower average token log probability are retried.
+pub const LOGPROB_THRESHOLD: f32 = -1.0;
+
+/// Attempts with a higher `<|nospeech|>` probability are treated as silence and not retried.
+pub const NO_SPEECH_THRESHOLD: f32 = 0.6;
+
+/// Whisper's temperature schedule: greedy first, then increasingly random sampling.
+pub const DEFAULT_TEMPERATURES: [f32; 6] = [0.0, 0.2, 0.4, 0.6, 0.8, 1.0];
+
+/// The attempt [full_with_fallback] settled on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FallbackResult {
+    pub transcription: Transcription,
+    /// The temperature the transcription was decoded with.
+    pub temperature: f32,
+    /// Average log probability of the emitted tokens.
+    pub avg_logprob: f32,
+    /// Probability of `<|nospeech|>`, see [crate::full_get_segment_no_speech_prob].
+    pub no_speech_prob: f32,
+    /// The repetition loop found in the text, if any.
+    pub repetition: Option<RepetitionInfo>,
+}
+
+impl FallbackResult {
+    /// Whether this attempt needs no retry, see the [module docs](self).
+    pub fn passed(&self) -> bool {
+        self.repetition.is_none()
+            && (self.avg_logprob >= LOGPROB_THRESHOLD || self.no_speech_prob > NO_SPEECH_THRESHOLD)
+    }
+
+    /// Whether this failed attempt is a better fallback than `other`, see the
+    /// [module docs](self).
+    fn beats(&self, other: &FallbackResult) -> bool {
+        match (self.repetition.is_none(), other.repetition.is_none()) {
+            (true, false) => true,
+            (false, true) => false,
+            _ => self.avg_logprob > other.avg_logprob,
+        }
+    }
+}
+
+/// Transcribe `data`, retrying with the next temperature while the result has low confidence.
+///
+/// # Arguments
+/// * ctx: The context to transcribe with.
+/// * base_params: The parameters of every attempt, with the temperature replaced by the
+///   attempt's entry of `temps`.
+/// * data: Mono 16 kHz samples.
+/// * temps: The temperatures to try in order, e.g. [DEFAULT_TEMPERATURES]. When empty, a
+///   single attempt uses the temperature of `base_params`.
+///
+/// # Returns
+/// The first attempt that passes the checks in the [module docs](self), otherwise the one with
+/// the highest average log probability. Errors of [full_parallel] end the loop and are
+/// returned as they are.
+pub fn full_with_fallback(
+    ctx: &mut SenseVoiceContext,
+    base_params: SenseVoiceFullParams,
+    data: &[f64],
+    temps: &[f32],
+) -> Result<FallbackResult, SenseVoiceError> {
+    let default_temps = [base_params.temperature];
+    let temps = if temps.is_empty() {
+        &default_temps[..]
+    } else {
+        temps
+    };
+
+    let mut best: Option<FallbackResult> = None;
+    for &temperature in temps {
+        let mut params = base_params.clone();
+        params.temperature = temperature;
+        full_parallel(ctx, params, data)?;
+        let transcription = full_get_transcription(ctx)?;
+        let attempt = FallbackResult {
+            repetition: text::detect_repetition(&transcription.text),
+            transcription,
+            temperature,
+            avg_logprob: full_get_segment_avg_logprob(ctx, 0),
+            no_speech_prob: full_get_segment_no_speech_prob(ctx, 0),
+        };
+        if attempt.passed() {
+            return Ok(attempt);
+        }
+        if best.as_ref().is_none_or(|best| attempt.beats(best)) {
+            best = Some(attempt);
+        }
+    }
+    Ok(best.expect("at least one temperature was tried"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::segment::Timings;
+
+    fn attempt(avg_logprob: f32, no_speech_prob: f32) -> FallbackResult {
+        FallbackResult {
+            transcription: Transcription {
+                text: String::new(),
+                segments: Vec::new(),
+                tokens: Vec::new(),
+                language: None,
+                timings: Timings::default(),
+            },
+            temperature: 0.0,
+            avg_logprob,
+            no_speech_prob,
+            repetition: None,
+        }
+    }
+
+    #[test]
+    fn confidence_checks() {
+        assert!(attempt(-0.3, 0.0).passed());
+        assert!(attempt(LOGPROB_THRESHOLD, 0.0).passed());
+        assert!(!attempt(-1.5, 0.0).passed());
+        assert!(!attempt(-1.5, NO_SPEECH_THRESHOLD).passed());
+        assert!(attempt(-1.5, 0.9).passed());
+    }
+
+    #[test]
+    fn thresholds_match_whisper_defaults() {
+        // whisper.cpp's whisper_full_default_params: logprob_thold, no_speech_thold and a
+        // schedule from temperature 0.0 in steps of temperature_inc 0.2
+        assert_eq!(LOGPROB_THRESHOLD, -1.0);
+        assert_eq!(NO_SPEECH_THRESHOLD, 0.6);
+        assert_eq!(DEFAULT_TEMPERATURES[0], 0.0);
+        for pair in DEFAULT_TEMPERATURES.windows(2) {
+            assert!((pair[1] - pair[0] - 0.2).abs() < 1e-6);
+        }
+        assert_eq!(DEFAULT_TEMPERATURES.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn repetition_forces_a_retry() {
+        let looping = FallbackResult {
+            repetition: Some(RepetitionInfo {
+                phrase: "thank you".to_string(),
+                count: 6,
+            }),
+            ..attempt(-0.1, 0.0)
+        };
+        assert!(!looping.passed());
+        // a less confident attempt without a loop is the better fallback
+        assert!(attempt(-2.0, 0.0).beats(&looping));
+        assert!(!looping.beats(&attempt(-2.0, 0.0)));
+        assert!(attempt(-1.2, 0.0).beats(&attempt(-1.5, 0.0)));
+    }
+}
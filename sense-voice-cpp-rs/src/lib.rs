@@ -1,8 +1,33 @@
+//! Rust bindings for sense-voice.cpp.
+//!
+//! # Tracing
+//! With the `tracing` feature, the following spans are emitted. Fields marked "on completion"
+//! are only recorded when the call succeeds; without the feature nothing is instrumented.
+//!
+//! | Span | Level | Fields |
+//! |------|-------|--------|
+//! | `sense_voice.load` | info | `model_path`, `use_gpu`, `backend` (on completion) |
+//! | `sense_voice.transcribe` | info | `n_samples`, `duration_ms`, `n_threads`; on completion `backend`, `feature_ms`, `encode_ms`, `decode_ms`, `rtf` |
+//! | `sense_voice.audio.extract_channel` | debug | `n_samples`, `channels`, `channel_index` |
+//! | `sense_voice.audio.resample` | debug | `n_samples`, `from_rate`, `to_rate` |
+//! | `sense_voice.audio.convert` | debug | |
+//! | `sense_voice.audio.open_wav` | debug | `path` |
+//!
+//! `rtf` is the realtime factor, the processing time divided by the audio duration (below 1.0
+//! is faster than realtime). Errors are recorded as an event on the span.
+
 use std::{
-    ffi::{CStr, CString, c_int},
+    collections::HashMap,
+    ffi::{CStr, CString, c_int, c_void},
+    hash::{Hash, Hasher},
+    io::Write,
     ptr::null_mut,
-    str::FromStr,
+    sync::{
+        Arc, Once, OnceLock,
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+    },
     thread,
+    time::{Duration, Instant},
 };
 
 use ggml_aio_sys::{
@@ -10,26 +35,157 @@ use ggml_aio_sys::{
     sense_voice_full_params__bindgen_ty_2,
 };
 
-use crate::error::SenseVoiceError;
+use crate::{
+    error::{LanguageCode, SenseVoiceError},
+    model::{FrontendParams, ModelInfo, TensorType},
+    segment::{Segment, TimestampPrecision, Timings, Token, Transcription},
+    words::CharTiming,
+};
 
+pub mod audio;
+#[cfg(feature = "clap")]
+pub mod cli;
+mod common_logging;
+#[cfg(feature = "download")]
+pub mod download;
 pub mod error;
+pub mod evaluate;
+pub mod fallback;
+mod ggml_logging_hook;
+pub mod mel;
+pub mod model;
+pub mod registry;
+pub mod segment;
+mod sense_voice_logging_hook;
+pub mod source;
+pub mod stream;
+pub mod text;
+pub mod transcriber;
+pub mod words;
+
+pub use common_logging::GGMLLogLevel;
+
+/// Sample rate SenseVoice models expect, in Hz.
+pub const SENSE_VOICE_SAMPLE_RATE: u32 = 16000;
+
+/// Milliseconds of audio from the start of the input that
+/// [SenseVoiceFullParamsBuilder::detect_language_only] looks at, as much as whisper's
+/// language detection.
+pub const LANGUAGE_DETECTION_MS: usize = 30_000;
+
+/// Milliseconds of audio covered by one encoder frame, the unit of
+/// [SenseVoiceFullParams::audio_ctx], for SenseVoiceSmall.
+///
+/// Models with other frontend settings report theirs in
+/// [model::FrontendParams::encoder_frame_ms].
+pub const AUDIO_CTX_FRAME_MS: i32 = 60;
+
+/// Samples the voice activity detector of [get_speech_prob] looks at per call, 40 ms at
+/// [SENSE_VOICE_SAMPLE_RATE].
+pub const VAD_WINDOW_SAMPLES: usize = 640;
+
+/// Redirect all sense-voice.cpp and GGML logs to logging hooks installed by sense-voice-cpp-rs.
+///
+/// This will stop most logs from being output to stdout/stderr and will bring them into
+/// `log` or `tracing`, if the `log_backend` or `tracing_backend` features, respectively,
+/// are enabled. If neither is enabled, this will essentially disable logging, as they won't
+/// be output anywhere.
+///
+/// This includes the lines written when [SenseVoiceFullParams::print_progress] or
+/// [SenseVoiceFullParams::print_timestamps] are set, which are logged at info level, so
+/// they end up in the application's structured logs instead of on raw stderr.
+///
+/// Safe to call multiple times. Only has an effect the first time; use [set_native_output]
+/// to switch back and forth later.
+pub fn install_logging_hooks() {
+    crate::sense_voice_logging_hook::install_sense_voice_logging_hook();
+    crate::ggml_logging_hook::install_ggml_logging_hook();
+}
+
+/// Where the native libraries write their output, see [set_native_output].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NativeOutput {
+    /// Write everything to stderr, the libraries' default.
+    Stderr,
+    /// Drop everything, including errors.
+    Suppressed,
+    /// Forward everything to `log` or `tracing`, like [install_logging_hooks].
+    Logging,
+}
+
+/// Send all output of sense-voice.cpp and GGML to `output`.
+///
+/// Nothing the native libraries print goes to stdout: the lines of
+/// [SenseVoiceFullParams::print_progress] and [SenseVoiceFullParams::print_timestamps], model
+/// loading messages and errors all pass through their log callbacks, which write to stderr by
+/// default. This picks the callbacks regardless of those flags, e.g. [NativeOutput::Suppressed]
+/// for a tool that keeps stderr for its own messages; see
+/// [SenseVoiceFullParamsBuilder::quiet] to turn off only the per-transcription lines.
+///
+/// Unlike [install_logging_hooks] this takes effect on every call; the last call wins, also
+/// over an earlier [install_logging_hooks]. Set it before loading models, the libraries log
+/// from whichever thread runs them.
+pub fn set_native_output(output: NativeOutput) {
+    let (sense_voice, ggml): (
+        ggml_aio_sys::ggml_log_callback,
+        ggml_aio_sys::ggml_log_callback,
+    ) = match output {
+        // a null callback restores the default, which writes to stderr
+        NativeOutput::Stderr => (None, None),
+        NativeOutput::Suppressed => (Some(discard_log), Some(discard_log)),
+        NativeOutput::Logging => (
+            Some(sense_voice_logging_hook::sense_voice_logging_trampoline),
+            Some(ggml_logging_hook::ggml_logging_trampoline),
+        ),
+    };
+    unsafe {
+        ggml_aio_sys::sense_voice_log_set(sense_voice, null_mut());
+        ggml_aio_sys::ggml_log_set(ggml, null_mut());
+    }
+}
+
+unsafe extern "C" fn discard_log(
+    _: ggml_aio_sys::ggml_log_level,
+    _: *const std::ffi::c_char,
+    _: *mut c_void,
+) {
+}
+
+impl Drop for SenseVoiceContext {
+    fn drop(&mut self) {
+        if !self.ctx.is_null() {
+            unsafe { ggml_aio_sys::sense_voice_free(self.ctx) };
+            LIVE_CONTEXTS.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
 
 // following implementations are safe
 // see https://github.com/ggerganov/whisper.cpp/issues/32#issuecomment-1272790388
 unsafe impl Send for SenseVoiceContext {}
 unsafe impl Sync for SenseVoiceContext {}
 
+#[derive(Debug, Clone, Default)]
 pub struct SenseVoiceContextParameters {
-    /// Use GPU if available.
+    /// Use GPU if available, default false, also in builds with a GPU feature; see
+    /// [Self::auto] to use a GPU where the machine has one.
     pub use_gpu: bool,
 
+    /// Apply inverse text normalization (punctuation, numbers as digits), default false.
+    /// Can be changed on a loaded context with [SenseVoiceContext::set_use_itn], or for a
+    /// single transcription with [SenseVoiceFullParamsBuilder::use_itn].
     pub use_itn: bool,
     /// Enable flash attention, default false
     ///
-    /// **Warning** Can't be used with DTW. DTW will be disabled if flash_attn is true
+    /// Checked against the selected backend once the model is loaded; see
+    /// [SenseVoiceContext::new_with_params]. SenseVoice has no DTW timestamps,
+    /// so whisper's flash_attn/DTW exclusion does not apply here.
     pub flash_attn: bool,
     /// GPU device id, default 0
     pub gpu_device: c_int,
+    /// Backends to try in order, see [Self::backend_chain]. Empty by default, which uses the
+    /// first GPU if `use_gpu` is set and the CPU otherwise.
+    pub backend_chain: Vec<Backend>,
 }
 impl SenseVoiceContextParameters {
     pub fn new() -> Self {
@@ -48,6 +204,27 @@ impl SenseVoiceContextParameters {
         self
     }
 
+    /// Try the backends of `chain` in order when creating a context, moving on to the next
+    /// one when a backend isn't usable, e.g. `[Backend::Cuda, Backend::Vulkan, Backend::Cpu]`
+    /// for one binary that runs on NVIDIA, other GPUs and GPU-less machines alike. Takes
+    /// precedence over `use_gpu`; see [SenseVoiceContext::new_with_params] for the details.
+    pub fn backend_chain(&mut self, chain: &[Backend]) -> &mut Self {
+        self.backend_chain = chain.to_vec();
+        self
+    }
+
+    /// Like [Self::default], but `use_gpu` reflects whether a GPU is actually usable.
+    ///
+    /// [Self::default] leaves `use_gpu` off, also in builds with a GPU feature. This asks
+    /// ggml's backend registry at runtime instead, so the same binary runs on the GPU where
+    /// there is one and on the CPU elsewhere. Prefer this over [Self::default].
+    pub fn auto() -> Self {
+        Self {
+            use_gpu: gpu_available(),
+            ..Self::default()
+        }
+    }
+
     fn to_c_struct(&self) -> ggml_aio_sys::sense_voice_context_params {
         ggml_aio_sys::sense_voice_context_params {
             use_gpu: self.use_gpu,
@@ -56,16 +233,167 @@ impl SenseVoiceContextParameters {
             gpu_device: self.gpu_device,
             cb_eval: None,
             cb_eval_user_data: std::ptr::null_mut(),
+            gpu_backend: std::ptr::null(),
+        }
+    }
+}
+
+/// A ggml backend, for [SenseVoiceContextParameters::backend_chain].
+///
+/// The GPU backends are only usable if the crate was built with their feature (`cuda`,
+/// `hipblas`, `metal` or `vulkan`) and the machine has a device for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// NVIDIA GPUs, the `cuda` feature.
+    Cuda,
+    /// AMD GPUs, the `hipblas` feature.
+    Hip,
+    /// Apple GPUs, the `metal` feature.
+    Metal,
+    /// Vulkan GPUs of any vendor, the `vulkan` feature.
+    Vulkan,
+    /// The CPU, always available.
+    Cpu,
+}
+
+impl Backend {
+    /// Name of the backend in ggml's backend registry.
+    pub fn registry_name(self) -> &'static str {
+        match self {
+            Backend::Cuda => "CUDA",
+            Backend::Hip => "ROCm",
+            Backend::Metal => "Metal",
+            Backend::Vulkan => "Vulkan",
+            Backend::Cpu => "CPU",
         }
     }
 }
 
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.registry_name())
+    }
+}
+
+static BACKEND_INIT: Once = Once::new();
+
+/// Number of [SenseVoiceContext]s that haven't been dropped yet, checked by [shutdown].
+static LIVE_CONTEXTS: AtomicUsize = AtomicUsize::new(0);
+
+/// One-time global ggml setup, run before the first context is created.
+///
+/// ggml initializes its timer and backend registry lazily on first use, which isn't safe when
+/// several threads load models at the same time. Every constructor of [SenseVoiceContext]
+/// calls this, so callers never need to.
+fn init_backends() {
+    BACKEND_INIT.call_once(|| unsafe {
+        ggml_aio_sys::ggml_time_init();
+        // enumerating the devices builds the backend registry
+        ggml_aio_sys::ggml_backend_dev_count();
+    });
+}
+
+/// Free ggml's process-global resources, for plugin hosts and test harnesses that load and
+/// unload the library repeatedly.
+///
+/// Must only be called once every [SenseVoiceContext] has been dropped; contexts own their
+/// backends, buffers and schedulers and free them on drop, so afterwards only ggml's
+/// process-global state is left. This frees the lookup tables ggml allocates when quantizing
+/// to the i-quant formats. Creating a context afterwards works as before, ggml sets up whatever
+/// it needs again.
+///
+/// ggml's backend registry stays: it is a static inside ggml without a teardown function, and
+/// with the backends linked into the library, as this crate builds them, it only holds a few
+/// descriptors per device. The CPU backend keeps no thread pool between graph computations,
+/// there's nothing to stop.
+///
+/// # Returns
+/// Err([SenseVoiceError::ContextsAlive]) without freeing anything if contexts still exist.
+///
+/// # C++ equivalent
+/// `void ggml_quantize_free(void)`
+pub fn shutdown() -> Result<(), SenseVoiceError> {
+    let live = LIVE_CONTEXTS.load(Ordering::SeqCst);
+    if live > 0 {
+        return Err(SenseVoiceError::ContextsAlive(live));
+    }
+    unsafe { ggml_aio_sys::ggml_quantize_free() };
+    Ok(())
+}
+
+/// Whether ggml found a GPU device, the same check sense-voice.cpp makes when `use_gpu` is set.
+fn gpu_available() -> bool {
+    init_backends();
+    (0..unsafe { ggml_aio_sys::ggml_backend_dev_count() }).any(|i| unsafe {
+        ggml_aio_sys::ggml_backend_dev_type(ggml_aio_sys::ggml_backend_dev_get(i))
+            == ggml_aio_sys::GGML_BACKEND_DEVICE_TYPE_GPU
+    })
+}
+
 #[derive(Debug)]
 pub struct SenseVoiceContext {
     pub(crate) ctx: *mut ggml_aio_sys::sense_voice_context,
+    /// Bits of the smoothed realtime factor of past transcriptions, 0 before the first one,
+    /// see [estimate_transcription_time].
+    realtime_factor: AtomicU32,
+    /// See [Self::label].
+    label: Option<String>,
+    /// [Self::special_tokens], read from the vocabulary on first use.
+    special_tokens: OnceLock<Vec<(i32, String)>>,
 }
 
 impl SenseVoiceContext {
+    pub(crate) fn from_raw(ctx: *mut ggml_aio_sys::sense_voice_context) -> Self {
+        Self {
+            ctx,
+            realtime_factor: AtomicU32::new(0),
+            label: None,
+            special_tokens: OnceLock::new(),
+        }
+    }
+
+    /// A free-form label for bookkeeping, e.g. the model name and version or the warmup state
+    /// of a context in a pool. None until [Self::set_label] is called.
+    ///
+    /// Purely informational: the label lives on the Rust side only, is never passed to the
+    /// library and doesn't affect transcription.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Set the label returned by [Self::label], replacing the previous one.
+    pub fn set_label(&mut self, label: impl Into<String>) {
+        self.label = Some(label.into());
+    }
+
+    /// Remove the label, returning it.
+    pub fn take_label(&mut self) -> Option<String> {
+        self.label.take()
+    }
+
+    /// Smoothed realtime factor (processing time divided by audio duration) of the
+    /// transcriptions this context ran so far, None before the first one. See
+    /// [estimate_transcription_time].
+    pub fn realtime_factor(&self) -> Option<f32> {
+        match self.realtime_factor.load(Ordering::Relaxed) {
+            0 => None,
+            bits => Some(f32::from_bits(bits)),
+        }
+    }
+
+    /// Fold the realtime factor of a finished transcription into [Self::realtime_factor].
+    fn record_realtime_factor(&self, rtf: f32) {
+        if !(rtf.is_finite() && rtf > 0.0) {
+            return;
+        }
+        let smoothed = match self.realtime_factor() {
+            Some(previous) => previous + REALTIME_FACTOR_SMOOTHING * (rtf - previous),
+            None => rtf,
+        };
+        self.realtime_factor
+            .store(smoothed.to_bits(), Ordering::Relaxed);
+    }
+
     /// Create a new SenseVoiceContext from a file, with parameters.
     ///
     /// # Arguments
@@ -75,24 +403,397 @@ impl SenseVoiceContext {
     /// # Returns
     /// Ok(Self) on success, Err(SenseVoiceError) on failure.
     ///
+    /// If `flash_attn` is requested but the backend the context initialized on can't run
+    /// flash attention for this model's head layout, the context is freed again and
+    /// [SenseVoiceError::FlashAttnUnsupported] is returned instead of running an unsupported graph.
+    /// Likewise, a model stored in a type the backend has no kernel for is rejected with
+    /// [SenseVoiceError::UnsupportedQuantization].
+    ///
+    /// The file's header is checked with [model::validate_model] before any weights are
+    /// loaded, so a truncated download fails with [SenseVoiceError::TruncatedModel] and
+    /// files that aren't SenseVoice models with [SenseVoiceError::InvalidModel].
+    ///
+    /// The first call also sets up ggml's backend registry, guarded so that loading models from
+    /// several threads at once is safe.
+    ///
+    /// # Backend fallback
+    /// With a [SenseVoiceContextParameters::backend_chain], the backends are tried in order
+    /// and the first context that loads is returned; [Self::active_backend] tells which one it
+    /// runs on. A backend is skipped when it has no device (including GPU backends the crate
+    /// wasn't built with), when its device fails to initialize, or when it can't run the model
+    /// as requested ([SenseVoiceError::FlashAttnUnsupported],
+    /// [SenseVoiceError::UnsupportedQuantization]). Unlike with `use_gpu`, a GPU backend
+    /// in the chain never silently falls back to the CPU; add [Backend::Cpu] at the end for
+    /// that. Every skipped backend is logged as a warning with its error, and if none of them
+    /// works, the error of the last one is returned. Each attempt loads the model anew, so a
+    /// broken model file fails the same way for every backend.
+    ///
     /// # C++ equivalent
     /// `struct whisper_context * sense_voice_small_init_from_file_with_params(const char * path_model, struct whisper_context_params params);`
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "sense_voice.load",
+            skip(parameters),
+            fields(model_path = path, use_gpu = parameters.use_gpu, backend = tracing::field::Empty),
+            err
+        )
+    )]
     pub fn new_with_params(
         path: &str,
         parameters: SenseVoiceContextParameters,
     ) -> Result<Self, SenseVoiceError> {
+        init_backends();
+        model::validate_model(path)?;
         let path_cstr = CString::new(path)?;
+        if parameters.backend_chain.is_empty() {
+            return Self::init_from_file(&path_cstr, &parameters, parameters.to_c_struct());
+        }
+        let mut last_error = SenseVoiceError::InitError;
+        for &backend in &parameters.backend_chain {
+            let name = CString::new(backend.registry_name())?;
+            let mut c_params = parameters.to_c_struct();
+            c_params.use_gpu = backend != Backend::Cpu;
+            if c_params.use_gpu {
+                c_params.gpu_backend = name.as_ptr();
+            }
+            match Self::init_from_file(&path_cstr, &parameters, c_params) {
+                Ok(ctx) => return Ok(ctx),
+                Err(e) => {
+                    common_logging::generic_warn!(
+                        "{} backend unavailable, trying the next one: {}",
+                        backend,
+                        e
+                    );
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Like [Self::new_with_params], but loads the model on tokio's blocking thread pool, so
+    /// a server can load models during startup without stalling the runtime's worker threads.
+    /// Must be called from within a tokio runtime. Requires the `tokio` feature.
+    ///
+    /// Only the load is async: the result is the same `Send` context [Self::new_with_params]
+    /// returns, and transcribing with it blocks as usual. Run transcriptions in
+    /// `tokio::task::spawn_blocking` or use `stream::transcribe_stream`.
+    ///
+    /// # Returns
+    /// The result of [Self::new_with_params], or Err([SenseVoiceError::Cancelled]) if the
+    /// runtime shut down before the load finished. A panic while loading is resumed here.
+    #[cfg(feature = "tokio")]
+    pub async fn new_with_params_async(
+        path: &str,
+        parameters: SenseVoiceContextParameters,
+    ) -> Result<Self, SenseVoiceError> {
+        let path = path.to_owned();
+        let load = tokio::task::spawn_blocking(move || Self::new_with_params(&path, parameters));
+        match load.await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(_) => Err(SenseVoiceError::Cancelled),
+        }
+    }
+
+    /// Load the model with `c_params` and check that the backend it landed on can run it as
+    /// `parameters` request.
+    fn init_from_file(
+        path: &CStr,
+        parameters: &SenseVoiceContextParameters,
+        c_params: ggml_aio_sys::sense_voice_context_params,
+    ) -> Result<Self, SenseVoiceError> {
         let ctx = unsafe {
-            ggml_aio_sys::sense_voice_small_init_from_file_with_params(
-                path_cstr.as_ptr(),
-                parameters.to_c_struct(),
-            )
+            ggml_aio_sys::sense_voice_small_init_from_file_with_params(path.as_ptr(), c_params)
         };
         if ctx.is_null() {
-            Err(SenseVoiceError::InitError)
-        } else {
-            Ok(Self { ctx })
+            return Err(SenseVoiceError::InitError);
+        }
+        LIVE_CONTEXTS.fetch_add(1, Ordering::SeqCst);
+        // from here on dropping `ctx` frees the native context
+        let ctx = Self::from_raw(ctx);
+        if parameters.flash_attn
+            && !unsafe { ggml_aio_sys::sense_voice_flash_attn_supported(ctx.ctx) }
+        {
+            return Err(SenseVoiceError::FlashAttnUnsupported);
+        }
+        if let Some(ty) = ctx
+            .model_info()
+            .tensor_types
+            .into_iter()
+            .map(|(ty, _)| ty)
+            .find(|ty| !unsafe { ggml_aio_sys::sense_voice_type_supported(ctx.ctx, ty.to_raw()) })
+        {
+            return Err(SenseVoiceError::UnsupportedQuantization(ty));
+        }
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("backend", ctx.active_backend());
+        Ok(ctx)
+    }
+
+    /// Name of the ggml backend this context actually runs on, e.g. `CUDA0`, `Metal` or `CPU`.
+    ///
+    /// This reflects the runtime state, not the request: with `use_gpu` set but no usable
+    /// GPU (missing driver, failed initialization, CPU-only build) the context silently
+    /// falls back to the CPU, and this returns `CPU`. Returns an empty string if the context
+    /// has no initialized state.
+    pub fn active_backend(&self) -> String {
+        let ptr = unsafe { ggml_aio_sys::sense_voice_active_backend(self.ctx) };
+        if ptr.is_null() {
+            return String::new();
+        }
+        unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Run a one second dummy transcription, so that GPU backends compile and load their
+    /// kernels now instead of during the first real request.
+    ///
+    /// CUDA and Metal build or load kernels the first time a graph runs, which can add seconds
+    /// to the first transcription. Latency-sensitive services should call this once after
+    /// construction, before serving traffic. The dummy result is cleared with
+    /// [reset_decode_state], which keeps the allocated buffers.
+    ///
+    /// A no-op on the CPU backend (see [Self::active_backend]), which has nothing to compile,
+    /// and for a context without state.
+    ///
+    /// # Returns
+    /// The errors of [full_parallel].
+    pub fn warmup(&mut self) -> Result<(), SenseVoiceError> {
+        let backend = self.active_backend();
+        if backend.is_empty() || backend == "CPU" {
+            return Ok(());
+        }
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .quiet()
+            .build();
+        let silence = vec![0.0; SENSE_VOICE_SAMPLE_RATE as usize];
+        full_parallel(self, params, &silence)?;
+        reset_decode_state(self);
+        Ok(())
+    }
+
+    /// Key-value metadata stored in the model file, such as `general.name`,
+    /// `general.license` or the architecture parameters.
+    ///
+    /// SenseVoice models are GGUF files, which always carry a key-value header; scalar values
+    /// are rendered as strings and array values (e.g. the tokenizer vocabulary) are left out.
+    /// Which keys are present depends on the converter that produced the file, so the map
+    /// may be empty.
+    pub fn metadata(&self) -> HashMap<String, String> {
+        let n = unsafe { ggml_aio_sys::sense_voice_model_meta_count(self.ctx) };
+        (0..n)
+            .filter_map(|i| {
+                let key = unsafe { ggml_aio_sys::sense_voice_model_meta_key_by_index(self.ctx, i) };
+                let val =
+                    unsafe { ggml_aio_sys::sense_voice_model_meta_val_str_by_index(self.ctx, i) };
+                if key.is_null() || val.is_null() {
+                    return None;
+                }
+                let key = unsafe { CStr::from_ptr(key) }
+                    .to_string_lossy()
+                    .into_owned();
+                let val = unsafe { CStr::from_ptr(val) }
+                    .to_string_lossy()
+                    .into_owned();
+                Some((key, val))
+            })
+            .collect()
+    }
+
+    /// Number of segments produced by the most recent transcription on this context.
+    ///
+    /// SenseVoice decodes its whole input with CTC and has no segmentation of its own, so
+    /// this is 1 after a successful [full_parallel] call and 0 before the first call or after
+    /// [reset_ctx_state]/[reset_decode_state]. Use [SenseVoiceContext::segment] to fetch one.
+    ///
+    /// # C++ equivalent
+    /// `int sense_voice_full_n_segments(struct sense_voice_context * ctx)`
+    pub fn n_segments(&self) -> usize {
+        let n = unsafe { ggml_aio_sys::sense_voice_full_n_segments(self.ctx) };
+        n.max(0) as usize
+    }
+
+    /// Get segment `i` of the most recent transcription, without prefix tags.
+    ///
+    /// # Returns
+    /// Ok(None) if `i` is not below [SenseVoiceContext::n_segments].
+    pub fn segment(&mut self, i: usize) -> Result<Option<Segment>, SenseVoiceError> {
+        if i >= self.n_segments() {
+            return Ok(None);
+        }
+        let i = i as c_int;
+        let start_ms = unsafe { ggml_aio_sys::sense_voice_full_get_segment_t0(self.ctx, i) };
+        let end_ms = unsafe { ggml_aio_sys::sense_voice_full_get_segment_t1(self.ctx, i) };
+        let text = full_get_text(self, false)?;
+        Ok(Some(Segment {
+            start_ms,
+            end_ms,
+            text,
+            no_speech_prob: unsafe {
+                ggml_aio_sys::sense_voice_full_get_segment_no_speech_prob(self.ctx, i)
+            },
+            avg_logprob: unsafe {
+                ggml_aio_sys::sense_voice_full_get_segment_avg_logprob(self.ctx, i)
+            },
+        }))
+    }
+
+    /// Describe the loaded model: the header's file type and the storage type of every tensor.
+    ///
+    /// See [model] for an overview of the common quantization types.
+    ///
+    /// # Returns
+    /// A [ModelInfo] listing each tensor type present and how many tensors use it.
+    pub fn model_info(&self) -> ModelInfo {
+        let ftype = unsafe { ggml_aio_sys::sense_voice_model_ftype(self.ctx) };
+        let tensor_types = (0..ggml_aio_sys::GGML_TYPE_COUNT)
+            .filter_map(|raw| {
+                let n = unsafe { ggml_aio_sys::sense_voice_model_n_tensors_of_type(self.ctx, raw) };
+                (n > 0).then(|| (TensorType::from_raw(raw), n as usize))
+            })
+            .collect();
+        ModelInfo {
+            ftype,
+            tensor_types,
+            max_audio_ctx: self.max_audio_ctx(),
+            frontend: self.frontend(),
+        }
+    }
+
+    /// The feature extraction settings of the loaded model, read from its metadata.
+    ///
+    /// See [model::FrontendParams] for the values of SenseVoiceSmall and how they differ from
+    /// whisper's.
+    pub fn frontend(&self) -> FrontendParams {
+        let raw = unsafe { ggml_aio_sys::sense_voice_model_frontend(self.ctx) };
+        FrontendParams::from_raw(&raw)
+    }
+
+    /// Number of tokens in the model's vocabulary, 25055 for SenseVoiceSmall.
+    pub fn n_vocab(&self) -> usize {
+        unsafe { ggml_aio_sys::sense_voice_model_n_vocab(self.ctx) as usize }
+    }
+
+    /// The special tokens of the loaded model's vocabulary with their ids, in id order: the
+    /// `<|...|>` tags (languages, emotions, audio events, `<|withitn|>`/`<|woitn|>` and
+    /// other control tags) and the SentencePiece control tokens `<unk>`, `<s>` and `</s>`.
+    ///
+    /// Read from the vocabulary rather than hardcoded, so the set and the ids vary with the
+    /// model version: later releases may add languages or events, and fine-tuned models may
+    /// define their own tags. Use it to recognize tags in decoded text or to find the ids of
+    /// tags, e.g. in the distributions from `full_parallel_with_probs` (`research` feature).
+    pub fn special_tokens(&self) -> Vec<(i32, String)> {
+        self.cached_special_tokens().to_vec()
+    }
+
+    /// [Self::special_tokens] without a copy, scanning the vocabulary only on the first call.
+    fn cached_special_tokens(&self) -> &[(i32, String)] {
+        self.special_tokens.get_or_init(|| {
+            (0..self.n_vocab() as c_int)
+                .filter_map(|id| {
+                    let ptr = unsafe { ggml_aio_sys::sense_voice_token_to_str(self.ctx, id) };
+                    if ptr.is_null() {
+                        return None;
+                    }
+                    let token = unsafe { CStr::from_ptr(ptr) }.to_str().ok()?;
+                    is_special_token(token).then(|| (id, token.to_string()))
+                })
+                .collect()
+        })
+    }
+
+    /// Largest valid [SenseVoiceFullParams::audio_ctx] for the loaded model, in encoder frames.
+    ///
+    /// One frame covers [AUDIO_CTX_FRAME_MS] of audio, so a clip of `d` ms needs
+    /// `d.div_ceil(AUDIO_CTX_FRAME_MS)` frames.
+    pub fn max_audio_ctx(&self) -> i32 {
+        unsafe { ggml_aio_sys::sense_voice_model_n_audio_ctx(self.ctx) }
+    }
+
+    /// Whether the next transcription applies inverse text normalization, i.e. adds
+    /// punctuation and writes numbers as digits. Starts out as
+    /// [SenseVoiceContextParameters::use_itn].
+    pub fn use_itn(&self) -> bool {
+        unsafe { ggml_aio_sys::sense_voice_use_itn(self.ctx) }
+    }
+
+    /// Switch inverse text normalization on or off for the following transcriptions, see
+    /// [full_parallel_text_both] for both variants of one input.
+    ///
+    /// # C++ equivalent
+    /// `void sense_voice_set_use_itn(struct sense_voice_context * ctx, bool use_itn)`
+    pub fn set_use_itn(&mut self, use_itn: bool) {
+        unsafe { ggml_aio_sys::sense_voice_set_use_itn(self.ctx, use_itn) }
+    }
+
+    /// Create a new SenseVoiceContext from a model held in memory, with parameters.
+    ///
+    /// The native loader only reads models from a path, so the buffer is written to a new
+    /// file in [std::env::temp_dir] and removed again once loading finished (successfully or
+    /// not). The file is created exclusively (`O_EXCL`), so an existing file or symlink of
+    /// the same name is never written through, and on Unix it is only readable by the owner.
+    ///
+    /// # Arguments
+    /// * buffer: The bytes of a GGUF model file.
+    /// * parameters: A parameter struct containing the parameters to use.
+    ///
+    /// # Returns
+    /// Ok(Self) on success, Err(SenseVoiceError) on failure.
+    pub fn new_from_buffer_with_params(
+        buffer: &[u8],
+        parameters: SenseVoiceContextParameters,
+    ) -> Result<Self, SenseVoiceError> {
+        static BUFFER_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let (path, mut file) = loop {
+            let path = std::env::temp_dir().join(format!(
+                "sense-voice-{}-{}.gguf",
+                std::process::id(),
+                BUFFER_ID.fetch_add(1, Ordering::Relaxed)
+            ));
+            let mut options = std::fs::File::options();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            match options.open(&path) {
+                Ok(file) => break (path, file),
+                // left behind by another process, or planted there: pick another name
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(SenseVoiceError::ModelBufferIo(e.kind())),
+            }
+        };
+        if let Err(e) = file.write_all(buffer).and_then(|_| file.flush()) {
+            let _ = std::fs::remove_file(&path);
+            return Err(SenseVoiceError::ModelBufferIo(e.kind()));
         }
+        drop(file);
+        let result = match path.to_str() {
+            Some(path) => Self::new_with_params(path, parameters),
+            None => Err(SenseVoiceError::InitError),
+        };
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    /// Create a new SenseVoiceContext from the model embedded at build time.
+    ///
+    /// Requires the `embed-model` feature and the `SENSE_VOICE_EMBED_MODEL` environment
+    /// variable to hold the (absolute) path of the model file when the crate is compiled.
+    /// The whole model ends up in the binary's read-only data, so the binary grows by the
+    /// size of the model file (several hundred MB for an f16 SenseVoiceSmall, less for
+    /// quantized variants), and loading still needs a temporary copy on disk, see
+    /// [SenseVoiceContext::new_from_buffer_with_params].
+    #[cfg(feature = "embed-model")]
+    pub fn new_embedded(parameters: SenseVoiceContextParameters) -> Result<Self, SenseVoiceError> {
+        #[cfg(not(docsrs))]
+        static EMBEDDED_MODEL: &[u8] = include_bytes!(env!("SENSE_VOICE_EMBED_MODEL"));
+        // docs.rs documents the feature without a model to embed
+        #[cfg(docsrs)]
+        static EMBEDDED_MODEL: &[u8] = &[];
+        Self::new_from_buffer_with_params(EMBEDDED_MODEL, parameters)
     }
 }
 
@@ -102,6 +803,17 @@ pub enum SenseVoiceDecodingStrategy {
     SamplingBeamSearch,
 }
 
+/// Format of the progress lines logged when [SenseVoiceFullParams::print_progress] is set.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProgressStyle {
+    /// Only the percentage, e.g. `progress =  90%`.
+    #[default]
+    Percentage,
+    /// The percentage plus the time the finished stage took,
+    /// e.g. `progress =  90% (encode took 412.30 ms)`.
+    Full,
+}
+
 #[derive(Clone)]
 pub struct SenseVoiceFullParams {
     pub strategy: SenseVoiceDecodingStrategy,
@@ -110,16 +822,58 @@ pub struct SenseVoiceFullParams {
     pub n_max_text_ctx: i32,
     pub offset_ms: i32,
     pub duration_ms: i32,
+    /// Don't use earlier text as context, see [SenseVoiceFullParamsBuilder::no_context].
+    pub no_context: bool,
     pub no_timestamps: bool,
     pub single_segment: bool,
+    /// Log progress at the end of each stage (features, encode, decode).
+    ///
+    /// Written through the library's log callback at info level, see [install_logging_hooks].
     pub print_progress: bool,
+    /// Log the transcribed text with its time span once decoding finished.
+    ///
+    /// Written through the library's log callback at info level, see [install_logging_hooks].
     pub print_timestamps: bool,
+    /// How detailed the lines written for [Self::print_progress] are.
+    pub progress_style: ProgressStyle,
     pub debug_mode: bool,
+    /// Encoder frames to use, not used by the encoder yet, see
+    /// [SenseVoiceFullParamsBuilder::audio_ctx].
     pub audio_ctx: i32,
     pub greedy: GreedyParams,
+    /// Length penalty alpha for beam search, not used by the CTC decoder yet, see
+    /// [SenseVoiceFullParamsBuilder::length_penalty].
+    pub length_penalty: f32,
+    /// Sampling temperature of the CTC decoder, see [SenseVoiceFullParamsBuilder::temperature].
+    pub temperature: f32,
     pub beam_search: BeamSearchParams,
+    /// Abort transcriptions that take longer than this, see [SenseVoiceFullParamsBuilder::timeout].
+    pub timeout: Option<Duration>,
+    /// Refuse likely narrowband input, see [SenseVoiceFullParamsBuilder::reject_narrowband].
+    pub reject_narrowband: bool,
+    /// Log a warning for clipped input, see [SenseVoiceFullParamsBuilder::warn_on_clipping].
+    pub warn_on_clipping: bool,
+    /// Refuse input with NaN or infinite samples, see
+    /// [SenseVoiceFullParamsBuilder::reject_non_finite].
+    pub reject_non_finite: bool,
+    /// Only identify the language, see [SenseVoiceFullParamsBuilder::detect_language_only].
+    pub detect_language_only: bool,
+    /// Samples are at full scale ±1.0 instead of the 16-bit integer range, see
+    /// [SenseVoiceFullParamsBuilder::normalized_input].
+    pub normalized_input: bool,
+    /// Override the context's ITN setting for this transcription, see
+    /// [SenseVoiceFullParamsBuilder::use_itn].
+    pub use_itn: Option<bool>,
+    /// Text tokens to keep per segment, 0 for no limit, see
+    /// [SenseVoiceFullParamsBuilder::max_tokens].
+    pub max_tokens: i32,
+    /// Phrases to boost and their logit bias, see [SenseVoiceFullParamsBuilder::hotwords].
+    pub hotwords: Vec<(String, f32)>,
+    raw_params_hook: Option<RawParamsHook>,
 }
 
+type RawParamsHook = Arc<dyn Fn(&mut sense_voice_full_params) + Send + Sync>;
+
 #[derive(Clone)]
 pub struct GreedyParams {
     pub best_of: i32,
@@ -128,6 +882,8 @@ pub struct GreedyParams {
 #[derive(Clone)]
 pub struct BeamSearchParams {
     pub beam_size: i32,
+    /// Not used by the CTC decoder yet, see [SenseVoiceFullParamsBuilder::beam_patience].
+    pub patience: f32,
 }
 
 impl SenseVoiceFullParams {
@@ -139,37 +895,202 @@ impl SenseVoiceFullParams {
         SenseVoiceFullParamsBuilder::new(strategy).build()
     }
 
-    pub fn to_c_struct(&self) -> sense_voice_full_params {
+    /// Params tuned for transcribing `code`, see [SenseVoiceFullParamsBuilder::for_language].
+    pub fn for_language(code: &str) -> Self {
+        SenseVoiceFullParamsBuilder::for_language(code).build()
+    }
+
+    /// A copy of these params that decodes with `strategy`, e.g. to compare greedy decoding
+    /// and beam search on the same settings.
+    ///
+    /// Reset to the defaults of [SenseVoiceFullParamsBuilder::new] for `strategy`:
+    /// [Self::strategy], [Self::greedy], [Self::beam_search] (beam size and patience) and
+    /// [Self::length_penalty]. All other fields, including the language, thread count,
+    /// temperature, timeout and a raw params hook, are kept.
+    pub fn with_strategy(&self, strategy: SenseVoiceDecodingStrategy) -> Self {
+        let defaults = SenseVoiceFullParamsBuilder::new(strategy).params;
+        Self {
+            strategy,
+            greedy: defaults.greedy,
+            beam_search: defaults.beam_search,
+            length_penalty: defaults.length_penalty,
+            ..self.clone()
+        }
+    }
+
+    /// A 64-bit fingerprint of the fields that affect the transcription, e.g. for the key of a
+    /// cache of results next to a hash of the audio.
+    ///
+    /// Identical settings give the same value across runs, processes and platforms: the
+    /// fields are fed into FNV-1a as little-endian bytes, floats by their bit patterns (with
+    /// `-0.0` counted as `0.0`), so nothing depends on [std::collections::hash_map::RandomState]
+    /// or on the platform. The value changes whenever a field that can change the output
+    /// changes:
+    ///
+    /// * hashed: [Self::strategy], [Self::language], [Self::n_max_text_ctx], [Self::offset_ms],
+    ///   [Self::duration_ms], [Self::no_context], [Self::no_timestamps], [Self::single_segment],
+    ///   [Self::audio_ctx], [Self::greedy], [Self::length_penalty], [Self::temperature],
+    ///   [Self::beam_search], [Self::reject_narrowband], [Self::detect_language_only],
+    ///   [Self::normalized_input], [Self::use_itn], [Self::max_tokens] and [Self::hotwords];
+    /// * not hashed, as they only affect logging, diagnostics or whether a run finishes:
+    ///   [Self::n_threads], [Self::print_progress], [Self::print_timestamps],
+    ///   [Self::progress_style], [Self::debug_mode], [Self::timeout],
+    ///   [Self::warn_on_clipping] and [Self::reject_non_finite].
+    ///
+    /// A hook set with [SenseVoiceFullParamsBuilder::with_raw_params] can change any C field
+    /// and is not part of the fingerprint, so include your own key for it. With a temperature
+    /// above 0 decoding samples at random, so equal fingerprints don't mean equal results.
+    /// The [Hash] implementation hashes the same fields.
+    pub fn params_fingerprint(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The C struct sense-voice.cpp reads, with the callbacks and the hotword bias unset;
+    /// [full_parallel] and friends fill those in per call.
+    ///
+    /// The struct's `language` points into the returned [CString]: keep the string alive
+    /// for as long as the struct is passed to the library.
+    ///
+    /// The struct is built field by field from the bindgen bindings, so a field added to or
+    /// removed from `sense_voice_full_params` in `sense-voice.h` fails to compile here. What
+    /// compiles but breaks silently is a library whose struct layout differs from the
+    /// bindings, e.g. after updating the vendored sources without regenerating them; the
+    /// `full_params_layout_matches_the_library` test compares every field offset with
+    /// `sense_voice_full_params_layout` from the library and fails then. When either fires,
+    /// map the new or changed C field here, give it a builder method if users should set it,
+    /// and add it to the field lists of the test and of `sense_voice_full_params_layout`.
+    pub fn to_c_struct(&self) -> (sense_voice_full_params, CString) {
         let c_language =
             CString::new(self.language.as_str()).expect("Failed to convert language to C string");
 
         let c_strategy = self.strategy as u32;
 
-        let c_struct = sense_voice_full_params {
+        let mut c_struct = sense_voice_full_params {
             strategy: c_strategy,
             n_threads: self.n_threads,
             language: c_language.as_ptr(),
             n_max_text_ctx: self.n_max_text_ctx,
             offset_ms: self.offset_ms,
-            duration_ms: self.offset_ms,
+            duration_ms: self.duration_ms,
+            no_context: self.no_context,
             no_timestamps: self.no_timestamps,
             single_segment: self.single_segment,
             print_progress: self.print_progress,
             print_timestamps: self.print_timestamps,
+            progress_style: match self.progress_style {
+                ProgressStyle::Percentage => ggml_aio_sys::SENSE_VOICE_PROGRESS_PERCENT,
+                ProgressStyle::Full => ggml_aio_sys::SENSE_VOICE_PROGRESS_FULL,
+            },
             debug_mode: self.debug_mode,
             audio_ctx: self.audio_ctx,
             greedy: sense_voice_full_params__bindgen_ty_1 {
                 best_of: self.greedy.best_of,
             },
+            length_penalty: self.length_penalty,
+            temperature: self.temperature,
             beam_search: sense_voice_full_params__bindgen_ty_2 {
                 beam_size: self.beam_search.beam_size,
+                patience: self.beam_search.patience,
             },
             progress_callback: None,
             progress_callback_user_data: null_mut(),
+            abort_callback: None,
+            abort_callback_user_data: null_mut(),
+            probs_callback: None,
+            probs_callback_user_data: null_mut(),
+            detect_language: self.detect_language_only,
+            max_tokens: self.max_tokens,
+            // set per call from the hotwords, see run_full
+            logit_bias: std::ptr::null(),
         };
 
+        if let Some(hook) = &self.raw_params_hook {
+            hook(&mut c_struct);
+        }
+
         // Return both the C struct and the CString to keep it alive
-        c_struct
+        (c_struct, c_language)
+    }
+}
+
+impl Hash for SenseVoiceFullParams {
+    /// Hashes the fields that affect the transcription, see
+    /// [SenseVoiceFullParams::params_fingerprint].
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // -0.0 and 0.0 are the same setting
+        fn bits(value: f32) -> [u8; 4] {
+            (value + 0.0).to_bits().to_le_bytes()
+        }
+
+        state.write_u8(match self.strategy {
+            SenseVoiceDecodingStrategy::SamplingGreedy => 0,
+            SenseVoiceDecodingStrategy::SamplingBeamSearch => 1,
+        });
+        state.write(self.language.as_bytes());
+        state.write_u8(0xff);
+        for value in [
+            self.n_max_text_ctx,
+            self.offset_ms,
+            self.duration_ms,
+            self.audio_ctx,
+            self.greedy.best_of,
+            self.beam_search.beam_size,
+            self.max_tokens,
+        ] {
+            state.write(&value.to_le_bytes());
+        }
+        for value in [
+            self.length_penalty,
+            self.temperature,
+            self.beam_search.patience,
+        ] {
+            state.write(&bits(value));
+        }
+        for flag in [
+            self.no_context,
+            self.no_timestamps,
+            self.single_segment,
+            self.reject_narrowband,
+            self.detect_language_only,
+            self.normalized_input,
+        ] {
+            state.write_u8(u8::from(flag));
+        }
+        state.write_u8(match self.use_itn {
+            None => 0,
+            Some(false) => 1,
+            Some(true) => 2,
+        });
+        for (phrase, bias) in &self.hotwords {
+            state.write(phrase.as_bytes());
+            state.write_u8(0xff);
+            state.write(&bits(*bias));
+        }
+    }
+}
+
+/// 64-bit FNV-1a, a fixed algorithm unlike [std::collections::hash_map::DefaultHasher], which
+/// may change between Rust releases.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
     }
 }
 
@@ -186,17 +1107,34 @@ impl SenseVoiceFullParamsBuilder {
                 thread::available_parallelism().map_or(4, |n| n.get() as i32),
             ),
             language: "auto".to_string(),
-            n_max_text_ctx: 16384,
+            n_max_text_ctx: 0,
             offset_ms: 0,
             duration_ms: 0,
+            no_context: true,
             no_timestamps: false,
             single_segment: true,
             print_progress: true,
             print_timestamps: true,
+            progress_style: ProgressStyle::default(),
             debug_mode: false,
             audio_ctx: 0,
             greedy: GreedyParams { best_of: -1 },
-            beam_search: BeamSearchParams { beam_size: -1 },
+            length_penalty: -1.0,
+            temperature: 0.0,
+            beam_search: BeamSearchParams {
+                beam_size: -1,
+                patience: -1.0,
+            },
+            timeout: None,
+            reject_narrowband: false,
+            warn_on_clipping: true,
+            reject_non_finite: false,
+            detect_language_only: false,
+            normalized_input: false,
+            use_itn: None,
+            max_tokens: 0,
+            hotwords: Vec::new(),
+            raw_params_hook: None,
         };
 
         // Set strategy-specific defaults
@@ -212,31 +1150,157 @@ impl SenseVoiceFullParamsBuilder {
         Self { params }
     }
 
+    /// Start from params tuned for the SenseVoice language code `code`; setters called
+    /// afterwards override the tuned values.
+    ///
+    /// The known codes `zh`, `yue`, `en`, `ja` and `ko` pin [SenseVoiceFullParams::language]
+    /// instead of relying on language identification, which is unreliable on short clips and
+    /// between close languages: it often mixes up Mandarin and Cantonese, and identifies
+    /// Japanese kanji runs as Chinese. Apart from the language they use the generic greedy
+    /// settings of [SenseVoiceFullParams::default_params]; sense-voice.cpp's CTC decoder takes
+    /// the best path per frame for every strategy (see [Self::beam_patience]), so a wider
+    /// search wouldn't change the transcript.
+    ///
+    /// Any other code, including `auto`, gets the same settings with automatic language
+    /// identification. Codes are matched case insensitively.
+    pub fn for_language(code: &str) -> Self {
+        let code = code.to_ascii_lowercase();
+        match code.as_str() {
+            "zh" | "yue" | "en" | "ja" | "ko" => {
+                Self::new(SenseVoiceDecodingStrategy::SamplingGreedy).language(&code)
+            }
+            _ => Self::new(SenseVoiceDecodingStrategy::SamplingGreedy),
+        }
+    }
+
+    /// Set the number of threads used for feature extraction, encoding and decoding.
+    ///
+    /// The value is checked when the params are built: [Self::build] clamps it to
+    /// `1..=`[max_threads], [Self::try_build] rejects values outside that range.
     pub fn n_threads(mut self, n_threads: i32) -> Self {
         self.params.n_threads = n_threads;
         self
     }
 
+    /// Language code to transcribe, e.g. "en" or "yue", or "auto" to identify it. Codes other
+    /// than "auto" must have a `<|code|>` tag in the loaded model's vocabulary, transcribing
+    /// fails with [SenseVoiceError::UnsupportedLanguageForModel] otherwise.
     pub fn language(mut self, language: &str) -> Self {
         self.params.language = language.to_string();
         self
     }
 
+    /// Only identify the language instead of transcribing, like whisper's `detect_language`.
+    ///
+    /// The decoder stops after the query frames, so the text of the result is empty and only
+    /// the tags are populated: [segment::Transcription::language] holds the detected language,
+    /// next to the emotion and event tags in [segment::Transcription::tokens]. Calls that get
+    /// samples also only pass the first [LANGUAGE_DETECTION_MS] of the input to the model, so
+    /// a long recording costs no more than its first 30 seconds. Off by default.
+    pub fn detect_language_only(mut self, detect_language_only: bool) -> Self {
+        self.params.detect_language_only = detect_language_only;
+        self
+    }
+
+    /// Apply inverse text normalization (punctuation, numbers as digits) for this
+    /// transcription only, overriding [SenseVoiceContextParameters::use_itn]. None (the
+    /// default) uses the context's setting.
+    ///
+    /// ITN is one of the query frames prepended to the encoder input, not post-processing, but
+    /// it is read per call, so one loaded model can serve normalized and verbatim requests
+    /// without a reload. The context's setting is restored when the call returns. To get both
+    /// texts for the same audio, [full_parallel_text_both] saves computing the features twice.
+    pub fn use_itn(mut self, use_itn: Option<bool>) -> Self {
+        self.params.use_itn = use_itn;
+        self
+    }
+
+    /// Stop every segment after `max_tokens` text tokens, a safety valve against repetition
+    /// loops that fill a transcript with the same phrase. 0 (the default) means no limit.
+    ///
+    /// CTC decoding emits at most one token per encoder frame ([AUDIO_CTX_FRAME_MS]), so
+    /// output can't grow without bound or hang, but a loop can still repeat a phrase for the
+    /// rest of the input. The decoded frames are cut after the token that reaches the limit,
+    /// so the text, [segment::Transcription::tokens] and their timestamps all end there; the
+    /// prefix tags don't count. With beam search the search still runs over the whole input
+    /// and each hypothesis of `full_get_nbest` is cut to the limit afterwards, so they are
+    /// ranked by their full scores. Negative values are rejected by [Self::try_build] and
+    /// raised to 0 by [Self::build].
+    pub fn max_tokens(mut self, max_tokens: i32) -> Self {
+        self.params.max_tokens = max_tokens;
+        self
+    }
+
+    /// Boost `hotwords`, e.g. names, brands or commands, by adding each phrase's bias to the
+    /// logits of its tokens on every CTC frame.
+    ///
+    /// Each phrase is split into the model's SentencePiece pieces by greedy longest match
+    /// against the vocabulary, every word starting with the `▁` word-start marker, so spell
+    /// and case phrases the way the model writes them; characters no piece covers are
+    /// skipped. A piece of several phrases gets the sum of their biases. Biases of 1.0 to 3.0
+    /// are a reasonable start, negative ones suppress a phrase.
+    ///
+    /// The bias is context free, which limits what it can do: CTC decodes every frame on its
+    /// own, so the pieces of a phrase are boosted everywhere, not as a sequence. Common
+    /// pieces like `▁the` or single CJK characters shift the whole transcript, and large
+    /// biases insert them where nothing was said. The confidence values of the result
+    /// ([full_get_segment_avg_logprob], `no_speech_prob`) are computed from the biased
+    /// distribution. SenseVoice has no text prompt to fall back to, its CTC decoder doesn't
+    /// condition on earlier text.
+    pub fn hotwords(mut self, hotwords: &[(String, f32)]) -> Self {
+        self.params.hotwords = hotwords.to_vec();
+        self
+    }
+
+    /// Maximum number of text tokens to keep for a transcription, 0 (the default) for the
+    /// model's maximum.
+    ///
+    /// CTC decoding emits at most one token per encoder frame, so the maximum is the model's
+    /// audio context ([ModelInfo::max_audio_ctx]); larger values are clamped to it with a
+    /// warning when transcribing. Negative values are rejected by [Self::try_build] and raised to 0 by
+    /// [Self::build].
     pub fn n_max_text_ctx(mut self, n_max_text_ctx: i32) -> Self {
         self.params.n_max_text_ctx = n_max_text_ctx;
         self
     }
 
+    /// Start transcribing `offset_ms` into the input, 0 (the default) for the start. Negative
+    /// values count as 0.
+    ///
+    /// The samples before the offset are cut off before the front end runs, so the model
+    /// never sees them and the timestamps of the result count from the offset: add
+    /// `offset_ms` to map them back onto the input. Transcriptions of samples fail with
+    /// [SenseVoiceError::OffsetBeyondAudio] if the offset isn't before the end of the input.
     pub fn offset_ms(mut self, offset_ms: i32) -> Self {
         self.params.offset_ms = offset_ms;
         self
     }
 
+    /// Transcribe at most `duration_ms` of the input from [Self::offset_ms] on, 0 (the
+    /// default) or a negative value for everything up to the end. The samples after it are
+    /// cut off like those before the offset.
     pub fn duration_ms(mut self, duration_ms: i32) -> Self {
         self.params.duration_ms = duration_ms;
         self
     }
 
+    /// Whether to start every window without the text of the previous one as context,
+    /// like whisper's `no_context`. Defaults to true.
+    ///
+    /// In whisper, true keeps a hallucinated phrase or topic from carrying over into the
+    /// following windows, while false makes long transcripts more coherent (names, spelling)
+    /// at the risk of the model "running away" with earlier mistakes. With
+    /// [Self::single_segment] the whole input is decoded as one window, so there is no
+    /// previous window and the flag has no effect.
+    ///
+    /// Forwarded to sense-voice.cpp, whose CTC decoder currently has no text context at all:
+    /// every window is decoded from its audio alone, which matches true. The setter exists so
+    /// params stay portable once context conditioning lands, and for parity with whisper.
+    pub fn no_context(mut self, no_context: bool) -> Self {
+        self.params.no_context = no_context;
+        self
+    }
+
     pub fn no_timestamps(mut self, no_timestamps: bool) -> Self {
         self.params.no_timestamps = no_timestamps;
         self
@@ -257,11 +1321,34 @@ impl SenseVoiceFullParamsBuilder {
         self
     }
 
+    /// Turn off every line sense-voice.cpp prints for a transcription in one call: clears
+    /// [Self::print_progress] and [Self::print_timestamps]. SenseVoice has no equivalent of
+    /// whisper's `print_realtime` and `print_special`, so there is nothing else to clear.
+    ///
+    /// Model loading messages, warnings and errors still reach the log callback, see
+    /// [set_native_output] to silence or redirect those too.
+    pub fn quiet(self) -> Self {
+        self.print_progress(false).print_timestamps(false)
+    }
+
+    pub fn progress_style(mut self, progress_style: ProgressStyle) -> Self {
+        self.params.progress_style = progress_style;
+        self
+    }
+
     pub fn debug_mode(mut self, debug_mode: bool) -> Self {
         self.params.debug_mode = debug_mode;
         self
     }
 
+    /// Number of encoder frames to use, 0 (the default) for the model's full context.
+    /// Currently a no-op beyond the range check: sense-voice.cpp's encoder always runs on the
+    /// whole input, so the value neither shortens encoding nor changes the transcript.
+    ///
+    /// Each frame covers [AUDIO_CTX_FRAME_MS] of audio. Values above
+    /// [SenseVoiceContext::max_audio_ctx] make [full_parallel] fail with
+    /// [SenseVoiceError::AudioCtxTooLarge]; to transcribe less audio, pass fewer samples or
+    /// use [Self::duration_ms].
     pub fn audio_ctx(mut self, audio_ctx: i32) -> Self {
         self.params.audio_ctx = audio_ctx;
         self
@@ -276,76 +1363,1999 @@ impl SenseVoiceFullParamsBuilder {
         self.params.beam_search.beam_size = beam_size;
         self
     }
-    pub fn build(self) -> SenseVoiceFullParams {
-        self.params
+
+    /// Beam search patience, whisper's stopping rule of `patience * beam_size` finished
+    /// hypotheses. Currently a no-op: the value is forwarded to the C params, but nothing in
+    /// sense-voice.cpp reads it.
+    ///
+    /// sense-voice.cpp's CTC decoder takes the best path per frame for every strategy, and
+    /// the prefix beam search behind the n-best list (`full_get_nbest`, `research` feature)
+    /// keeps a fixed number of beams over every frame, with no early stopping to tune. The
+    /// field is kept for parity with whisper; values must be at least 1.0, or -1.0 (the
+    /// default), and require [SenseVoiceDecodingStrategy::SamplingBeamSearch], see
+    /// [Self::try_build].
+    pub fn beam_patience(mut self, patience: f32) -> Self {
+        self.params.beam_search.patience = patience;
+        self
     }
-}
 
-pub fn get_speech_prob(ctx: &mut SenseVoiceContext, data: &[f64]) -> f32 {
-    if data.is_empty() {
-        return -1.0f32;
+    /// Abort a transcription with [SenseVoiceError::Timeout] once it ran longer than `timeout`.
+    ///
+    /// The deadline is checked cooperatively through sense-voice.cpp's abort callback: between
+    /// the feature, encode and decode stages, and between graph nodes on the CPU backend. A
+    /// single long-running GPU kernel can't be interrupted, so calls may overrun the timeout
+    /// by up to one stage there.
+    ///
+    /// This installs its own abort callback, replacing one set through [Self::with_raw_params].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.params.timeout = Some(timeout);
+        self
     }
-    let ret = unsafe {
-        ggml_aio_sys::sense_voice_get_speech_prob(ctx.ctx, data.as_ptr(), data.len() as c_int, 8)
-    };
-    ret
-}
 
-pub fn full_parallel(
+    /// Length penalty alpha for ranking beam search hypotheses, whisper's
+    /// `((5 + length) / 6) ^ alpha`. Currently a no-op like [Self::beam_patience]: the value
+    /// is forwarded to the C params, but the CTC decoder ranks hypotheses by plain log
+    /// probability.
+    ///
+    /// Values must be in `0.0..=1.0`, or -1.0 (the default), and require
+    /// [SenseVoiceDecodingStrategy::SamplingBeamSearch], see [Self::try_build].
+    pub fn length_penalty(mut self, length_penalty: f32) -> Self {
+        self.params.length_penalty = length_penalty;
+        self
+    }
+
+    /// Check the input with [audio::is_likely_narrowband] before transcribing and fail with
+    /// [SenseVoiceError::LikelyNarrowbandAudio] if it looks like upsampled 8 kHz audio.
+    ///
+    /// Off by default: the check costs an FFT pass over the input and has false positives,
+    /// see its limitations, so enable it where you'd rather tell users about poor input than
+    /// return a poor transcript. Only applies to calls that get samples, not to
+    /// [mel::full_parallel_mel].
+    pub fn reject_narrowband(mut self, reject_narrowband: bool) -> Self {
+        self.params.reject_narrowband = reject_narrowband;
+        self
+    }
+
+    /// Measure [audio::clipping_ratio] before transcribing and log a warning if more than
+    /// [audio::CLIPPING_WARN_RATIO] of the samples are at full scale.
+    ///
+    /// On by default: the check is a single pass over the input and never fails the
+    /// transcription, it only points at a likely cause of a poor transcript. Like
+    /// [Self::reject_narrowband], only applies to calls that get samples.
+    pub fn warn_on_clipping(mut self, warn_on_clipping: bool) -> Self {
+        self.params.warn_on_clipping = warn_on_clipping;
+        self
+    }
+
+    /// Take samples with full scale at ±1.0, as the decoders in [audio] produce them, and
+    /// scale them by [audio::FRONTEND_SCALE] for the front end.
+    ///
+    /// Off by default: the front end computes its features from samples in the 16-bit integer
+    /// range, -32768.0..=32767.0, and takes them as they are. The level isn't normalized
+    /// either way, see the [audio](audio#amplitude) module. [Self::warn_on_clipping] measures
+    /// against the full scale of the chosen range. Like [Self::reject_narrowband], only
+    /// applies to calls that get samples.
+    pub fn normalized_input(mut self, normalized_input: bool) -> Self {
+        self.params.normalized_input = normalized_input;
+        self
+    }
+
+    /// Check the input with [audio::first_non_finite] before transcribing and fail with
+    /// [SenseVoiceError::NonFiniteAudio] if any sample is NaN or infinite.
+    ///
+    /// Without the check such samples turn the features of their frames into NaN, and the
+    /// transcript into garbage. Off by default: the scan is one more pass over the input, a
+    /// comparison per sample that stops at the first hit, about a millisecond for an hour
+    /// of audio but wasted where the samples come from a trusted decoder. To repair the input
+    /// instead of refusing it, use [audio::sanitize_audio]. Like [Self::reject_narrowband],
+    /// only applies to calls that get samples.
+    pub fn reject_non_finite(mut self, reject_non_finite: bool) -> Self {
+        self.params.reject_non_finite = reject_non_finite;
+        self
+    }
+
+    /// Sample every CTC frame from `softmax(logits / temperature)` instead of taking the most
+    /// likely token.
+    ///
+    /// 0.0 (the default) decodes deterministically. Higher values produce more varied output,
+    /// which [fallback::full_with_fallback] uses to retry low-confidence transcriptions.
+    /// Frames are sampled with a generator seeded once per context, so results with a
+    /// temperature above 0.0 differ between calls.
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.params.temperature = temperature;
+        self
+    }
+    /// Mutate the raw C params directly, for fields that have no safe setter yet.
+    ///
+    /// The closure runs every time the params are converted for a transcription call,
+    /// after all safe fields have been written, so it always has the final say.
+    ///
+    /// # Safety
+    /// The closure must leave the struct valid for `sense_voice_full_parallel`:
+    /// * any pointer it stores (strings, callback user data, ...) must stay valid until the
+    ///   transcription call returns, i.e. don't point at locals of the closure itself;
+    /// * callbacks it installs must be safe to call from the thread running the transcription.
+    ///
+    /// Calling this more than once replaces the previous closure.
+    pub unsafe fn with_raw_params<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut sense_voice_full_params) + Send + Sync + 'static,
+    {
+        self.params.raw_params_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Build the params, clamping [SenseVoiceFullParams::n_threads] to `1..=`[max_threads]
+    /// and a negative [SenseVoiceFullParams::n_max_text_ctx],
+    /// [SenseVoiceFullParams::max_tokens] or [SenseVoiceFullParams::temperature] to 0, and
+    /// dropping hotwords with a bias that isn't finite.
+    pub fn build(mut self) -> SenseVoiceFullParams {
+        self.params.n_threads = self.params.n_threads.clamp(1, max_threads());
+        self.params.n_max_text_ctx = self.params.n_max_text_ctx.max(0);
+        self.params.max_tokens = self.params.max_tokens.max(0);
+        self.params.hotwords.retain(|(_, bias)| bias.is_finite());
+        self.params.temperature = self.params.temperature.max(0.0);
+        self.params
+    }
+
+    /// Build the params, failing instead of clamping.
+    ///
+    /// # Returns
+    /// Err([SenseVoiceError::InvalidThreadCount]) if the thread count is below 1 or above
+    /// [max_threads].
+    ///
+    /// Err([SenseVoiceError::IncompatibleParams]) if `n_max_text_ctx`, `max_tokens` or
+    /// `temperature` is negative, a hotword bias isn't finite, or beam
+    /// search options are set together with greedy sampling or are out of range.
+    pub fn try_build(self) -> Result<SenseVoiceFullParams, SenseVoiceError> {
+        if !(1..=max_threads()).contains(&self.params.n_threads) {
+            return Err(SenseVoiceError::InvalidThreadCount);
+        }
+        if self.params.n_max_text_ctx < 0 {
+            return Err(SenseVoiceError::IncompatibleParams(
+                "n_max_text_ctx must not be negative",
+            ));
+        }
+        if self.params.max_tokens < 0 {
+            return Err(SenseVoiceError::IncompatibleParams(
+                "max_tokens must not be negative",
+            ));
+        }
+        if self
+            .params
+            .hotwords
+            .iter()
+            .any(|(_, bias)| !bias.is_finite())
+        {
+            return Err(SenseVoiceError::IncompatibleParams(
+                "hotword biases must be finite",
+            ));
+        }
+        if !(0.0..).contains(&self.params.temperature) {
+            return Err(SenseVoiceError::IncompatibleParams(
+                "temperature must not be negative",
+            ));
+        }
+        let patience = self.params.beam_search.patience;
+        let length_penalty = self.params.length_penalty;
+        if self.params.strategy == SenseVoiceDecodingStrategy::SamplingGreedy
+            && (patience != -1.0 || length_penalty != -1.0)
+        {
+            return Err(SenseVoiceError::IncompatibleParams(
+                "beam patience and length penalty require the beam search strategy",
+            ));
+        }
+        if patience != -1.0 && patience < 1.0 {
+            return Err(SenseVoiceError::IncompatibleParams(
+                "beam patience must be at least 1.0",
+            ));
+        }
+        if length_penalty != -1.0 && !(0.0..=1.0).contains(&length_penalty) {
+            return Err(SenseVoiceError::IncompatibleParams(
+                "length penalty must be between 0.0 and 1.0",
+            ));
+        }
+        Ok(self.params)
+    }
+}
+
+/// Upper bound for [SenseVoiceFullParams::n_threads]: twice the available parallelism.
+///
+/// More threads than that only add scheduling overhead in ggml, and very large counts
+/// fail deep inside ggml's thread pool creation instead of with a usable error.
+pub fn max_threads() -> i32 {
+    thread::available_parallelism().map_or(4, |n| n.get() as i32) * 2
+}
+
+/// Whether `token` is a tag or control token, see [SenseVoiceContext::special_tokens].
+fn is_special_token(token: &str) -> bool {
+    let tag = token
+        .strip_prefix("<|")
+        .and_then(|rest| rest.strip_suffix("|>"));
+    tag.is_some_and(|name| !name.is_empty() && !name.contains(['<', '>', '|']))
+        || matches!(token, "<unk>" | "<s>" | "</s>")
+}
+
+/// Realtime factor [estimate_transcription_time] assumes before a context has transcribed
+/// anything: as long as the audio itself, slower than SenseVoiceSmall runs even on modest
+/// CPUs, so the first estimate errs on the long side.
+pub const DEFAULT_REALTIME_FACTOR: f32 = 1.0;
+
+/// Weight of the newest transcription in [SenseVoiceContext::realtime_factor], the older
+/// ones decay exponentially.
+const REALTIME_FACTOR_SMOOTHING: f32 = 0.3;
+
+/// Rough estimate of how long transcribing `n_samples` samples of 16 kHz audio will take with
+/// `ctx`, e.g. for an ETA in a progress UI or to balance load across workers.
+///
+/// Scales the audio duration by the context's [SenseVoiceContext::realtime_factor], the
+/// wall-clock time of its past transcriptions (including [SenseVoiceContext::warmup])
+/// divided by their audio duration, smoothed so recent runs count most. Before the first
+/// transcription [DEFAULT_REALTIME_FACTOR] is used.
+///
+/// This is a rough estimate. The factor mixes whatever was transcribed before: beam search
+/// or a larger `n_threads` change the speed considerably, as does other load on the machine,
+/// and a fixed per-call overhead makes very short clips slower than their length suggests.
+/// The first run on a GPU backend also includes kernel compilation, which the smoothing
+/// washes out after a few transcriptions.
+pub fn estimate_transcription_time(ctx: &SenseVoiceContext, n_samples: usize) -> Duration {
+    let rtf = ctx.realtime_factor().unwrap_or(DEFAULT_REALTIME_FACTOR);
+    let audio = Duration::from_secs_f64(n_samples as f64 / f64::from(SENSE_VOICE_SAMPLE_RATE));
+    audio.mul_f32(rtf)
+}
+
+/// Length of a sample buffer as the `int` the C API takes.
+///
+/// # Returns
+/// Err([SenseVoiceError::AudioTooLong]) for more than `i32::MAX` samples, which a cast would
+/// wrap into a negative or truncated length and let the library read out of bounds.
+pub(crate) fn c_sample_count(len: usize) -> Result<c_int, SenseVoiceError> {
+    c_int::try_from(len).map_err(|_| SenseVoiceError::AudioTooLong {
+        samples: len,
+        max: c_int::MAX,
+    })
+}
+
+/// Probability from 0.0 to 1.0 that `data` contains speech, from the voice activity detector
+/// bundled with the model. Returns -1.0 for empty input, input longer than `i32::MAX` samples
+/// or if the detector fails.
+///
+/// # Preprocessing
+/// The detector scores one window of [VAD_WINDOW_SAMPLES]: the first that many samples of
+/// `data`, zero-padded if `data` is shorter, later samples are ignored. The samples are only
+/// converted to `f32`, neither scaled nor normalized, and the detector expects them at full
+/// scale ±1.0. [full_parallel] defaults to the 16-bit integer range instead: to transcribe
+/// the same buffer, build its params with [SenseVoiceFullParamsBuilder::normalized_input],
+/// which only multiplies by the constant [audio::FRONTEND_SCALE]. Neither side normalizes,
+/// so a threshold tuned on audio at one level holds for the transcription of that audio,
+/// and boosting quiet input, e.g. with [audio::normalize_peak], changes both alike; see the
+/// [audio](audio#amplitude) module. The detector is recurrent and carries its state over
+/// from earlier calls.
+pub fn get_speech_prob(ctx: &mut SenseVoiceContext, data: &[f64]) -> f32 {
+    if data.is_empty() {
+        return -1.0f32;
+    }
+    let Ok(n_samples) = c_sample_count(data.len()) else {
+        return -1.0f32;
+    };
+    unsafe { ggml_aio_sys::sense_voice_get_speech_prob(ctx.ctx, data.as_ptr(), n_samples, 8) }
+}
+
+/// Cut the leading and trailing silence off `data`, e.g. before transcribing recordings with
+/// long silent stretches, which saves compute and avoids hallucinated text.
+///
+/// `data` is scanned in consecutive windows of [VAD_WINDOW_SAMPLES] (40 ms, the last one
+/// zero-padded), each scored with [get_speech_prob]. Everything from the start of the first
+/// window whose probability is at least `threshold` to the end of the last such window is
+/// kept, including silence in between. The detector is recurrent and carries its state over
+/// from earlier calls; a few windows of lead-in are enough for it to settle.
+///
+/// # Arguments
+/// * ctx: The context whose voice activity detector to use.
+/// * data: Mono 16 kHz samples at full scale ±1.0, as the detector expects them.
+/// * threshold: Speech probability from 0.0 to 1.0, 0.5 is a reasonable start.
+///
+/// # Returns
+/// The kept samples, as `f32` at the ±1.0 scale of `data`, and the number of samples cut
+/// from the front. Transcribe them with [SenseVoiceFullParamsBuilder::normalized_input]
+/// switched on, see [get_speech_prob]. Add `offset * 1000 / 16000` ms to the timestamps of
+/// a transcription of the kept samples to map them back onto the original timeline. If no
+/// window reaches `threshold` the samples are empty and the offset is `data.len()`.
+///
+/// Err([SenseVoiceError::UnableToCalculateEvaluation]) if the detector fails.
+pub fn trim_silence(
+    ctx: &mut SenseVoiceContext,
+    data: &[f64],
+    threshold: f32,
+) -> Result<(Vec<f32>, usize), SenseVoiceError> {
+    let mut probs = Vec::with_capacity(data.len().div_ceil(VAD_WINDOW_SAMPLES));
+    for window in data.chunks(VAD_WINDOW_SAMPLES) {
+        // the detector always reads a whole window
+        let prob = get_speech_prob(ctx, &audio::pad_to_min(window, VAD_WINDOW_SAMPLES));
+        if prob < 0.0 {
+            return Err(SenseVoiceError::UnableToCalculateEvaluation);
+        }
+        probs.push(prob);
+    }
+    Ok(match speech_windows(&probs, threshold) {
+        Some((first, last)) => {
+            let start = first * VAD_WINDOW_SAMPLES;
+            let end = ((last + 1) * VAD_WINDOW_SAMPLES).min(data.len());
+            let kept = data[start..end]
+                .iter()
+                .map(|&sample| sample as f32)
+                .collect();
+            (kept, start)
+        }
+        None => (Vec::new(), data.len()),
+    })
+}
+
+/// Check that `language` is "auto" or has a `<|code|>` tag among `special_tokens`, see
+/// [SenseVoiceContext::special_tokens]. Codes are matched case insensitively.
+fn check_language_supported<'a>(
+    language: &str,
+    special_tokens: impl IntoIterator<Item = &'a str>,
+) -> Result<(), SenseVoiceError> {
+    let code = language.to_ascii_lowercase();
+    if code == "auto" {
+        return Ok(());
+    }
+    let tag = format!("<|{code}|>");
+    if special_tokens.into_iter().any(|token| token == tag) {
+        Ok(())
+    } else {
+        Err(SenseVoiceError::UnsupportedLanguageForModel {
+            language: LanguageCode::new(language),
+        })
+    }
+}
+
+/// Indices of the first and last of `probs` that reach `threshold`.
+fn speech_windows(probs: &[f32], threshold: f32) -> Option<(usize, usize)> {
+    let first = probs.iter().position(|&p| p >= threshold)?;
+    let last = probs.iter().rposition(|&p| p >= threshold)?;
+    Some((first, last))
+}
+
+/// Transcribe `data`, mono 16 kHz samples in the 16-bit integer range, or with full scale at
+/// ±1.0 with [SenseVoiceFullParamsBuilder::normalized_input]. See the
+/// [audio](audio#amplitude) module for how the level affects the transcription.
+///
+/// # Returns
+/// 0 on success. The results are read with the `full_get_*` functions. Inputs longer than
+/// `i32::MAX` samples are rejected with [SenseVoiceError::AudioTooLong], languages the model
+/// has no tag for with [SenseVoiceError::UnsupportedLanguageForModel].
+pub fn full_parallel(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    data: &[f64],
+) -> Result<c_int, SenseVoiceError> {
+    full_parallel_cancellable(ctx, params, data, None)
+}
+
+/// Like [full_parallel], but also stops with [SenseVoiceError::Cancelled] once `cancel`
+/// returns true. `cancel` is polled through the abort callback, see
+/// [SenseVoiceFullParamsBuilder::timeout] for when that happens; it must be `Sync` as ggml
+/// calls the callback from its compute threads.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "sense_voice.transcribe",
+        skip(ctx, params, data, cancel),
+        fields(
+            n_samples = data.len(),
+            duration_ms = segment::samples_to_ms(data.len()),
+            n_threads = params.n_threads,
+            backend = tracing::field::Empty,
+            feature_ms = tracing::field::Empty,
+            encode_ms = tracing::field::Empty,
+            decode_ms = tracing::field::Empty,
+            rtf = tracing::field::Empty,
+        ),
+        err
+    )
+)]
+pub(crate) fn full_parallel_cancellable(
     ctx: &mut SenseVoiceContext,
     params: SenseVoiceFullParams,
     data: &[f64],
+    cancel: Option<&(dyn Fn() -> bool + Sync)>,
 ) -> Result<c_int, SenseVoiceError> {
+    let data = prepare_samples(&params, data)?;
+    let n_samples = data.len() as c_int;
+    run_full(ctx, params, cancel, data.len(), |ctx, c_params| unsafe {
+        ggml_aio_sys::sense_voice_full_parallel(ctx, c_params, data.as_ptr(), n_samples, 8)
+    })
+}
+
+/// Check `data` against `params`, cut it to [SenseVoiceFullParams::offset_ms] and
+/// [SenseVoiceFullParams::duration_ms] and scale it for the front end, the checks every call
+/// that gets samples shares. The result fits into a `c_int` count.
+fn prepare_samples(
+    params: &SenseVoiceFullParams,
+    data: &[f64],
+) -> Result<Vec<f64>, SenseVoiceError> {
     if data.is_empty() {
         // can randomly trigger segmentation faults if we don't check this
         return Err(SenseVoiceError::NoSamples);
     }
+    c_sample_count(data.len())?;
+    let audio_ms = segment::samples_to_ms(data.len());
+    if i64::from(params.offset_ms) >= audio_ms {
+        return Err(SenseVoiceError::OffsetBeyondAudio {
+            offset_ms: params.offset_ms,
+            audio_ms,
+        });
+    }
+    // sense-voice.cpp ignores the offset and duration of its params, so cut the input here;
+    // the offset is before the end of the input, leaving at least one sample
+    let start = segment::ms_to_samples(i64::from(params.offset_ms.max(0))) as usize;
+    let end = if params.duration_ms > 0 {
+        let n_samples = segment::ms_to_samples(i64::from(params.duration_ms)) as usize;
+        data.len().min(start + n_samples)
+    } else {
+        data.len()
+    };
+    let data = &data[start..end];
+    let data = if params.detect_language_only {
+        let n_samples = LANGUAGE_DETECTION_MS * SENSE_VOICE_SAMPLE_RATE as usize / 1000;
+        &data[..data.len().min(n_samples)]
+    } else {
+        data
+    };
+    if params.reject_non_finite
+        && let Some(index) = audio::first_non_finite(data)
+    {
+        return Err(SenseVoiceError::NonFiniteAudio {
+            index: start + index,
+        });
+    }
+    if params.reject_narrowband
+        && let Some(high_band_db) = audio::high_band_energy_db(data)
+        && high_band_db < audio::NARROWBAND_THRESHOLD_DB
+    {
+        return Err(SenseVoiceError::LikelyNarrowbandAudio { high_band_db });
+    }
+    if params.warn_on_clipping {
+        let full_scale = if params.normalized_input {
+            1.0
+        } else {
+            audio::FRONTEND_SCALE
+        };
+        let clipped = audio::clipping_ratio_at(data, full_scale);
+        if clipped > audio::CLIPPING_WARN_RATIO {
+            common_logging::generic_warn!(
+                "{:.2}% of the input samples are clipped, expect a worse transcription",
+                clipped * 100.0
+            );
+        }
+    }
+    Ok(if params.normalized_input {
+        audio::to_frontend_scale(data)
+    } else {
+        data.to_vec()
+    })
+}
 
-    let ret = unsafe {
-        ggml_aio_sys::sense_voice_full_parallel(
-            ctx.ctx,
-            &params.to_c_struct(),
-            data.as_ptr(),
-            data.len() as c_int,
-            8,
+/// Transcribe several clips in one batched forward pass, for GPU throughput.
+///
+/// Sequential calls leave most of a GPU idle on short clips. Here all clips are padded with
+/// silence to the length of the longest one and encoded and decoded together, so a batch
+/// costs about as much as one call on `clips.len()` times the longest clip. That padding is
+/// pure overhead: batch clips of similar lengths, e.g. by sorting them by length and cutting
+/// batches from the sorted list, and keep batches small enough for the device memory. On the
+/// CPU batching gains little over [full_parallel].
+///
+/// The encoder attends over the padding too, so the result of a short clip in a batch of long
+/// ones can differ slightly from transcribing it alone. Each [Transcription] carries the
+/// [segment::Timings] of the whole batch. The per-frame probabilities of
+/// `full_parallel_with_probs` and the hypotheses of `full_get_nbest` aren't available
+/// for batched clips.
+///
+/// Afterwards the context holds the result of the last clip, e.g. for [full_get_text].
+///
+/// # Arguments
+/// * ctx: The context to transcribe with.
+/// * params: The parameters for every clip, checked per clip like in [full_parallel].
+/// * clips: Mono 16 kHz samples, see [full_parallel].
+///
+/// # Returns
+/// One result per clip, in the order of `clips`. Clips that fail the checks of
+/// [full_parallel], e.g. empty ones with [SenseVoiceError::NoSamples], get their error and
+/// the others are still transcribed; if the batch itself fails, all of them get its error.
+pub fn full_parallel_batch(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    clips: &[&[f64]],
+) -> Vec<Result<Transcription, SenseVoiceError>> {
+    let prepared: Vec<_> = clips
+        .iter()
+        .map(|clip| prepare_samples(&params, clip))
+        .collect();
+    let batch: Vec<&[f64]> = prepared
+        .iter()
+        .filter_map(|clip| clip.as_deref().ok())
+        .collect();
+    let mut transcriptions = if batch.is_empty() {
+        Vec::new()
+    } else {
+        transcribe_batch(ctx, params, &batch)
+    }
+    .into_iter();
+    prepared
+        .into_iter()
+        .map(|clip| clip.and_then(|_| transcriptions.next().expect("one result per batched clip")))
+        .collect()
+}
+
+/// Run the batched transcription of the prepared `batch` and collect one result per clip.
+fn transcribe_batch(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    batch: &[&[f64]],
+) -> Vec<Result<Transcription, SenseVoiceError>> {
+    let samples: Vec<*const f64> = batch.iter().map(|clip| clip.as_ptr()).collect();
+    let n_samples: Vec<c_int> = batch.iter().map(|clip| clip.len() as c_int).collect();
+    // the forward pass runs over the padded length, count that for the realtime factor
+    let padded_len = batch.iter().map(|clip| clip.len()).max().unwrap_or(0);
+    let ret = run_full(ctx, params, None, padded_len, |ctx, c_params| unsafe {
+        ggml_aio_sys::sense_voice_full_parallel_batch(
+            ctx,
+            c_params,
+            samples.as_ptr(),
+            n_samples.as_ptr(),
+            batch.len() as c_int,
         )
+    });
+    match ret {
+        Ok(_) => (0..batch.len())
+            .map(|i| {
+                if !unsafe { ggml_aio_sys::sense_voice_batch_select(ctx.ctx, i as c_int) } {
+                    return Err(SenseVoiceError::NullPointer);
+                }
+                full_get_transcription(ctx)
+            })
+            .collect(),
+        Err(e) => vec![Err(e); batch.len()],
+    }
+}
+
+/// Check `params` against the model, install the abort callback and map the return code of
+/// `run`, which does the actual transcription of `n_samples` samples.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn run_full(
+    ctx: &mut SenseVoiceContext,
+    mut params: SenseVoiceFullParams,
+    cancel: Option<&(dyn Fn() -> bool + Sync)>,
+    n_samples: usize,
+    run: impl FnOnce(
+        *mut ggml_aio_sys::sense_voice_context,
+        &ggml_aio_sys::sense_voice_full_params,
+    ) -> c_int,
+) -> Result<c_int, SenseVoiceError> {
+    let max_audio_ctx = ctx.max_audio_ctx();
+    // one CTC token per encoder frame at most, 0 (the default) stands for that maximum
+    if params.n_max_text_ctx == 0 {
+        params.n_max_text_ctx = max_audio_ctx;
+    } else if params.n_max_text_ctx > max_audio_ctx {
+        common_logging::generic_warn!(
+            "n_max_text_ctx {} exceeds the model's maximum of {}, clamping",
+            params.n_max_text_ctx,
+            max_audio_ctx
+        );
+        params.n_max_text_ctx = max_audio_ctx;
+    }
+    if params.audio_ctx > max_audio_ctx {
+        return Err(SenseVoiceError::AudioCtxTooLarge {
+            audio_ctx: params.audio_ctx,
+            max_audio_ctx,
+        });
+    }
+    if !params.language.eq_ignore_ascii_case("auto") {
+        let special_tokens = ctx.cached_special_tokens();
+        check_language_supported(
+            &params.language,
+            special_tokens.iter().map(|(_, token)| token.as_str()),
+        )?;
+    }
+
+    // keeps c_params.language valid until the call returns
+    let (mut c_params, _c_language) = params.to_c_struct();
+    // must outlive the call like the abort check below
+    let logit_bias = hotword_bias(ctx, &params.hotwords);
+    if let Some(logit_bias) = &logit_bias {
+        c_params.logit_bias = logit_bias.as_ptr();
+    }
+    // must outlive the call, the abort callback reads it through the user data pointer
+    let abort = AbortCheck {
+        deadline: params.timeout.map(|timeout| Instant::now() + timeout),
+        cancel,
     };
-    if ret == -1 {
+    if abort.deadline.is_some() || abort.cancel.is_some() {
+        c_params.abort_callback = Some(abort_requested);
+        c_params.abort_callback_user_data = &abort as *const AbortCheck as *mut c_void;
+    }
+
+    let context_itn = params.use_itn.map(|use_itn| {
+        let previous = ctx.use_itn();
+        ctx.set_use_itn(use_itn);
+        previous
+    });
+    let started = Instant::now();
+    let ret = run(ctx.ctx, &c_params);
+    if let Some(previous) = context_itn {
+        ctx.set_use_itn(previous);
+    }
+    if ret == -7 && abort.timed_out() {
+        Err(SenseVoiceError::Timeout)
+    } else if ret == -7 && abort.cancel.is_some() {
+        Err(SenseVoiceError::Cancelled)
+    } else if ret == -1 {
         Err(SenseVoiceError::UnableToCalculateSpectrogram)
     } else if ret == 7 {
         Err(SenseVoiceError::FailedToEncode)
     } else if ret == 8 {
         Err(SenseVoiceError::FailedToDecode)
     } else if ret == 0 {
+        if n_samples > 0 {
+            let audio_secs = n_samples as f32 / SENSE_VOICE_SAMPLE_RATE as f32;
+            ctx.record_realtime_factor(started.elapsed().as_secs_f32() / audio_secs);
+        }
+        #[cfg(feature = "tracing")]
+        record_transcription(ctx, n_samples);
         Ok(ret)
     } else {
         Err(SenseVoiceError::GenericError(ret))
     }
 }
 
-#[allow(clippy::derivable_impls)] // this impl cannot be derived
-impl Default for SenseVoiceContextParameters {
-    fn default() -> Self {
-        Self {
-            use_gpu: cfg!(feature = "_gpu"),
-            use_itn: false,
-            flash_attn: false,
-            gpu_device: 0,
+/// The logit bias of `hotwords` for every token of the vocabulary, see
+/// [SenseVoiceFullParamsBuilder::hotwords]. None without hotwords.
+fn hotword_bias(ctx: &SenseVoiceContext, hotwords: &[(String, f32)]) -> Option<Vec<f32>> {
+    if hotwords.is_empty() {
+        return None;
+    }
+    let n_vocab = ctx.n_vocab();
+    let mut vocab = HashMap::with_capacity(n_vocab);
+    for id in 0..n_vocab as c_int {
+        let ptr = unsafe { ggml_aio_sys::sense_voice_token_to_str(ctx.ctx, id) };
+        if ptr.is_null() {
+            continue;
+        }
+        // the vocabulary lives as long as the context
+        if let Ok(piece) = unsafe { CStr::from_ptr(ptr) }.to_str() {
+            vocab.insert(piece, id);
+        }
+    }
+    let mut bias = vec![0.0; n_vocab];
+    for (phrase, weight) in hotwords {
+        let ids = tokenize_greedy(&vocab, phrase);
+        if ids.is_empty() {
+            common_logging::generic_warn!("hotword {:?} matches no token, ignoring it", phrase);
+        }
+        for id in ids {
+            bias[id as usize] += weight;
+        }
+    }
+    Some(bias)
+}
+
+/// Split `text` into pieces of `vocab` by greedy longest match, each word starting with the
+/// SentencePiece word-start marker `▁`. Characters no piece covers are skipped.
+fn tokenize_greedy(vocab: &HashMap<&str, c_int>, text: &str) -> Vec<c_int> {
+    let mut ids = Vec::new();
+    for word in text.split_whitespace() {
+        let word = format!("\u{2581}{word}");
+        let mut rest = word.as_str();
+        while let Some(first) = rest.chars().next() {
+            let longest = rest
+                .char_indices()
+                .map(|(i, c)| i + c.len_utf8())
+                .rev()
+                .find_map(|end| vocab.get(&rest[..end]).map(|&id| (end, id)));
+            match longest {
+                Some((end, id)) => {
+                    ids.push(id);
+                    rest = &rest[end..];
+                }
+                None => rest = &rest[first.len_utf8()..],
+            }
         }
     }
+    ids
+}
+
+/// Record backend, stage timings and realtime factor of the finished transcription on the
+/// current `sense_voice.transcribe` span.
+#[cfg(feature = "tracing")]
+fn record_transcription(ctx: &SenseVoiceContext, n_samples: usize) {
+    let timings = unsafe { ggml_aio_sys::sense_voice_get_timings(ctx.ctx) };
+    let total_ms = timings.feature_ms + timings.encode_ms + timings.decode_ms;
+    let duration_ms = segment::samples_to_ms(n_samples) as f32;
+    let span = tracing::Span::current();
+    span.record("backend", ctx.active_backend());
+    span.record("feature_ms", timings.feature_ms);
+    span.record("encode_ms", timings.encode_ms);
+    span.record("decode_ms", timings.decode_ms);
+    if duration_ms > 0.0 {
+        span.record("rtf", total_ms / duration_ms);
+    }
+}
+
+/// What the abort callback installed by [full_parallel_cancellable] checks.
+struct AbortCheck<'a> {
+    deadline: Option<Instant>,
+    cancel: Option<&'a (dyn Fn() -> bool + Sync)>,
+}
+
+impl AbortCheck<'_> {
+    fn timed_out(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn should_abort(&self) -> bool {
+        self.timed_out() || self.cancel.is_some_and(|cancel| cancel())
+    }
+}
+
+unsafe extern "C" fn abort_requested(user_data: *mut c_void) -> bool {
+    let abort = unsafe { &*(user_data as *const AbortCheck) };
+    abort.should_abort()
+}
+
+/// Like [full_parallel], but pads `data` with silence to at least `min_samples` first,
+/// see [audio::pad_to_min].
+///
+/// Use this instead of rejecting clips that are too short. The padding can make the model
+/// emit trailing tokens that weren't spoken; trimming them is the caller's responsibility.
+pub fn full_parallel_padded(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    data: &[f64],
+    min_samples: usize,
+) -> Result<c_int, SenseVoiceError> {
+    full_parallel(ctx, params, &audio::pad_to_min(data, min_samples))
+}
+
+/// Like [full_parallel], but takes the samples from an iterator, e.g. a decoder or a chain of
+/// iterator adapters, instead of a slice.
+///
+/// The iterator is drained into a buffer before transcription starts, so the whole input is
+/// held in memory at once, 8 bytes per sample (about 460 MB per hour of 16 kHz audio). The
+/// C API transcribes one contiguous buffer, so this only saves collecting it yourself. For
+/// long or unbounded sources use [stream::transcribe_iter], which buffers one window at a
+/// time.
+pub fn full_parallel_iter(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    samples: impl IntoIterator<Item = f32>,
+) -> Result<c_int, SenseVoiceError> {
+    let data: Vec<f64> = samples.into_iter().map(f64::from).collect();
+    full_parallel(ctx, params, &data)
+}
+
+/// Like [full_parallel], but takes half-precision samples, e.g. audio kept in `f16` on a GPU
+/// pipeline to halve the transfer size.
+///
+/// The native front end computes its features from `f64` samples on the CPU, there's no `f16`
+/// path into the backend, so the samples are widened first. That conversion is exact; the
+/// precision was lost when the audio was narrowed to `f16`, see [audio::f16_to_f32].
+#[cfg(feature = "half")]
+pub fn full_parallel_f16(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    samples: &[half::f16],
+) -> Result<c_int, SenseVoiceError> {
+    let data: Vec<f64> = samples.iter().map(|&sample| sample.to_f64()).collect();
+    full_parallel(ctx, params, &data)
+}
+
+/// Transcribe one channel of an interleaved multi-channel buffer.
+///
+/// Useful for capture setups where e.g. channel 0 is the microphone and channel 1 an echo
+/// reference that shouldn't be transcribed.
+///
+/// # Arguments
+/// * `interleaved` - Samples at [SENSE_VOICE_SAMPLE_RATE], ordered frame by frame.
+/// * `channels` - Number of channels in the buffer.
+/// * `channel_index` - The channel to transcribe, starting at 0.
+///
+/// # Errors
+/// [SenseVoiceError::InvalidAudioBuffer] if `channel_index >= channels` or the buffer length
+/// isn't a multiple of `channels`, otherwise the errors of [full_parallel].
+pub fn full_parallel_channel(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    interleaved: &[f32],
+    channels: usize,
+    channel_index: usize,
+) -> Result<c_int, SenseVoiceError> {
+    let mono = audio::extract_channel(interleaved, channels, channel_index)?;
+    full_parallel(ctx, params, &mono)
+}
+
+/// Run [full_parallel_cancellable] on `data` and collect the result as a [Segment] starting at
+/// `start_ms`.
+pub(crate) fn full_parallel_segment(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    data: &[f64],
+    start_ms: i64,
+    cancel: Option<&(dyn Fn() -> bool + Sync)>,
+) -> Result<Segment, SenseVoiceError> {
+    full_parallel_cancellable(ctx, params, data, cancel)?;
+    let text = full_get_text(ctx, false)?;
+    Ok(Segment {
+        start_ms,
+        end_ms: start_ms + segment::samples_to_ms(data.len()),
+        text,
+        no_speech_prob: full_get_segment_no_speech_prob(ctx, 0),
+        avg_logprob: full_get_segment_avg_logprob(ctx, 0),
+    })
+}
+
+/// Probability that segment `i` of the most recent transcription contains no speech.
+///
+/// SenseVoice predicts the language in the first output frame, and one of the candidates
+/// is a `<|nospeech|>` tag. This returns the probability the model assigned to that tag,
+/// the counterpart of whisper's per-segment `no_speech_prob`. Upstream whisper treats
+/// segments above ~0.6 as silence/music; the same threshold is a reasonable start here,
+/// tune it on your own audio.
+///
+/// Returns 0.0 for segments that don't exist or if the model has no `<|nospeech|>` token.
+///
+/// # C++ equivalent
+/// `float sense_voice_full_get_segment_no_speech_prob(struct sense_voice_context * ctx, int i_segment)`
+pub fn full_get_segment_no_speech_prob(ctx: &mut SenseVoiceContext, i: usize) -> f32 {
+    if i >= ctx.n_segments() {
+        return 0.0;
+    }
+    unsafe { ggml_aio_sys::sense_voice_full_get_segment_no_speech_prob(ctx.ctx, i as c_int) }
+}
+
+/// Average log probability of the tokens segment `i` of the most recent transcription emitted.
+///
+/// Only frames that emit a token count, i.e. not the four tag frames, CTC blanks or repeats.
+/// Values close to 0.0 mean the model was confident; whisper treats averages below -1.0 as a
+/// failed decode, see [fallback::full_with_fallback].
+///
+/// Returns 0.0 for segments that don't exist or emitted no tokens.
+///
+/// # C++ equivalent
+/// `float sense_voice_full_get_segment_avg_logprob(struct sense_voice_context * ctx, int i_segment)`
+pub fn full_get_segment_avg_logprob(ctx: &mut SenseVoiceContext, i: usize) -> f32 {
+    if i >= ctx.n_segments() {
+        return 0.0;
+    }
+    unsafe { ggml_aio_sys::sense_voice_full_get_segment_avg_logprob(ctx.ctx, i as c_int) }
+}
+
+/// Width of the rows returned by [full_get_encoder_embeddings], 512 for SenseVoiceSmall.
+///
+/// Returns 0 if the context hasn't encoded anything yet.
+#[cfg(feature = "research")]
+pub fn encoder_embedding_dim(ctx: &mut SenseVoiceContext) -> usize {
+    let mut n_dim: c_int = 0;
+    unsafe {
+        ggml_aio_sys::sense_voice_get_encoder_output(ctx.ctx, null_mut(), 0, null_mut(), &mut n_dim)
+    };
+    n_dim.max(0) as usize
+}
+
+/// The acoustic encoder's output of the last encode call, e.g. as input for speaker or
+/// emotion classifiers.
+///
+/// The embedding is flattened row-major with shape `[n_frames, n_dim]`, where `n_dim` is
+/// [encoder_embedding_dim] and `n_frames = len / n_dim`. The first 4 rows belong to the
+/// language, event, emotion and ITN query slots the model prepends; every following row
+/// covers [AUDIO_CTX_FRAME_MS] of the input audio.
+///
+/// The values reflect the last call that ran the encoder ([full_parallel] and friends) and
+/// are overwritten by the next one.
+///
+/// # Returns
+/// Err([SenseVoiceError::NoEncoderOutput]) if the context hasn't encoded anything yet.
+#[cfg(feature = "research")]
+pub fn full_get_encoder_embeddings(
+    ctx: &mut SenseVoiceContext,
+) -> Result<Vec<f32>, SenseVoiceError> {
+    let len = unsafe {
+        ggml_aio_sys::sense_voice_get_encoder_output(ctx.ctx, null_mut(), 0, null_mut(), null_mut())
+    };
+    if len <= 0 {
+        return Err(SenseVoiceError::NoEncoderOutput);
+    }
+    let mut embeddings = vec![0.0f32; len as usize];
+    unsafe {
+        ggml_aio_sys::sense_voice_get_encoder_output(
+            ctx.ctx,
+            embeddings.as_mut_ptr(),
+            len,
+            null_mut(),
+            null_mut(),
+        )
+    };
+    Ok(embeddings)
+}
+
+/// The `n` most likely transcriptions of the last [full_parallel] call with their scores,
+/// best first, e.g. for rescoring with a language model or for confidence estimation.
+///
+/// Only meaningful with [SenseVoiceDecodingStrategy::SamplingBeamSearch] and a
+/// [BeamSearchParams::beam_size] above 1: sense-voice.cpp then runs a CTC prefix beam search
+/// over the model's output and keeps up to `beam_size` hypotheses, each scored with the natural
+/// log of its CTC probability (all frame paths that collapse to it). The text returned by
+/// [full_get_text] is still the frame-wise best path, which usually but not always equals the
+/// first hypothesis. With greedy decoding or a temperature above 0.0 there is exactly one
+/// entry, the decoded text scored with the log probability of its single frame path.
+///
+/// Texts are without prefix tags, like `full_get_text(ctx, false)`. Batched segments aren't
+/// covered.
+///
+/// # Returns
+/// At most `n` entries, none before the first transcription.
+#[cfg(feature = "research")]
+pub fn full_get_nbest(
+    ctx: &mut SenseVoiceContext,
+    n: usize,
+) -> Result<Vec<(String, f32)>, SenseVoiceError> {
+    let n_hypotheses = unsafe { ggml_aio_sys::sense_voice_full_n_hypotheses(ctx.ctx) };
+    let mut nbest = Vec::new();
+    for i in 0..(n_hypotheses.max(0) as usize).min(n) {
+        let i = i as c_int;
+        let len = unsafe {
+            ggml_aio_sys::sense_voice_full_get_hypothesis_tokens(ctx.ctx, i, null_mut(), 0)
+        };
+        let mut ids = vec![0 as c_int; len.max(0) as usize];
+        unsafe {
+            ggml_aio_sys::sense_voice_full_get_hypothesis_tokens(ctx.ctx, i, ids.as_mut_ptr(), len)
+        };
+        let mut text = String::new();
+        for id in ids {
+            let ptr = unsafe { ggml_aio_sys::sense_voice_token_to_str(ctx.ctx, id) };
+            if !ptr.is_null() {
+                text.push_str(unsafe { CStr::from_ptr(ptr) }.to_str()?);
+            }
+        }
+        let score = unsafe { ggml_aio_sys::sense_voice_full_get_hypothesis_score(ctx.ctx, i) };
+        nbest.push((text, score));
+    }
+    Ok(nbest)
+}
+
+/// Like [full_parallel], but hands the output probability distribution of every CTC frame to
+/// `on_probs`, e.g. for confidence calibration research.
+///
+/// `on_probs(step, probs)` is called once per frame, in order, after the decoder ran and before
+/// this function returns. `probs` is the softmax over the whole vocabulary
+/// ([SenseVoiceContext::n_vocab] values, 25055 for SenseVoiceSmall, summing to 1) and is only
+/// borrowed for the call. Steps 0 to 3 are the language, emotion, event and itn slots, every
+/// further step covers one encoder frame ([AUDIO_CTX_FRAME_MS] of audio). The distribution is
+/// the model's, at temperature 1, whatever [SenseVoiceFullParams::temperature] is set to.
+///
+/// # Performance
+/// Every frame is copied off the backend separately, about 100 KB for SenseVoiceSmall, i.e.
+/// 1.7 MB per second of audio, a device-to-host transfer each on GPU backends. Expect decoding
+/// to slow down noticeably; the encoder, which dominates the run time, is unaffected.
+///
+/// `on_probs` runs on the calling thread inside the native decoder and must not panic: a panic
+/// can't unwind through the C frames and aborts the process.
+///
+/// # Returns
+/// The results of [full_parallel].
+#[cfg(feature = "research")]
+pub fn full_parallel_with_probs(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    data: &[f64],
+    mut on_probs: impl FnMut(usize, &[f32]),
+) -> Result<c_int, SenseVoiceError> {
+    let data = prepare_samples(&params, data)?;
+    let n_samples = data.len() as c_int;
+    let mut on_probs: &mut dyn FnMut(usize, &[f32]) = &mut on_probs;
+    run_full(ctx, params, None, data.len(), |ctx, c_params| {
+        let mut c_params = *c_params;
+        c_params.probs_callback = Some(forward_probs);
+        c_params.probs_callback_user_data = &mut on_probs as *mut _ as *mut c_void;
+        unsafe {
+            ggml_aio_sys::sense_voice_full_parallel(ctx, &c_params, data.as_ptr(), n_samples, 8)
+        }
+    })
+}
+
+#[cfg(feature = "research")]
+unsafe extern "C" fn forward_probs(
+    _ctx: *mut ggml_aio_sys::sense_voice_context,
+    step: c_int,
+    probs: *const f32,
+    n_vocab: c_int,
+    user_data: *mut c_void,
+) {
+    let on_probs = unsafe { &mut *(user_data as *mut &mut dyn FnMut(usize, &[f32])) };
+    let probs = unsafe { std::slice::from_raw_parts(probs, n_vocab as usize) };
+    on_probs(step as usize, probs);
 }
 
 pub fn full_get_text(
     ctx: &mut SenseVoiceContext,
     need_prefix: bool,
 ) -> Result<String, SenseVoiceError> {
+    full_get_text_ref(ctx, need_prefix).map(str::to_string)
+}
+
+/// Like [full_get_text], but borrowing the text from the context instead of copying it, for
+/// callers that serialize or forward it right away.
+///
+/// The text lives in a buffer of the context that sense-voice.cpp rebuilds on every call of
+/// this function, [full_get_text] and [SenseVoiceContext::segment], and clears on
+/// [full_parallel] and friends, [reset_decode_state] and [reset_ctx_state]. All of those need
+/// `&mut SenseVoiceContext`, so the borrow checker ends the returned borrow before any of them
+/// can run: the `&str` can't outlive the result it was taken from. Copy it with
+/// `to_string()` to keep it longer.
+///
+/// # Returns
+/// Err([SenseVoiceError::InvalidUtf8]) if the text isn't valid UTF-8.
+///
+/// # C++ equivalent
+/// `const char * sense_voice_full_get_text(struct sense_voice_context * ctx, bool need_prefix)`
+pub fn full_get_text_ref(
+    ctx: &mut SenseVoiceContext,
+    need_prefix: bool,
+) -> Result<&str, SenseVoiceError> {
     let ret = unsafe { ggml_aio_sys::sense_voice_full_get_text(ctx.ctx, need_prefix) };
     if ret.is_null() {
         return Err(SenseVoiceError::NullPointer);
     }
-    unsafe { Ok(String::from_str(CStr::from_ptr(ret).to_str().unwrap()).unwrap()) }
+    // SAFETY: the buffer belongs to the context's state, which only changes through calls that
+    // take `ctx` mutably, and the returned borrow holds `ctx` mutably for its lifetime
+    Ok(unsafe { CStr::from_ptr(ret) }.to_str()?)
+}
+
+/// Transcribe `data` without and with inverse text normalization, for showing a verbatim
+/// reading next to one with punctuation and numbers written as digits.
+///
+/// ITN isn't a post-processing step in SenseVoice: the `withitn`/`woitn` choice is one of the
+/// query frames prepended to the encoder input, so the model decodes different tokens for
+/// each setting and both texts can't be read from one run. This computes the features once
+/// and runs the encoder and decoder twice, which saves the front end's share of the work,
+/// not half of it; the encoder dominates the run time.
+///
+/// The context's own ITN setting is restored afterwards and [SenseVoiceFullParams::use_itn]
+/// is ignored. The context's results, e.g. for [full_get_transcription], are those of the
+/// ITN run.
+///
+/// # Arguments
+/// * ctx: The context to transcribe with.
+/// * params: The parameters for both runs.
+/// * data: Mono 16 kHz samples, checked and scaled like for [full_parallel].
+///
+/// # Returns
+/// Ok((raw, itn)), the texts without prefix tags.
+pub fn full_parallel_text_both(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    data: &[f64],
+) -> Result<(String, String), SenseVoiceError> {
+    let params = SenseVoiceFullParams {
+        use_itn: None,
+        ..params
+    };
+    // compute_mel takes the samples as they are, the checks and scaling happen here
+    let data = prepare_samples(&params, data)?;
+    let features = mel::compute_mel(ctx, &data)?;
+    let use_itn = ctx.use_itn();
+    let transcribe = |ctx: &mut SenseVoiceContext, itn: bool| {
+        ctx.set_use_itn(itn);
+        mel::full_parallel_mel(ctx, params.clone(), &features)?;
+        full_get_text(ctx, false)
+    };
+    let texts = transcribe(ctx, false).and_then(|raw| Ok((raw, transcribe(ctx, true)?)));
+    ctx.set_use_itn(use_itn);
+    texts
+}
+
+/// The text of segment `i` of the most recent transcription, without prefix tags, borrowed
+/// from the context without allocating.
+///
+/// The allocation-free counterpart of [SenseVoiceContext::segment]'s text, with the same
+/// invalidation rules as [full_get_text_ref].
+///
+/// # Returns
+/// Ok(None) if `i` is not below [SenseVoiceContext::n_segments].
+pub fn segment_text_ref(
+    ctx: &mut SenseVoiceContext,
+    i: usize,
+) -> Result<Option<&str>, SenseVoiceError> {
+    if i >= ctx.n_segments() {
+        return Ok(None);
+    }
+    // sense-voice.cpp decodes the whole input as one segment, its text is the full text
+    full_get_text_ref(ctx, false).map(Some)
+}
+
+/// Collect the result of the last [full_parallel] call into an owned [Transcription].
+///
+/// The result holds no references into the context, so it can be sent to other threads
+/// while the context is reused.
+pub fn full_get_transcription(
+    ctx: &mut SenseVoiceContext,
+) -> Result<Transcription, SenseVoiceError> {
+    let tokens = full_get_tokens(ctx)?;
+    let language = tokens
+        .first()
+        .and_then(|token| segment::language_from_tag(&token.text));
+
+    let mut segments = Vec::with_capacity(ctx.n_segments());
+    for i in 0..ctx.n_segments() {
+        segments.extend(ctx.segment(i)?);
+    }
+
+    let timings = unsafe { ggml_aio_sys::sense_voice_get_timings(ctx.ctx) };
+    Ok(Transcription {
+        text: full_get_text(ctx, false)?,
+        segments,
+        tokens,
+        language,
+        timings: Timings {
+            feature_ms: timings.feature_ms,
+            encode_ms: timings.encode_ms,
+            decode_ms: timings.decode_ms,
+        },
+    })
+}
+
+/// Character-level timestamps of segment `i` of the most recent transcription, for languages
+/// without word boundaries such as Chinese and Japanese.
+///
+/// Each character gets a share of the span of the token it was decoded in, see
+/// [words::chars_from_tokens] for the heuristic. Tags are skipped.
+///
+/// # Returns
+/// The characters in order, empty if `i` is not below [SenseVoiceContext::n_segments].
+pub fn full_get_chars(
+    ctx: &mut SenseVoiceContext,
+    i: usize,
+) -> Result<Vec<CharTiming>, SenseVoiceError> {
+    if i >= ctx.n_segments() {
+        return Ok(Vec::new());
+    }
+    // sense-voice.cpp decodes the whole input as one segment, its tokens are all tokens
+    Ok(words::chars_from_tokens(&full_get_tokens(ctx)?))
+}
+
+/// Start and end of every token of the most recent transcription, in `precision`.
+///
+/// The same spans as [segment::Token::start_ms] and [segment::Token::end_ms], in the order of
+/// [segment::Transcription::tokens], converted with [TimestampPrecision::from_ms]. Whatever the
+/// unit, the timestamps fall on encoder frame boundaries: the achievable precision is one
+/// frame, 60 ms (960 samples) for SenseVoiceSmall, see [TimestampPrecision].
+///
+/// # C++ equivalent
+/// `int sense_voice_full_get_token_times(struct sense_voice_context * ctx, int64_t * t0, int64_t * t1, int n_max)`
+pub fn full_get_token_times(
+    ctx: &mut SenseVoiceContext,
+    precision: TimestampPrecision,
+) -> Vec<(i64, i64)> {
+    let n_tokens = unsafe {
+        ggml_aio_sys::sense_voice_full_get_token_times(ctx.ctx, null_mut(), null_mut(), 0)
+    };
+    let mut t0 = vec![0i64; n_tokens.max(0) as usize];
+    let mut t1 = vec![0i64; t0.len()];
+    unsafe {
+        ggml_aio_sys::sense_voice_full_get_token_times(
+            ctx.ctx,
+            t0.as_mut_ptr(),
+            t1.as_mut_ptr(),
+            t0.len() as c_int,
+        )
+    };
+    let frame_ms = ctx.frontend().encoder_frame_ms();
+    t0.into_iter()
+        .zip(t1)
+        .map(|(start, end)| {
+            (
+                precision.from_ms(start, frame_ms),
+                precision.from_ms(end, frame_ms),
+            )
+        })
+        .collect()
+}
+
+/// The decoded tokens of the most recent transcription, with their times.
+fn full_get_tokens(ctx: &mut SenseVoiceContext) -> Result<Vec<Token>, SenseVoiceError> {
+    let n_tokens = unsafe { ggml_aio_sys::sense_voice_full_get_tokens(ctx.ctx, null_mut(), 0) };
+    let mut ids = vec![0 as c_int; n_tokens.max(0) as usize];
+    unsafe {
+        ggml_aio_sys::sense_voice_full_get_tokens(ctx.ctx, ids.as_mut_ptr(), ids.len() as c_int)
+    };
+    let mut t0 = vec![0i64; ids.len()];
+    let mut t1 = vec![0i64; ids.len()];
+    unsafe {
+        ggml_aio_sys::sense_voice_full_get_token_times(
+            ctx.ctx,
+            t0.as_mut_ptr(),
+            t1.as_mut_ptr(),
+            ids.len() as c_int,
+        )
+    };
+    ids.into_iter()
+        .zip(t0.into_iter().zip(t1))
+        .map(|(id, (start_ms, end_ms))| {
+            let ptr = unsafe { ggml_aio_sys::sense_voice_token_to_str(ctx.ctx, id) };
+            let text = if ptr.is_null() {
+                String::new()
+            } else {
+                unsafe { CStr::from_ptr(ptr) }.to_str()?.to_string()
+            };
+            Ok(Token {
+                id,
+                text,
+                start_ms,
+                end_ms,
+            })
+        })
+        .collect()
 }
 
 pub fn reset_ctx_state(ctx: &mut SenseVoiceContext) {
     unsafe { ggml_aio_sys::sense_voice_reset_ctx_state(ctx.ctx) };
 }
+
+/// Reset only the text side of the context state, e.g. so that [full_get_text] doesn't
+/// return the text of an earlier call.
+///
+/// Cleared: the CTC token ids, the text returned by [full_get_text], the tokens of
+/// batched segments and the decode timing.
+///
+/// Left as they are: the input features, the encoder output of the last [full_parallel] call,
+/// the batched segment samples and the VAD LSTM state used by [get_speech_prob]. Nothing
+/// decodes the kept encoder output again: every transcription computes the features and
+/// runs the encoder anew, so this doesn't make decoding the same audio twice any cheaper.
+///
+/// Unlike [reset_ctx_state] no backend buffers are freed or reallocated.
+///
+/// # C++ equivalent
+/// `void sense_voice_reset_decode_state(struct sense_voice_context *ctx);`
+pub fn reset_decode_state(ctx: &mut SenseVoiceContext) {
+    unsafe { ggml_aio_sys::sense_voice_reset_decode_state(ctx.ctx) };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rejects_lengths_beyond_c_int() {
+        assert_eq!(c_sample_count(16_000).unwrap(), 16_000);
+        assert_eq!(c_sample_count(i32::MAX as usize).unwrap(), i32::MAX);
+        // one past the limit would wrap to i32::MIN with an `as` cast
+        let too_long = i32::MAX as usize + 1;
+        assert!(matches!(
+            c_sample_count(too_long),
+            Err(SenseVoiceError::AudioTooLong {
+                samples,
+                max: i32::MAX,
+            }) if samples == too_long
+        ));
+    }
+
+    #[test]
+    fn realtime_factor_estimates() {
+        let ctx = SenseVoiceContext::from_raw(std::ptr::null_mut());
+        assert_eq!(ctx.realtime_factor(), None);
+        // the default applies before any transcription
+        assert_eq!(
+            estimate_transcription_time(&ctx, 16_000 * 10),
+            Duration::from_secs(10)
+        );
+        ctx.record_realtime_factor(0.1);
+        assert_eq!(ctx.realtime_factor(), Some(0.1));
+        // broken measurements are ignored, later ones are smoothed in
+        ctx.record_realtime_factor(f32::NAN);
+        ctx.record_realtime_factor(0.0);
+        assert_eq!(ctx.realtime_factor(), Some(0.1));
+        ctx.record_realtime_factor(0.2);
+        assert!((ctx.realtime_factor().unwrap() - 0.13).abs() < 1e-6);
+        let estimate = estimate_transcription_time(&ctx, 16_000 * 100);
+        assert!((estimate.as_secs_f32() - 13.0).abs() < 1e-3, "{estimate:?}");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_load_reports_errors() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(SenseVoiceContext::new_with_params_async(
+            "/nonexistent/model.gguf",
+            SenseVoiceContextParameters::default(),
+        ));
+        assert!(matches!(
+            result,
+            Err(SenseVoiceError::ModelFileIo(std::io::ErrorKind::NotFound))
+        ));
+    }
+
+    #[test]
+    fn rejects_offset_past_the_end() {
+        let mut ctx = SenseVoiceContext::from_raw(std::ptr::null_mut());
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .offset_ms(2_000)
+            .build();
+        // one second of audio, checked before anything reaches the library
+        let result = full_parallel(&mut ctx, params.clone(), &[0.0; 16_000]);
+        assert!(matches!(
+            result,
+            Err(SenseVoiceError::OffsetBeyondAudio {
+                offset_ms: 2_000,
+                audio_ms: 1_000
+            })
+        ));
+        let result = full_parallel(&mut ctx, params, &[0.0; 32_000]);
+        assert!(matches!(
+            result,
+            Err(SenseVoiceError::OffsetBeyondAudio {
+                offset_ms: 2_000,
+                audio_ms: 2_000
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_non_finite_samples_when_asked() {
+        let mut ctx = SenseVoiceContext::from_raw(std::ptr::null_mut());
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .reject_non_finite(true)
+            .build();
+        let mut data = vec![0.0; 16_000];
+        data[100] = f64::NAN;
+        data[200] = f64::INFINITY;
+        // checked before anything reaches the library
+        let result = full_parallel(&mut ctx, params, &data);
+        assert!(matches!(
+            result,
+            Err(SenseVoiceError::NonFiniteAudio { index: 100 })
+        ));
+    }
+
+    #[test]
+    fn offset_and_duration_cut_the_samples() {
+        let data: Vec<f64> = (0..32_000).map(f64::from).collect();
+        let params = |offset_ms, duration_ms| {
+            SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+                .offset_ms(offset_ms)
+                .duration_ms(duration_ms)
+                .build()
+        };
+        assert_eq!(prepare_samples(&params(0, 0), &data).unwrap(), data);
+        // 16 samples per millisecond
+        assert_eq!(
+            prepare_samples(&params(500, 0), &data).unwrap(),
+            data[8_000..]
+        );
+        assert_eq!(
+            prepare_samples(&params(500, 250), &data).unwrap(),
+            data[8_000..12_000]
+        );
+        // a duration past the end stops at the end
+        assert_eq!(
+            prepare_samples(&params(1_500, 1_000), &data).unwrap(),
+            data[24_000..]
+        );
+        // indices of bad samples still count from the start of the input
+        let mut bad = data.clone();
+        bad[10_000] = f64::NAN;
+        let checked = SenseVoiceFullParams {
+            reject_non_finite: true,
+            ..params(500, 0)
+        };
+        assert!(matches!(
+            prepare_samples(&checked, &bad),
+            Err(SenseVoiceError::NonFiniteAudio { index: 10_000 })
+        ));
+    }
+
+    #[test]
+    fn only_normalized_input_is_scaled() {
+        let data = vec![0.5; 16_000];
+        let params =
+            SenseVoiceFullParams::default_params(SenseVoiceDecodingStrategy::SamplingGreedy);
+        // 16-bit range samples reach the front end as they are
+        assert_eq!(prepare_samples(&params, &data).unwrap(), data);
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .normalized_input(true)
+            .build();
+        assert_eq!(
+            prepare_samples(&params, &data).unwrap(),
+            vec![0.5 * audio::FRONTEND_SCALE; 16_000]
+        );
+    }
+
+    #[test]
+    fn text_both_checks_the_samples() {
+        let mut ctx = SenseVoiceContext::from_raw(std::ptr::null_mut());
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .offset_ms(2_000)
+            .build();
+        // the same checks as full_parallel, before the front end runs
+        let result = full_parallel_text_both(&mut ctx, params, &[0.0; 16_000]);
+        assert!(matches!(
+            result,
+            Err(SenseVoiceError::OffsetBeyondAudio {
+                offset_ms: 2_000,
+                audio_ms: 1_000
+            })
+        ));
+    }
+
+    #[test]
+    fn labels_are_rust_side_metadata() {
+        let mut ctx = SenseVoiceContext::from_raw(std::ptr::null_mut());
+        assert_eq!(ctx.label(), None);
+        ctx.set_label("small-v1 cold");
+        ctx.set_label(format!("small-v1 {}", "warm"));
+        assert_eq!(ctx.label(), Some("small-v1 warm"));
+        assert_eq!(ctx.take_label().as_deref(), Some("small-v1 warm"));
+        assert_eq!(ctx.label(), None);
+    }
+
+    #[test]
+    fn languages_are_checked_against_the_model_tags() {
+        // a model fine-tuned on Chinese and English only
+        let tags = [
+            "<unk>",
+            "<|zh|>",
+            "<|en|>",
+            "<|NEUTRAL|>",
+            "<|Speech|>",
+            "<|woitn|>",
+        ];
+        assert!(check_language_supported("auto", tags).is_ok());
+        assert!(check_language_supported("AUTO", tags).is_ok());
+        assert!(check_language_supported("en", tags).is_ok());
+        assert!(check_language_supported("ZH", tags).is_ok());
+        assert!(matches!(
+            check_language_supported("ja", tags),
+            Err(SenseVoiceError::UnsupportedLanguageForModel { language })
+                if language.as_str() == "ja"
+        ));
+        // plain tokens like <unk> aren't tags
+        assert!(check_language_supported("", tags).is_err());
+        assert!(check_language_supported("unk", tags).is_err());
+        // the error keeps long codes cut at a character boundary
+        assert_eq!(LanguageCode::new("aééééééééé").as_str(), "aééééééé");
+    }
+
+    #[test]
+    fn batch_reports_clip_errors_in_order() {
+        let mut ctx = SenseVoiceContext::from_raw(std::ptr::null_mut());
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .offset_ms(1_000)
+            .build();
+        assert!(full_parallel_batch(&mut ctx, params.clone(), &[]).is_empty());
+        // every clip fails its checks, so nothing reaches the library
+        let results = full_parallel_batch(&mut ctx, params, &[&[0.0; 8_000], &[], &[0.0; 16_000]]);
+        assert_eq!(results.len(), 3);
+        assert!(matches!(
+            results[0],
+            Err(SenseVoiceError::OffsetBeyondAudio { audio_ms: 500, .. })
+        ));
+        assert!(matches!(results[1], Err(SenseVoiceError::NoSamples)));
+        assert!(matches!(
+            results[2],
+            Err(SenseVoiceError::OffsetBeyondAudio {
+                audio_ms: 1_000,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn fingerprint_covers_decoding_fields_only() {
+        let params = SenseVoiceFullParams::for_language("en");
+        let fingerprint = params.params_fingerprint();
+        assert_eq!(
+            SenseVoiceFullParams::for_language("en").params_fingerprint(),
+            fingerprint
+        );
+
+        let cosmetic = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .language("en")
+            .n_threads(1)
+            .quiet()
+            .progress_style(ProgressStyle::Full)
+            .timeout(Duration::from_secs(1))
+            .warn_on_clipping(false)
+            .build();
+        assert_eq!(cosmetic.params_fingerprint(), fingerprint);
+
+        let mut negative_zero = params.clone();
+        negative_zero.temperature = -0.0;
+        assert_eq!(negative_zero.params_fingerprint(), fingerprint);
+
+        let mut hotter = params.clone();
+        hotter.temperature = 0.2;
+        assert_ne!(hotter.params_fingerprint(), fingerprint);
+        let mut detect_only = params.clone();
+        detect_only.detect_language_only = true;
+        assert_ne!(detect_only.params_fingerprint(), fingerprint);
+        let mut normalized = params.clone();
+        normalized.normalized_input = true;
+        assert_ne!(normalized.params_fingerprint(), fingerprint);
+        let beam = params.with_strategy(SenseVoiceDecodingStrategy::SamplingBeamSearch);
+        assert_ne!(beam.params_fingerprint(), fingerprint);
+        assert_ne!(
+            SenseVoiceFullParams::for_language("zh").params_fingerprint(),
+            fingerprint
+        );
+    }
+
+    #[test]
+    fn recognizes_special_tokens() {
+        for token in [
+            "<|zh|>",
+            "<|HAPPY|>",
+            "<|Event_UNK|>",
+            "<|woitn|>",
+            "<unk>",
+            "</s>",
+        ] {
+            assert!(is_special_token(token), "{token}");
+        }
+        for token in [
+            "▁the",
+            "<",
+            "<||>",
+            "<|",
+            "|>",
+            "<|a|><|b|>",
+            "<b>",
+            "▁<|en|>",
+        ] {
+            assert!(!is_special_token(token), "{token}");
+        }
+    }
+
+    #[test]
+    fn use_itn_overrides_per_call() {
+        let params = SenseVoiceFullParams::for_language("en");
+        assert_eq!(params.use_itn, None);
+        let verbatim = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .language("en")
+            .use_itn(Some(false))
+            .build();
+        let normalized = SenseVoiceFullParamsBuilder::for_language("en")
+            .use_itn(Some(true))
+            .build();
+        assert_eq!(normalized.use_itn, Some(true));
+        // each setting decodes different text, so results must not be cached under one key
+        let fingerprints = [&params, &verbatim, &normalized].map(|p| p.params_fingerprint());
+        assert_ne!(fingerprints[0], fingerprints[1]);
+        assert_ne!(fingerprints[0], fingerprints[2]);
+        assert_ne!(fingerprints[1], fingerprints[2]);
+        // switching strategy keeps the override
+        let beam = normalized.with_strategy(SenseVoiceDecodingStrategy::SamplingBeamSearch);
+        assert_eq!(beam.use_itn, Some(true));
+    }
+
+    #[cfg(feature = "research")]
+    #[test]
+    fn probs_reach_the_callback() {
+        let mut seen = Vec::new();
+        let mut record = |step: usize, probs: &[f32]| seen.push((step, probs.to_vec()));
+        let mut on_probs: &mut dyn FnMut(usize, &[f32]) = &mut record;
+        let probs = [0.25f32, 0.75];
+        unsafe {
+            forward_probs(
+                null_mut(),
+                3,
+                probs.as_ptr(),
+                2,
+                &mut on_probs as *mut _ as *mut c_void,
+            )
+        };
+        assert_eq!(seen, [(3, vec![0.25, 0.75])]);
+    }
+
+    #[cfg(feature = "research")]
+    #[test]
+    fn probs_run_checks_the_samples() {
+        let mut ctx = SenseVoiceContext::from_raw(null_mut());
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .reject_non_finite(true)
+            .build();
+        let mut data = vec![0.0; 16_000];
+        data[100] = f64::NAN;
+        // the same checks as full_parallel, before anything reaches the library
+        let result = full_parallel_with_probs(&mut ctx, params, &data, |_, _| {});
+        assert!(matches!(
+            result,
+            Err(SenseVoiceError::NonFiniteAudio { index: 100 })
+        ));
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        let mut hasher = Fnv1a::default();
+        assert_eq!(hasher.finish(), 0xcbf2_9ce4_8422_2325);
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn build_clamps_thread_count() {
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .n_threads(100_000)
+            .build();
+        assert_eq!(params.n_threads, max_threads());
+
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .n_threads(0)
+            .build();
+        assert_eq!(params.n_threads, 1);
+    }
+
+    #[test]
+    fn try_build_rejects_thread_count() {
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .n_threads(100_000)
+            .try_build();
+        assert!(matches!(params, Err(SenseVoiceError::InvalidThreadCount)));
+
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .n_threads(1)
+            .try_build();
+        assert!(params.is_ok());
+    }
+
+    #[test]
+    fn try_build_rejects_beam_options_with_greedy() {
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .beam_patience(1.5)
+            .try_build();
+        assert!(matches!(
+            params,
+            Err(SenseVoiceError::IncompatibleParams(_))
+        ));
+
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingBeamSearch)
+            .beam_patience(1.5)
+            .length_penalty(0.6)
+            .try_build();
+        assert!(params.is_ok());
+    }
+
+    #[test]
+    fn tokenizes_hotwords() {
+        let vocab: HashMap<&str, c_int> = [
+            ("\u{2581}", 1),
+            ("\u{2581}sense", 2),
+            ("voice", 3),
+            ("\u{2581}s", 4),
+            ("e", 5),
+            ("\u{2581}北", 6),
+            ("京", 7),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(tokenize_greedy(&vocab, "sensevoice"), [2, 3]);
+        assert_eq!(tokenize_greedy(&vocab, " se  北京 "), [4, 5, 6, 7]);
+        // "x" is in no piece and skipped, the word start still matches
+        assert_eq!(tokenize_greedy(&vocab, "xe"), [1, 5]);
+        assert!(tokenize_greedy(&vocab, "").is_empty());
+
+        let builder = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .hotwords(&[("Rust".to_string(), 2.0), ("nan".to_string(), f32::NAN)]);
+        assert!(matches!(
+            builder.try_build(),
+            Err(SenseVoiceError::IncompatibleParams(_))
+        ));
+        let builder = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .hotwords(&[("Rust".to_string(), 2.0), ("nan".to_string(), f32::NAN)]);
+        assert_eq!(builder.build().hotwords, [("Rust".to_string(), 2.0)]);
+    }
+
+    #[test]
+    fn max_tokens_checks() {
+        let params =
+            SenseVoiceFullParams::default_params(SenseVoiceDecodingStrategy::SamplingGreedy);
+        assert_eq!(params.max_tokens, 0);
+        assert_eq!(params.to_c_struct().0.max_tokens, 0);
+
+        let builder = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingBeamSearch)
+            .max_tokens(-1);
+        assert!(matches!(
+            builder.try_build(),
+            Err(SenseVoiceError::IncompatibleParams(_))
+        ));
+        let builder = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingBeamSearch)
+            .max_tokens(-1);
+        assert_eq!(builder.build().max_tokens, 0);
+
+        let capped = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .max_tokens(200)
+            .try_build()
+            .unwrap();
+        assert_eq!(capped.to_c_struct().0.max_tokens, 200);
+        assert_ne!(capped.params_fingerprint(), params.params_fingerprint());
+    }
+
+    #[test]
+    fn language_profiles() {
+        let params = SenseVoiceFullParams::for_language("YUE");
+        assert_eq!(params.language, "yue");
+        assert!(params.strategy == SenseVoiceDecodingStrategy::SamplingGreedy);
+        assert_eq!(params.greedy.best_of, 5);
+
+        let params = SenseVoiceFullParams::for_language("ja");
+        assert_eq!(params.language, "ja");
+        assert!(params.strategy == SenseVoiceDecodingStrategy::SamplingGreedy);
+
+        let params = SenseVoiceFullParams::for_language("xx");
+        assert_eq!(params.language, "auto");
+        assert!(params.strategy == SenseVoiceDecodingStrategy::SamplingGreedy);
+
+        let params = SenseVoiceFullParamsBuilder::for_language("zh")
+            .greedy_best_of(3)
+            .try_build()
+            .unwrap();
+        assert_eq!(params.greedy.best_of, 3);
+    }
+
+    #[test]
+    fn speech_windows_span_first_to_last() {
+        assert_eq!(
+            speech_windows(&[0.1, 0.7, 0.2, 0.9, 0.3], 0.5),
+            Some((1, 3))
+        );
+        assert_eq!(speech_windows(&[0.5], 0.5), Some((0, 0)));
+        assert_eq!(speech_windows(&[0.1, 0.2], 0.5), None);
+        assert_eq!(speech_windows(&[], 0.5), None);
+    }
+
+    #[test]
+    fn full_params_layout_matches_the_library() {
+        use std::mem::offset_of;
+
+        let layout = |field: Option<&str>| {
+            let field = field.map(|field| CString::new(field).unwrap());
+            let ptr = field
+                .as_ref()
+                .map_or(std::ptr::null(), |field| field.as_ptr());
+            unsafe { ggml_aio_sys::sense_voice_full_params_layout(ptr) }
+        };
+        type P = sense_voice_full_params;
+        assert_eq!(size_of::<P>(), layout(None));
+        for (field, offset) in [
+            ("strategy", offset_of!(P, strategy)),
+            ("n_threads", offset_of!(P, n_threads)),
+            ("language", offset_of!(P, language)),
+            ("n_max_text_ctx", offset_of!(P, n_max_text_ctx)),
+            ("offset_ms", offset_of!(P, offset_ms)),
+            ("duration_ms", offset_of!(P, duration_ms)),
+            ("no_context", offset_of!(P, no_context)),
+            ("no_timestamps", offset_of!(P, no_timestamps)),
+            ("single_segment", offset_of!(P, single_segment)),
+            ("print_progress", offset_of!(P, print_progress)),
+            ("print_timestamps", offset_of!(P, print_timestamps)),
+            ("progress_style", offset_of!(P, progress_style)),
+            ("debug_mode", offset_of!(P, debug_mode)),
+            ("audio_ctx", offset_of!(P, audio_ctx)),
+            ("greedy.best_of", offset_of!(P, greedy.best_of)),
+            ("length_penalty", offset_of!(P, length_penalty)),
+            ("temperature", offset_of!(P, temperature)),
+            (
+                "beam_search.beam_size",
+                offset_of!(P, beam_search.beam_size),
+            ),
+            ("beam_search.patience", offset_of!(P, beam_search.patience)),
+            ("progress_callback", offset_of!(P, progress_callback)),
+            (
+                "progress_callback_user_data",
+                offset_of!(P, progress_callback_user_data),
+            ),
+            ("abort_callback", offset_of!(P, abort_callback)),
+            (
+                "abort_callback_user_data",
+                offset_of!(P, abort_callback_user_data),
+            ),
+            ("probs_callback", offset_of!(P, probs_callback)),
+            (
+                "probs_callback_user_data",
+                offset_of!(P, probs_callback_user_data),
+            ),
+            ("detect_language", offset_of!(P, detect_language)),
+            ("max_tokens", offset_of!(P, max_tokens)),
+            ("logit_bias", offset_of!(P, logit_bias)),
+        ] {
+            assert_eq!(offset, layout(Some(field)), "offset of {field}");
+        }
+        assert_eq!(layout(Some("no_such_field")), usize::MAX);
+    }
+
+    #[test]
+    fn to_c_struct_maps_duration_and_language() {
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .language("ja")
+            .offset_ms(1_000)
+            .duration_ms(2_500)
+            .build();
+        let (c_params, c_language) = params.to_c_struct();
+        assert_eq!(c_params.offset_ms, 1_000);
+        assert_eq!(c_params.duration_ms, 2_500);
+        assert_eq!(c_params.language, c_language.as_ptr());
+        // the string the struct points to is still alive here
+        let language = unsafe { CStr::from_ptr(c_params.language) };
+        assert_eq!(language.to_str(), Ok("ja"));
+    }
+
+    #[test]
+    fn to_c_struct_forwards_floats_exactly() {
+        let params =
+            SenseVoiceFullParams::default_params(SenseVoiceDecodingStrategy::SamplingGreedy);
+        assert_eq!(params.temperature, 0.0);
+        assert_eq!(params.to_c_struct().0.temperature, 0.0);
+
+        let mut params = params.with_strategy(SenseVoiceDecodingStrategy::SamplingBeamSearch);
+        params.temperature = 0.1;
+        params.length_penalty = 0.3;
+        params.beam_search.patience = 1.7;
+        let (c_params, _) = params.to_c_struct();
+        assert_eq!(c_params.temperature.to_bits(), 0.1f32.to_bits());
+        assert_eq!(c_params.length_penalty.to_bits(), 0.3f32.to_bits());
+        assert_eq!(c_params.beam_search.patience.to_bits(), 1.7f32.to_bits());
+    }
+
+    #[test]
+    fn with_strategy_keeps_shared_settings() {
+        let beam = SenseVoiceFullParamsBuilder::new(SenseVoiceDecodingStrategy::SamplingBeamSearch)
+            .language("zh")
+            .n_threads(2)
+            .length_penalty(0.5)
+            .build();
+        let greedy = beam.with_strategy(SenseVoiceDecodingStrategy::SamplingGreedy);
+        assert!(greedy.strategy == SenseVoiceDecodingStrategy::SamplingGreedy);
+        assert_eq!(greedy.language, "zh");
+        assert_eq!(greedy.n_threads, 2);
+        assert_eq!(greedy.greedy.best_of, 5);
+        assert_eq!(greedy.beam_search.beam_size, -1);
+        assert_eq!(greedy.beam_search.patience, -1.0);
+        assert_eq!(greedy.length_penalty, -1.0);
+
+        let beam = greedy.with_strategy(SenseVoiceDecodingStrategy::SamplingBeamSearch);
+        assert_eq!(beam.beam_search.beam_size, 5);
+        assert_eq!(beam.greedy.best_of, -1);
+    }
+
+    #[test]
+    fn abort_after_deadline_checks_elapsed_time() {
+        let abort = AbortCheck {
+            deadline: Some(Instant::now() + Duration::from_secs(60)),
+            cancel: None,
+        };
+        let user_data = &abort as *const AbortCheck as *mut c_void;
+        assert!(!unsafe { abort_requested(user_data) });
+
+        let abort = AbortCheck {
+            deadline: Some(Instant::now()),
+            cancel: None,
+        };
+        let user_data = &abort as *const AbortCheck as *mut c_void;
+        assert!(unsafe { abort_requested(user_data) });
+        assert!(abort.timed_out());
+    }
+
+    #[test]
+    fn abort_when_cancelled() {
+        let cancelled = std::sync::atomic::AtomicBool::new(false);
+        let cancel = || cancelled.load(Ordering::Relaxed);
+        let abort = AbortCheck {
+            deadline: None,
+            cancel: Some(&cancel),
+        };
+        let user_data = &abort as *const AbortCheck as *mut c_void;
+        assert!(!unsafe { abort_requested(user_data) });
+
+        cancelled.store(true, Ordering::Relaxed);
+        assert!(unsafe { abort_requested(user_data) });
+        assert!(!abort.timed_out());
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-with-tiny-model")]
+mod test_with_tiny_model {
+    use super::*;
+    const MODEL_PATH: &str = "./models/sense-voice-small-q4_k.gguf";
+
+    // These tests expect a SenseVoiceSmall model at MODEL_PATH, converted with
+    // `ggml-aio-sys/cc/sense-voice.cpp/scripts/convert-pt-to-gguf.py`
+
+    fn load_model() -> SenseVoiceContext {
+        SenseVoiceContext::new_with_params(MODEL_PATH, SenseVoiceContextParameters::default())
+            .expect("Convert a SenseVoiceSmall model to ./models/sense-voice-small-q4_k.gguf")
+    }
+
+    /// Speech probability of `data` from a fresh detector, which carries its state over
+    /// between calls.
+    fn speech_prob(data: &[f64]) -> f32 {
+        get_speech_prob(&mut load_model(), data)
+    }
+
+    #[test]
+    fn speech_prob_after_a_timed_transcription() {
+        let mut ctx = load_model();
+        let samples = vec![0.0; 16_000];
+        let params = SenseVoiceFullParams::builder(SenseVoiceDecodingStrategy::SamplingGreedy)
+            .timeout(Duration::from_secs(60))
+            .build();
+        full_parallel(&mut ctx, params, &samples).unwrap();
+        // the abort check lived on the stack of the transcription, the detector's graph
+        // shares the backends and must not call it anymore
+        let prob = get_speech_prob(&mut ctx, &samples[..VAD_WINDOW_SAMPLES]);
+        assert!((0.0..=1.0).contains(&prob));
+        let cancel = || false;
+        full_parallel_cancellable(
+            &mut ctx,
+            SenseVoiceFullParams::default_params(SenseVoiceDecodingStrategy::SamplingGreedy),
+            &samples,
+            Some(&cancel),
+        )
+        .unwrap();
+        let prob = get_speech_prob(&mut ctx, &samples[..VAD_WINDOW_SAMPLES]);
+        assert!((0.0..=1.0).contains(&prob));
+    }
+
+    /// Transcribe a second of tone with `use_itn` for this call only and return the text with
+    /// its prompt tags; the fourth tag echoes the ITN query the encoder ran with.
+    fn tagged_text(ctx: &mut SenseVoiceContext, use_itn: Option<bool>) -> String {
+        let tone: Vec<f64> = (0..16_000)
+            .map(|i| 0.5 * (i as f64 * 2.0 * std::f64::consts::PI * 220.0 / 16_000.0).sin())
+            .collect();
+        let params = SenseVoiceFullParamsBuilder::for_language("en")
+            .use_itn(use_itn)
+            .build();
+        full_parallel(ctx, params, &tone).unwrap();
+        full_get_text(ctx, true).unwrap()
+    }
+
+    #[test]
+    fn use_itn_override_applies_to_one_call() {
+        let mut ctx = load_model();
+        ctx.set_use_itn(false);
+        assert!(tagged_text(&mut ctx, Some(true)).contains("<|withitn|>"));
+        assert!(!ctx.use_itn());
+        // the context's own setting is back for the next call
+        assert!(tagged_text(&mut ctx, None).contains("<|woitn|>"));
+
+        ctx.set_use_itn(true);
+        assert!(tagged_text(&mut ctx, Some(false)).contains("<|woitn|>"));
+        assert!(ctx.use_itn());
+        assert!(tagged_text(&mut ctx, None).contains("<|withitn|>"));
+    }
+
+    #[test]
+    fn speech_prob_sees_samples_at_their_level() {
+        let tone: Vec<f64> = (0..VAD_WINDOW_SAMPLES)
+            .map(|i| 0.5 * (i as f64 * 2.0 * std::f64::consts::PI * 220.0 / 16_000.0).sin())
+            .collect();
+        let quiet: Vec<f64> = tone.iter().map(|sample| sample * 0.01).collect();
+        let loud_prob = speech_prob(&tone);
+        let quiet_prob = speech_prob(&quiet);
+        assert!((0.0..=1.0).contains(&loud_prob));
+        assert!((0.0..=1.0).contains(&quiet_prob));
+        // nothing normalizes the level away before the detector
+        assert_ne!(loud_prob, quiet_prob);
+        assert_eq!(speech_prob(&tone), loud_prob);
+
+        // short input is zero-padded to a whole window, samples after it are ignored
+        let mut padded = tone[..100].to_vec();
+        padded.resize(VAD_WINDOW_SAMPLES, 0.0);
+        assert_eq!(speech_prob(&tone[..100]), speech_prob(&padded));
+        let mut longer = tone.clone();
+        longer.extend(std::iter::repeat_n(0.9, 1_000));
+        assert_eq!(speech_prob(&longer), loud_prob);
+    }
+}
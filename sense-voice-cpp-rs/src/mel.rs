@@ -0,0 +1,284 @@
+//! Encoder input features, computed separately from running the model.
+//!
+//! [compute_mel] runs only the front end (fbank, low frame rate stacking and CMVN), e.g. to cache
+//! features or to inspect them, and [full_parallel_mel] transcribes features computed earlier
+//! without repeating that work. With the `debug-viz` feature, `save_mel_png` renders them as
+//! an image.
+
+use std::ffi::c_int;
+
+use crate::{
+    SenseVoiceContext, SenseVoiceFullParams, error::SenseVoiceError, model::FrontendParams,
+    run_full,
+};
+
+/// Number of consecutive mel frames stacked into one encoder input frame (`lfr_m`) by
+/// SenseVoiceSmall, see [FrontendParams::lfr_m] for the value of a loaded model.
+pub const LFR_FRAMES: usize = 7;
+
+/// Encoder input features: `n_frames` rows of `n_mels * lfr_m` values each.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MelFeatures {
+    /// Mel bins per audio frame, 80 for SenseVoiceSmall.
+    pub n_mels: usize,
+    /// Mel frames stacked per row, [LFR_FRAMES] for SenseVoiceSmall.
+    pub lfr_m: usize,
+    /// Encoder input frames, one per [FrontendParams::encoder_frame_ms] of audio.
+    pub n_frames: usize,
+    /// Row-major feature values, `lfr_m` stacked frames of `n_mels` bins per row.
+    pub data: Vec<f32>,
+}
+
+impl MelFeatures {
+    /// Number of values per encoder input frame.
+    pub fn frame_dim(&self) -> usize {
+        self.n_mels * self.lfr_m
+    }
+
+    /// Check these features against the frontend settings of a model.
+    fn validate(&self, expected: &FrontendParams) -> Result<(), SenseVoiceError> {
+        if self.n_mels != expected.n_mels {
+            return Err(SenseVoiceError::MelDimensionMismatch {
+                expected: expected.n_mels,
+                got: self.n_mels,
+            });
+        }
+        if self.lfr_m != expected.lfr_m {
+            return Err(SenseVoiceError::InvalidAudioBuffer(
+                "features stack a different number of frames than the model's lfr_m",
+            ));
+        }
+        if self.n_frames == 0 {
+            return Err(SenseVoiceError::NoSamples);
+        }
+        if self.data.len() != self.n_frames * self.frame_dim() {
+            return Err(SenseVoiceError::InvalidAudioBuffer(
+                "feature data length is not n_frames * n_mels * lfr_m",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Number of mel bins the loaded model expects per audio frame.
+pub fn model_n_mels(ctx: &SenseVoiceContext) -> usize {
+    unsafe { ggml_aio_sys::sense_voice_model_n_mels(ctx.ctx) as usize }
+}
+
+/// Compute the encoder input features of `data` without running the model.
+///
+/// # Arguments
+/// * ctx: The context whose front end settings and state to use.
+/// * data: Mono 16 kHz samples in the 16-bit integer range, see the
+///   [audio](crate::audio#amplitude) module.
+///
+/// # Returns
+/// The features, [SenseVoiceError::NoSamples] for empty input or
+/// [SenseVoiceError::AudioTooLong] for more than `i32::MAX` samples.
+pub fn compute_mel(
+    ctx: &mut SenseVoiceContext,
+    data: &[f64],
+) -> Result<MelFeatures, SenseVoiceError> {
+    if data.is_empty() {
+        return Err(SenseVoiceError::NoSamples);
+    }
+    let n_samples = crate::c_sample_count(data.len())?;
+    let n_frames =
+        unsafe { ggml_aio_sys::sense_voice_pcm_to_features(ctx.ctx, data.as_ptr(), n_samples, 8) };
+    if n_frames < 0 {
+        return Err(SenseVoiceError::UnableToCalculateSpectrogram);
+    }
+    Ok(MelFeatures {
+        n_mels: model_n_mels(ctx),
+        lfr_m: ctx.frontend().lfr_m,
+        n_frames: n_frames as usize,
+        data: last_features(ctx),
+    })
+}
+
+/// Copy of the features the context computed last, empty if it hasn't computed any.
+fn last_features(ctx: &mut SenseVoiceContext) -> Vec<f32> {
+    let len = unsafe { ggml_aio_sys::sense_voice_get_features(ctx.ctx, std::ptr::null_mut(), 0) };
+    let mut features = vec![0.0f32; len.max(0) as usize];
+    unsafe {
+        ggml_aio_sys::sense_voice_get_features(ctx.ctx, features.as_mut_ptr(), len);
+    }
+    features
+}
+
+/// Render the mel spectrogram of `features` as a grayscale image, see [save_mel_png] for the
+/// layout.
+#[cfg(feature = "debug-viz")]
+pub fn mel_image(features: &MelFeatures) -> image::GrayImage {
+    let n_mels = features.n_mels;
+    // the lfr_m stacked frames of a row are centered on the row's own time step
+    let center = (features.lfr_m.saturating_sub(1)) / 2 * n_mels;
+    let columns: Vec<&[f32]> = features
+        .data
+        .chunks_exact(features.frame_dim().max(1))
+        .map(|row| &row[center..center + n_mels])
+        .collect();
+    let (min, max) = columns
+        .iter()
+        .flat_map(|column| column.iter())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        });
+    let range = if max > min { max - min } else { 1.0 };
+    image::GrayImage::from_fn(columns.len() as u32, n_mels as u32, |x, y| {
+        // the lowest mel bin goes in the bottom row
+        let value = columns[x as usize][n_mels - 1 - y as usize];
+        image::Luma([((value - min) / range * 255.0).round() as u8])
+    })
+}
+
+/// Save the features `ctx` computed last, by [compute_mel] or a transcription, as a grayscale
+/// PNG spectrogram, to check by eye that audio reaches the model as expected.
+///
+/// # Layout
+/// * Time runs from left to right, one column per encoder input frame
+///   ([FrontendParams::encoder_frame_ms], 60 ms for SenseVoiceSmall). Each column shows the
+///   mel frame the encoder frame is centered on; the frames stacked around it are left out.
+/// * Frequency runs from bottom to top, one row per mel bin, so low frequencies are at the
+///   bottom as in common spectrogram plots. Mel bins are spaced closer at low frequencies.
+/// * Brightness is linear in the feature value, the log-mel energy after the model's CMVN,
+///   stretched so the smallest value of the image is black and the largest is white. Images
+///   of different inputs are therefore not comparable in absolute terms.
+///
+/// Silence shows as uniformly dark columns, clipping as bright smears reaching up into the
+/// highest bins, and 8 kHz audio upsampled to 16 kHz as a dark band across the top rows.
+/// Audio at the wrong sample rate has its harmonics, the stripes of voiced speech, spread too
+/// far apart or squeezed together.
+///
+/// # Returns
+/// Err([SenseVoiceError::SpectrogramNotInitialized]) if the context hasn't computed features
+/// yet, or [SenseVoiceError::MelImageIo] if writing the file fails.
+#[cfg(feature = "debug-viz")]
+pub fn save_mel_png(
+    ctx: &mut SenseVoiceContext,
+    path: impl AsRef<std::path::Path>,
+) -> Result<(), SenseVoiceError> {
+    let data = last_features(ctx);
+    let frontend = ctx.frontend();
+    let frame_dim = frontend.n_mels * frontend.lfr_m;
+    if data.len() < frame_dim || frame_dim == 0 {
+        return Err(SenseVoiceError::SpectrogramNotInitialized);
+    }
+    let features = MelFeatures {
+        n_mels: frontend.n_mels,
+        lfr_m: frontend.lfr_m,
+        n_frames: data.len() / frame_dim,
+        data,
+    };
+    mel_image(&features)
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| {
+            SenseVoiceError::MelImageIo(match e {
+                image::ImageError::IoError(e) => e.kind(),
+                _ => std::io::ErrorKind::Other,
+            })
+        })
+}
+
+/// Like [crate::full_parallel], but starting from features computed by [compute_mel] or an
+/// external front end.
+///
+/// # Arguments
+/// * ctx: The context to transcribe with.
+/// * params: The parameters to use, see [crate::full_parallel].
+/// * features: The encoder input features.
+///
+/// # Returns
+/// Ok(0) on success, [SenseVoiceError::MelDimensionMismatch] if `features.n_mels` doesn't
+/// match [model_n_mels], or [SenseVoiceError::InvalidAudioBuffer] if `features.data` doesn't
+/// hold `n_frames` rows.
+pub fn full_parallel_mel(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    features: &MelFeatures,
+) -> Result<c_int, SenseVoiceError> {
+    let frontend = ctx.frontend();
+    features.validate(&frontend)?;
+    let n_samples =
+        features.n_frames * frontend.encoder_frame_ms() as usize * frontend.sample_rate as usize
+            / 1000;
+    let ret = run_full(ctx, params, None, n_samples, |ctx, c_params| unsafe {
+        ggml_aio_sys::sense_voice_full_parallel_features(
+            ctx,
+            c_params,
+            features.data.as_ptr(),
+            features.n_frames as c_int,
+            features.frame_dim() as c_int,
+        )
+    });
+    match ret {
+        Err(SenseVoiceError::GenericError(-8)) => Err(SenseVoiceError::MelDimensionMismatch {
+            expected: frontend.n_mels,
+            got: features.n_mels,
+        }),
+        ret => ret,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn features(n_mels: usize, n_frames: usize, len: usize) -> MelFeatures {
+        MelFeatures {
+            n_mels,
+            lfr_m: LFR_FRAMES,
+            n_frames,
+            data: vec![0.0; len],
+        }
+    }
+
+    #[cfg(feature = "debug-viz")]
+    #[test]
+    fn mel_image_layout() {
+        // two encoder frames of three stacked frames with two bins each, only the middle
+        // stacked frame (values 10 and up) is drawn
+        let features = MelFeatures {
+            n_mels: 2,
+            lfr_m: 3,
+            n_frames: 2,
+            data: vec![
+                0.0, 0.0, 10.0, 12.0, 0.0, 0.0, 0.0, 0.0, 14.0, 11.0, 0.0, 0.0,
+            ],
+        };
+        let image = mel_image(&features);
+        assert_eq!(image.dimensions(), (2, 2));
+        // bin 0 in the bottom row, scaled from the range 10 to 14
+        assert_eq!(image.get_pixel(0, 1).0, [0]);
+        assert_eq!(image.get_pixel(0, 0).0, [128]);
+        assert_eq!(image.get_pixel(1, 1).0, [255]);
+        assert_eq!(image.get_pixel(1, 0).0, [64]);
+    }
+
+    #[test]
+    fn validate_mel_dimension() {
+        let small = FrontendParams::default();
+        assert!(features(80, 2, 2 * 560).validate(&small).is_ok());
+        assert!(matches!(
+            features(128, 2, 2 * 128 * LFR_FRAMES).validate(&small),
+            Err(SenseVoiceError::MelDimensionMismatch {
+                expected: 80,
+                got: 128
+            })
+        ));
+        assert!(matches!(
+            features(80, 2, 560).validate(&small),
+            Err(SenseVoiceError::InvalidAudioBuffer(_))
+        ));
+        let mut stacked_differently = features(80, 2, 2 * 80 * 5);
+        stacked_differently.lfr_m = 5;
+        assert!(matches!(
+            stacked_differently.validate(&small),
+            Err(SenseVoiceError::InvalidAudioBuffer(_))
+        ));
+        assert!(matches!(
+            features(80, 0, 0).validate(&small),
+            Err(SenseVoiceError::NoSamples)
+        ));
+    }
+}
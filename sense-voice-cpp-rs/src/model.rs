@@ -0,0 +1,413 @@
+//! Information about a loaded model, or a model file, see [validate_model].
+//!
+//! # Quantization types
+//! SenseVoice GGUF files are commonly published in these weight formats:
+//!
+//! | Type | Bits/weight | Notes |
+//! |------|-------------|-------|
+//! | `f32` | 32 | Reference precision, largest and slowest to load. |
+//! | `f16` | 16 | Default export. Practically lossless, well supported on every backend. |
+//! | `q8_0` | 8.5 | Half the size of `f16` with no audible accuracy loss for most users. |
+//! | `q5_0`/`q5_1` | 5.5–6 | Good size/accuracy tradeoff on CPU, slightly higher error rates. |
+//! | `q4_0`/`q4_1` | 4.5–5 | Smallest of the legacy types, accuracy loss becomes noticeable. |
+//! | `q4_K`…`q6_K` | 4.5–6.5 | k-quants, better accuracy per bit but not supported by every GPU backend. |
+//!
+//! Quantized weights are dequantized on the fly, so a backend without a native kernel for a
+//! type is either very slow or can't run the model at all. [crate::SenseVoiceContext::new_with_params]
+//! rejects such models with [crate::error::SenseVoiceError::UnsupportedQuantization].
+
+use std::ffi::{CStr, CString};
+
+use ggml_aio_sys::ggml_type;
+
+use crate::error::SenseVoiceError;
+
+/// Storage type of a model tensor.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TensorType {
+    F32,
+    F16,
+    BF16,
+    Q4_0,
+    Q4_1,
+    Q5_0,
+    Q5_1,
+    Q8_0,
+    Q2_K,
+    Q3_K,
+    Q4_K,
+    Q5_K,
+    Q6_K,
+    /// Any other ggml type, by its raw `enum ggml_type` value.
+    Other(ggml_type),
+}
+
+impl TensorType {
+    pub(crate) fn from_raw(raw: ggml_type) -> Self {
+        use ggml_aio_sys::*;
+        match raw {
+            GGML_TYPE_F32 => Self::F32,
+            GGML_TYPE_F16 => Self::F16,
+            GGML_TYPE_BF16 => Self::BF16,
+            GGML_TYPE_Q4_0 => Self::Q4_0,
+            GGML_TYPE_Q4_1 => Self::Q4_1,
+            GGML_TYPE_Q5_0 => Self::Q5_0,
+            GGML_TYPE_Q5_1 => Self::Q5_1,
+            GGML_TYPE_Q8_0 => Self::Q8_0,
+            GGML_TYPE_Q2_K => Self::Q2_K,
+            GGML_TYPE_Q3_K => Self::Q3_K,
+            GGML_TYPE_Q4_K => Self::Q4_K,
+            GGML_TYPE_Q5_K => Self::Q5_K,
+            GGML_TYPE_Q6_K => Self::Q6_K,
+            other => Self::Other(other),
+        }
+    }
+
+    pub(crate) fn to_raw(self) -> ggml_type {
+        use ggml_aio_sys::*;
+        match self {
+            Self::F32 => GGML_TYPE_F32,
+            Self::F16 => GGML_TYPE_F16,
+            Self::BF16 => GGML_TYPE_BF16,
+            Self::Q4_0 => GGML_TYPE_Q4_0,
+            Self::Q4_1 => GGML_TYPE_Q4_1,
+            Self::Q5_0 => GGML_TYPE_Q5_0,
+            Self::Q5_1 => GGML_TYPE_Q5_1,
+            Self::Q8_0 => GGML_TYPE_Q8_0,
+            Self::Q2_K => GGML_TYPE_Q2_K,
+            Self::Q3_K => GGML_TYPE_Q3_K,
+            Self::Q4_K => GGML_TYPE_Q4_K,
+            Self::Q5_K => GGML_TYPE_Q5_K,
+            Self::Q6_K => GGML_TYPE_Q6_K,
+            Self::Other(raw) => raw,
+        }
+    }
+
+    /// Whether this is a block-quantized type rather than a float type.
+    pub fn is_quantized(self) -> bool {
+        !matches!(self, Self::F32 | Self::F16 | Self::BF16)
+    }
+
+    /// ggml's name for the type, e.g. `q5_0`.
+    pub fn name(self) -> &'static str {
+        let ptr = unsafe { ggml_aio_sys::ggml_type_name(self.to_raw()) };
+        if ptr.is_null() {
+            return "unknown";
+        }
+        unsafe { CStr::from_ptr(ptr) }.to_str().unwrap_or("unknown")
+    }
+}
+
+impl std::fmt::Display for TensorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Feature extraction settings a model was trained with, see [crate::SenseVoiceContext::frontend].
+///
+/// The converter stores them in the `frontend.*` keys of the GGUF file, and sense-voice.cpp
+/// computes every model's features with its own settings rather than fixed constants. Models
+/// converted before those keys existed get the SenseVoiceSmall values of [Self::default]:
+///
+/// | Setting | SenseVoiceSmall | whisper, for comparison |
+/// |---------|-----------------|-------------------------|
+/// | `sample_rate` | 16000 Hz | 16000 Hz |
+/// | `n_mels` | 80 | 80 or 128 |
+/// | `frame_length_ms` | 25 ms (hamming window) | 25 ms (400-sample hann window) |
+/// | `frame_shift_ms` | 10 ms | 10 ms |
+/// | `lfr_m` / `lfr_n` | 7 / 6 | no frame stacking |
+///
+/// Whisper's values don't describe SenseVoice features: its encoder sees one frame per 20 ms,
+/// SenseVoice's one per `lfr_n * frame_shift_ms` = 60 ms, see [Self::encoder_frame_ms].
+///
+/// The front end is compiled for 16 kHz input with a hamming window and carries the CMVN
+/// statistics of SenseVoiceSmall, so models whose settings need anything else are rejected
+/// with [SenseVoiceError::InvalidModel] by [validate_model] and with
+/// [SenseVoiceError::InitError] when loading.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FrontendParams {
+    /// Sample rate of the input audio, in Hz.
+    pub sample_rate: u32,
+    /// Mel bins per audio frame.
+    pub n_mels: usize,
+    /// Length of the fbank analysis window, in ms.
+    pub frame_length_ms: u32,
+    /// Hop between fbank frames, in ms.
+    pub frame_shift_ms: u32,
+    /// Consecutive fbank frames stacked into one encoder input frame (low frame rate).
+    pub lfr_m: usize,
+    /// Fbank frames advanced between encoder input frames.
+    pub lfr_n: usize,
+}
+
+impl Default for FrontendParams {
+    /// The settings of SenseVoiceSmall.
+    fn default() -> Self {
+        Self {
+            sample_rate: crate::SENSE_VOICE_SAMPLE_RATE,
+            n_mels: 80,
+            frame_length_ms: 25,
+            frame_shift_ms: 10,
+            lfr_m: crate::mel::LFR_FRAMES,
+            lfr_n: 6,
+        }
+    }
+}
+
+impl FrontendParams {
+    pub(crate) fn from_raw(raw: &ggml_aio_sys::sense_voice_frontend_params) -> Self {
+        Self {
+            sample_rate: raw.sample_rate as u32,
+            n_mels: raw.n_mels as usize,
+            frame_length_ms: raw.frame_length_ms as u32,
+            frame_shift_ms: raw.frame_shift_ms as u32,
+            lfr_m: raw.lfr_m as usize,
+            lfr_n: raw.lfr_n as usize,
+        }
+    }
+
+    /// Milliseconds of audio covered by one encoder frame, [crate::AUDIO_CTX_FRAME_MS] for
+    /// SenseVoiceSmall.
+    pub fn encoder_frame_ms(&self) -> u32 {
+        self.lfr_n as u32 * self.frame_shift_ms
+    }
+
+    /// Number of values per encoder input frame, `n_mels * lfr_m`.
+    pub fn feature_dim(&self) -> usize {
+        self.n_mels * self.lfr_m
+    }
+}
+
+/// Summary of a loaded model, see [crate::SenseVoiceContext::model_info].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelInfo {
+    /// The `ftype` stored in the GGUF header (`enum ggml_ftype`), i.e. the type the
+    /// converter was asked to produce. Small tensors such as norms usually stay `f32`.
+    pub ftype: i32,
+    /// Every tensor type present in the model with the number of tensors stored in it,
+    /// ordered by ggml type id.
+    pub tensor_types: Vec<(TensorType, usize)>,
+    /// Upper bound for [crate::SenseVoiceFullParams::audio_ctx], in encoder frames.
+    ///
+    /// The encoder sees one frame per 60 ms of audio (low frame rate stacking of 6 frames with
+    /// a 10 ms shift), so a context of `n` frames covers `n * 60` ms, e.g. 1600 frames for
+    /// 96 s with SenseVoiceSmall. See [crate::SenseVoiceContext::max_audio_ctx].
+    ///
+    /// This is the upper bound for [crate::SenseVoiceFullParams::n_max_text_ctx] too:
+    /// SenseVoice decodes with CTC, which emits at most one token per encoder frame, so the
+    /// model has no separate text context. Larger values are clamped by [crate::full_parallel].
+    pub max_audio_ctx: i32,
+    /// The feature extraction settings, see [FrontendParams].
+    pub frontend: FrontendParams,
+}
+
+impl ModelInfo {
+    /// The quantized types present in the model, empty for float models.
+    pub fn quantization_types(&self) -> impl Iterator<Item = TensorType> + '_ {
+        self.tensor_types
+            .iter()
+            .map(|(ty, _)| *ty)
+            .filter(|ty| ty.is_quantized())
+    }
+}
+
+/// Check that `path` is a well-formed SenseVoice model by parsing only its GGUF header.
+///
+/// Reads the magic, version, keys and tensor infos, but allocates no weight buffers and reads
+/// no tensor data, so it's cheap even for large files. This is meant for model management UIs
+/// and CI checks before committing to a full [crate::SenseVoiceContext::new_with_params].
+///
+/// The file size is checked against the offsets and sizes of the tensors the header declares,
+/// which catches interrupted downloads. Weights that are complete but corrupted aren't
+/// detected, and support for the tensor types by the compiled backend isn't checked either,
+/// see the module docs.
+///
+/// # Arguments
+/// * path: Path to the model file.
+///
+/// # Returns
+/// The [ModelInfo] described by the header, [SenseVoiceError::ModelFileIo] if the file can't
+/// be opened, [SenseVoiceError::InvalidModel] if it isn't a usable SenseVoice model, or
+/// [SenseVoiceError::TruncatedModel] if it ends before the last tensor does.
+pub fn validate_model(path: &str) -> Result<ModelInfo, SenseVoiceError> {
+    let actual_bytes = std::fs::File::open(path)
+        .and_then(|file| file.metadata())
+        .map_err(|e| SenseVoiceError::ModelFileIo(e.kind()))?
+        .len();
+
+    let path = CString::new(path)?;
+    let mut probe = ggml_aio_sys::sense_voice_model_probe {
+        ftype: 0,
+        n_audio_ctx: 0,
+        frontend: ggml_aio_sys::sense_voice_frontend_params {
+            sample_rate: 0,
+            n_mels: 0,
+            frame_length_ms: 0,
+            frame_shift_ms: 0,
+            lfr_m: 0,
+            lfr_n: 0,
+        },
+        n_tensors_of_type: [0; ggml_aio_sys::GGML_TYPE_COUNT as usize],
+        file_size: 0,
+    };
+    let ret = unsafe { ggml_aio_sys::sense_voice_model_probe_file(path.as_ptr(), &mut probe) };
+    match ret {
+        0 => {}
+        -1 => return Err(SenseVoiceError::InvalidModel("not a readable GGUF file")),
+        -2 => {
+            return Err(SenseVoiceError::InvalidModel(
+                "missing SenseVoice hyperparameters, vocabulary or tensors",
+            ));
+        }
+        -3 => return Err(SenseVoiceError::InvalidModel("invalid file type")),
+        -4 => {
+            return Err(SenseVoiceError::InvalidModel(
+                "unsupported feature extraction settings",
+            ));
+        }
+        other => return Err(SenseVoiceError::GenericError(other)),
+    }
+    if actual_bytes < probe.file_size {
+        return Err(SenseVoiceError::TruncatedModel {
+            expected_bytes: probe.file_size,
+            actual_bytes,
+        });
+    }
+
+    let tensor_types = probe
+        .n_tensors_of_type
+        .iter()
+        .enumerate()
+        .filter(|&(_, &n)| n > 0)
+        .map(|(raw, &n)| (TensorType::from_raw(raw as ggml_type), n as usize))
+        .collect();
+    Ok(ModelInfo {
+        ftype: probe.ftype,
+        tensor_types,
+        max_audio_ctx: probe.n_audio_ctx,
+        frontend: FrontendParams::from_raw(&probe.frontend),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A GGUF file with the keys the probe requires, the `frontend.*` keys the converter writes
+    /// for SenseVoiceSmall with `sample_rate` replaced, and a single small tensor.
+    fn write_gguf(name: &str, sample_rate: i32) -> std::path::PathBuf {
+        fn string(out: &mut Vec<u8>, s: &str) {
+            out.extend((s.len() as u64).to_le_bytes());
+            out.extend(s.as_bytes());
+        }
+        fn kv_i32(out: &mut Vec<u8>, key: &str, value: i32) {
+            string(out, key);
+            out.extend(5u32.to_le_bytes());
+            out.extend(value.to_le_bytes());
+        }
+
+        let mut kv = Vec::new();
+        kv_i32(&mut kv, "tokenizer.vocab_size", 2);
+        string(&mut kv, "tokenizer.ggml.tokens");
+        kv.extend(9u32.to_le_bytes());
+        kv.extend(8u32.to_le_bytes());
+        kv.extend(2u64.to_le_bytes());
+        string(&mut kv, "<blank>");
+        string(&mut kv, "a");
+        kv_i32(&mut kv, "encoder.output_size", 512);
+        kv_i32(&mut kv, "encoder.linear_units", 2048);
+        kv_i32(&mut kv, "encoder.attention_heads", 4);
+        kv_i32(&mut kv, "encoder.num_blocks", 50);
+        kv_i32(&mut kv, "frontend.sample_rate", sample_rate);
+        string(&mut kv, "frontend.window");
+        kv.extend(8u32.to_le_bytes());
+        string(&mut kv, "hamming");
+        kv_i32(&mut kv, "frontend.num_mels", 80);
+        kv_i32(&mut kv, "frontend.frame_length", 25);
+        kv_i32(&mut kv, "frontend.frame_shift", 10);
+        kv_i32(&mut kv, "frontend.lfr_m", 7);
+        kv_i32(&mut kv, "frontend.lfr_n", 6);
+
+        let mut file = b"GGUF".to_vec();
+        file.extend(3u32.to_le_bytes());
+        file.extend(1u64.to_le_bytes());
+        file.extend(13u64.to_le_bytes());
+        file.extend(kv);
+        // one f32 tensor of 4 elements at the start of the data section
+        string(&mut file, "encoder.embed.weight");
+        file.extend(1u32.to_le_bytes());
+        file.extend(4u64.to_le_bytes());
+        file.extend(0u32.to_le_bytes());
+        file.extend(0u64.to_le_bytes());
+        file.resize(file.len().next_multiple_of(32), 0);
+        file.extend([0u8; 16]);
+
+        let path =
+            std::env::temp_dir().join(format!("sense-voice-{}-{}.gguf", name, std::process::id()));
+        std::fs::write(&path, file).unwrap();
+        path
+    }
+
+    #[test]
+    fn small_frontend_defaults() {
+        let small = FrontendParams::default();
+        assert_eq!(small.encoder_frame_ms(), crate::AUDIO_CTX_FRAME_MS as u32);
+        assert_eq!(small.feature_dim(), 560);
+    }
+
+    #[test]
+    fn validate_model_reads_frontend() {
+        let path = write_gguf("frontend", 16000);
+        let info = validate_model(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            info.unwrap().frontend,
+            FrontendParams {
+                sample_rate: 16000,
+                n_mels: 80,
+                frame_length_ms: 25,
+                frame_shift_ms: 10,
+                lfr_m: 7,
+                lfr_n: 6,
+            }
+        );
+
+        let path = write_gguf("frontend-8k", 8000);
+        let info = validate_model(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(info, Err(SenseVoiceError::InvalidModel(_))));
+    }
+
+    #[test]
+    fn validate_model_reports_truncated_file() {
+        let path = write_gguf("truncated", 16000);
+        let len = std::fs::metadata(&path).unwrap().len();
+        // cut into the 16 bytes of tensor data, as an interrupted download would
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 8).unwrap();
+        drop(file);
+        let info = validate_model(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(
+            matches!(
+                info,
+                Err(SenseVoiceError::TruncatedModel {
+                    expected_bytes,
+                    actual_bytes,
+                }) if expected_bytes == len && actual_bytes == len - 8
+            ),
+            "{info:?}"
+        );
+    }
+
+    #[test]
+    fn validate_model_reports_missing_file() {
+        let path = std::env::temp_dir().join("sense-voice-no-such-model.gguf");
+        assert!(matches!(
+            validate_model(path.to_str().unwrap()),
+            Err(SenseVoiceError::ModelFileIo(std::io::ErrorKind::NotFound))
+        ));
+    }
+}
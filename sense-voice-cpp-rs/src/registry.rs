@@ -0,0 +1,245 @@
+//! Several models kept loaded side by side, e.g. one per language in a multilingual service.
+//!
+//! [ModelRegistry] maps names to model files and loads each one at most once, so requests
+//! don't pay the load cost. With a memory cap it unloads the least recently used models to
+//! make room and loads them again the next time they are asked for.
+//!
+//! # Memory accounting
+//! A model counts with the size of its GGUF file, read when it is registered. The weights
+//! dominate a context's memory and are loaded as stored, so this is a close lower bound;
+//! the compute buffers of each context (a few tens of MB for SenseVoiceSmall) are not counted.
+//!
+//! # Eviction policy
+//! Before a model that isn't loaded is loaded, the loaded models are unloaded in least
+//! recently used order until the new model fits under the cap. "Used" means returned by
+//! [ModelRegistry::get]. A model larger than the cap on its own is still loaded, after
+//! everything else was unloaded. Without a cap nothing is ever unloaded implicitly.
+
+use std::collections::HashMap;
+
+use crate::{SenseVoiceContext, SenseVoiceContextParameters, error::SenseVoiceError};
+
+/// Named models, loaded on first use or up front with [ModelRegistry::load].
+///
+/// # Examples
+/// ```no_run
+/// # use sense_voice_cpp_rs::{SenseVoiceContextParameters, SenseVoiceFullParams, full_parallel};
+/// # use sense_voice_cpp_rs::registry::ModelRegistry;
+/// let mut registry = ModelRegistry::with_memory_cap(2 << 30);
+/// registry.load("zh", "sense-voice-zh.gguf", SenseVoiceContextParameters::auto())?;
+/// registry.register("en", "sense-voice-en.gguf", SenseVoiceContextParameters::auto())?;
+///
+/// let samples = vec![0.0f64; 16_000];
+/// let ctx = registry.get("en")?;
+/// full_parallel(ctx, SenseVoiceFullParams::for_language("en"), &samples)?;
+/// # Ok::<(), sense_voice_cpp_rs::error::SenseVoiceError>(())
+/// ```
+#[derive(Default)]
+pub struct ModelRegistry {
+    models: HashMap<String, RegisteredModel>,
+    memory_cap: Option<u64>,
+    /// Incremented on every [ModelRegistry::get], orders the models by last use.
+    clock: u64,
+}
+
+struct RegisteredModel {
+    path: String,
+    params: SenseVoiceContextParameters,
+    size: u64,
+    ctx: Option<SenseVoiceContext>,
+    last_used: u64,
+}
+
+impl ModelRegistry {
+    /// A registry that never unloads models implicitly.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry that keeps the loaded models under `bytes`, see the [module docs](self).
+    pub fn with_memory_cap(bytes: u64) -> Self {
+        Self {
+            memory_cap: Some(bytes),
+            ..Self::default()
+        }
+    }
+
+    /// Register the model at `path` under `name` without loading it yet.
+    ///
+    /// Registering a name again replaces the previous model and unloads it.
+    ///
+    /// # Returns
+    /// [SenseVoiceError::ModelFileIo] if the file's size can't be read.
+    pub fn register(
+        &mut self,
+        name: &str,
+        path: &str,
+        params: SenseVoiceContextParameters,
+    ) -> Result<(), SenseVoiceError> {
+        let size = std::fs::metadata(path)
+            .map_err(|e| SenseVoiceError::ModelFileIo(e.kind()))?
+            .len();
+        self.models.insert(
+            name.to_string(),
+            RegisteredModel {
+                path: path.to_string(),
+                params,
+                size,
+                ctx: None,
+                last_used: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// Register the model at `path` under `name` and load it right away.
+    ///
+    /// # Returns
+    /// The errors of [Self::register] and [SenseVoiceContext::new_with_params]. The model
+    /// stays registered if loading fails.
+    pub fn load(
+        &mut self,
+        name: &str,
+        path: &str,
+        params: SenseVoiceContextParameters,
+    ) -> Result<(), SenseVoiceError> {
+        self.register(name, path, params)?;
+        self.get(name).map(|_| ())
+    }
+
+    /// The context of the model registered as `name`, loading it if needed.
+    ///
+    /// Loading may unload other models first, see the [module docs](self).
+    ///
+    /// # Returns
+    /// [SenseVoiceError::UnknownModel] if nothing is registered under `name`, or the errors
+    /// of [SenseVoiceContext::new_with_params]. Models unloaded to make room stay unloaded
+    /// when loading fails.
+    pub fn get(&mut self, name: &str) -> Result<&mut SenseVoiceContext, SenseVoiceError> {
+        self.clock += 1;
+        let model = self.models.get(name).ok_or(SenseVoiceError::UnknownModel)?;
+        if model.ctx.is_none() {
+            if let Some(cap) = self.memory_cap {
+                let loaded = self.models.iter().filter_map(|(name, model)| {
+                    model
+                        .ctx
+                        .as_ref()
+                        .map(|_| (name.as_str(), model.size, model.last_used))
+                });
+                let victims: Vec<String> = lru_victims(loaded, cap, model.size)
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect();
+                for victim in victims {
+                    self.unload(&victim);
+                }
+            }
+            let model = self.models.get_mut(name).expect("registered model");
+            model.ctx = Some(SenseVoiceContext::new_with_params(
+                &model.path,
+                model.params.clone(),
+            )?);
+        }
+        let model = self.models.get_mut(name).expect("registered model");
+        model.last_used = self.clock;
+        Ok(model.ctx.as_mut().expect("model loaded above"))
+    }
+
+    /// Unload the model registered as `name`, keeping it registered.
+    ///
+    /// # Returns
+    /// Whether a loaded model was unloaded.
+    pub fn unload(&mut self, name: &str) -> bool {
+        self.models
+            .get_mut(name)
+            .is_some_and(|model| model.ctx.take().is_some())
+    }
+
+    /// Unload and forget the model registered as `name`.
+    ///
+    /// # Returns
+    /// Whether a model was registered under `name`.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.models.remove(name).is_some()
+    }
+
+    /// Whether the model registered as `name` is currently loaded.
+    pub fn is_loaded(&self, name: &str) -> bool {
+        self.models
+            .get(name)
+            .is_some_and(|model| model.ctx.is_some())
+    }
+
+    /// Sum of the file sizes of the loaded models, in bytes.
+    pub fn loaded_bytes(&self) -> u64 {
+        self.models
+            .values()
+            .filter(|model| model.ctx.is_some())
+            .map(|model| model.size)
+            .sum()
+    }
+
+    /// The memory cap set with [Self::with_memory_cap].
+    pub fn memory_cap(&self) -> Option<u64> {
+        self.memory_cap
+    }
+
+    /// Names of all registered models, loaded or not, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.models.keys().map(String::as_str)
+    }
+}
+
+/// Names of the loaded models to unload, least recently used first, so that `incoming`
+/// more bytes fit under `cap`.
+///
+/// `loaded` yields the name, size and last use of every loaded model.
+fn lru_victims<'a>(
+    loaded: impl Iterator<Item = (&'a str, u64, u64)>,
+    cap: u64,
+    incoming: u64,
+) -> Vec<&'a str> {
+    let mut loaded: Vec<_> = loaded.collect();
+    loaded.sort_by_key(|&(_, _, last_used)| last_used);
+    let mut total: u64 = loaded.iter().map(|&(_, size, _)| size).sum();
+    let mut victims = Vec::new();
+    for (name, size, _) in loaded {
+        if total + incoming <= cap {
+            break;
+        }
+        total -= size;
+        victims.push(name);
+    }
+    victims
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used_first() {
+        let loaded = [("a", 40, 3), ("b", 30, 1), ("c", 20, 2)];
+        assert!(lru_victims(loaded.into_iter(), 100, 10).is_empty());
+        assert_eq!(lru_victims(loaded.into_iter(), 100, 20), ["b"]);
+        assert_eq!(lru_victims(loaded.into_iter(), 100, 60), ["b", "c"]);
+        // too large for the cap on its own: everything goes
+        assert_eq!(lru_victims(loaded.into_iter(), 100, 150), ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn unknown_models() {
+        let mut registry = ModelRegistry::with_memory_cap(1 << 20);
+        assert!(matches!(
+            registry.get("zh"),
+            Err(SenseVoiceError::UnknownModel)
+        ));
+        assert!(matches!(
+            registry.register("zh", "/nonexistent/model.gguf", Default::default()),
+            Err(SenseVoiceError::ModelFileIo(std::io::ErrorKind::NotFound))
+        ));
+        assert!(!registry.unload("zh") && !registry.remove("zh"));
+        assert_eq!(registry.loaded_bytes(), 0);
+        assert_eq!(registry.names().count(), 0);
+    }
+}
@@ -0,0 +1,405 @@
+//! Owned transcription results.
+//!
+//! Everything here is plain owned data without pointers into the native context, so results
+//! are `Send + Sync` and can be moved into channels or async tasks, while the
+//! [crate::SenseVoiceContext] that produced them stays where it is.
+
+use std::collections::BTreeMap;
+
+/// A piece of transcribed text and the span of input audio it covers.
+///
+/// Marked `#[non_exhaustive]` so that fields can be added without breaking downstream code;
+/// build one outside this crate with [Segment::new].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Segment {
+    /// Start of the segment, in milliseconds from the start of the input audio.
+    pub start_ms: i64,
+    /// End of the segment, in milliseconds from the start of the input audio.
+    pub end_ms: i64,
+    /// The transcribed text, without the language/emotion/event prefix tags.
+    pub text: String,
+    /// Probability the model gave to `<|nospeech|>` for this segment, see
+    /// [crate::full_get_segment_no_speech_prob].
+    pub no_speech_prob: f32,
+    /// Average log probability of the segment's tokens, see
+    /// [crate::full_get_segment_avg_logprob]. NaN if unknown, e.g. for the sentences of
+    /// [crate::words::segment_by_sentence], which weren't decoded on their own.
+    pub avg_logprob: f32,
+}
+
+impl Segment {
+    /// A segment of `text` from `start_ms` to `end_ms` that wasn't decoded by the model,
+    /// e.g. for tests or text from another source: [Segment::no_speech_prob] is 0.0 and
+    /// [Segment::avg_logprob] is NaN, unknown.
+    pub fn new(start_ms: i64, end_ms: i64, text: &str) -> Self {
+        Self {
+            start_ms,
+            end_ms,
+            text: text.to_string(),
+            no_speech_prob: 0.0,
+            avg_logprob: f32::NAN,
+        }
+    }
+}
+
+/// A decoded token, after CTC blanks and repeats were removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// Vocabulary id.
+    pub id: i32,
+    /// The token's text, e.g. `<|en|>` for tags or a word piece for text.
+    pub text: String,
+    /// Start of the CTC frames that emitted the token, in milliseconds. 0 for the prefix tags.
+    pub start_ms: i64,
+    /// End of the CTC frames that emitted the token, in milliseconds. 0 for the prefix tags.
+    pub end_ms: i64,
+}
+
+/// Time spent in each stage of a transcription, in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timings {
+    pub feature_ms: f32,
+    pub encode_ms: f32,
+    pub decode_ms: f32,
+}
+
+/// The complete result of a transcription, see [crate::full_get_transcription].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcription {
+    /// The transcribed text, without prefix tags.
+    pub text: String,
+    pub segments: Vec<Segment>,
+    /// All decoded tokens, including the language/emotion/event/itn tags SenseVoice emits first.
+    pub tokens: Vec<Token>,
+    /// The language tag the model emitted, e.g. `zh` or `en`.
+    pub language: Option<String>,
+    pub timings: Timings,
+}
+
+impl Transcription {
+    /// Group the text tokens into words with timestamps, see [crate::words::words_from_tokens].
+    pub fn words(&self) -> Vec<crate::words::Word> {
+        crate::words::words_from_tokens(&self.tokens)
+    }
+
+    /// Speaking rate in words per minute, for meeting or lecture analytics.
+    ///
+    /// Counts the words of [Self::words] over the time from the start of the first word to
+    /// the end of the last one. The `<|...|>` tags SenseVoice emits, including the audio
+    /// event tags such as `<|Laughter|>` or `<|BGM|>`, are not words and aren't counted.
+    /// CJK characters count as one word each.
+    ///
+    /// Returns 0.0 if there are no words or they span no time.
+    pub fn words_per_minute(&self) -> f32 {
+        let words = self.words();
+        let (Some(first), Some(last)) = (words.first(), words.last()) else {
+            return 0.0;
+        };
+        let duration_ms = last.end_ms - first.start_ms;
+        if duration_ms <= 0 {
+            return 0.0;
+        }
+        words.len() as f32 * 60_000.0 / duration_ms as f32
+    }
+
+    /// One confidence score for the whole transcription, in `0.0..=1.0`, e.g. to flag
+    /// transcriptions for a retry or human review.
+    ///
+    /// The score is `exp(Σ dᵢ·lpᵢ / Σ dᵢ)`, the geometric mean token probability with each
+    /// segment's [Segment::avg_logprob] `lpᵢ` weighted by its duration `dᵢ`. If no segment
+    /// spans any time, the segments are weighted equally. Segments whose `avg_logprob` is
+    /// unknown (NaN) are left out.
+    ///
+    /// This is a heuristic, not a calibrated probability: a 0.8 doesn't mean 80% of the
+    /// words are right. Segments that emitted no tokens have an `avg_logprob` of 0.0 and
+    /// count as fully confident, so check [Segment::no_speech_prob] for silence as well.
+    /// Compare scores from the same model on similar audio only, and pick thresholds on
+    /// your own data.
+    ///
+    /// Returns 0.0 if no segment has a known `avg_logprob`.
+    pub fn overall_confidence(&self) -> f32 {
+        let segments: Vec<&Segment> = self
+            .segments
+            .iter()
+            .filter(|segment| !segment.avg_logprob.is_nan())
+            .collect();
+        if segments.is_empty() {
+            return 0.0;
+        }
+        let durations: Vec<f32> = segments
+            .iter()
+            .map(|segment| (segment.end_ms - segment.start_ms).max(0) as f32)
+            .collect();
+        let total: f32 = durations.iter().sum();
+        let avg_logprob = if total > 0.0 {
+            segments
+                .iter()
+                .zip(&durations)
+                .map(|(segment, duration)| segment.avg_logprob * duration)
+                .sum::<f32>()
+                / total
+        } else {
+            segments
+                .iter()
+                .map(|segment| segment.avg_logprob)
+                .sum::<f32>()
+                / segments.len() as f32
+        };
+        avg_logprob.exp().clamp(0.0, 1.0)
+    }
+
+    /// Segment texts keyed by their start time, in milliseconds from the start of the input
+    /// audio, e.g. as an index for caption editors.
+    ///
+    /// Segments that start at the same millisecond share one entry, their texts joined with a
+    /// space in segment order.
+    pub fn by_timecode(&self) -> BTreeMap<i64, String> {
+        let mut map = BTreeMap::new();
+        for segment in &self.segments {
+            map.entry(segment.start_ms)
+                .and_modify(|text: &mut String| {
+                    text.push(' ');
+                    text.push_str(&segment.text);
+                })
+                .or_insert_with(|| segment.text.clone());
+        }
+        map
+    }
+
+    /// Remove the spaces the tokenizer put between CJK characters from the text and every
+    /// segment, see [crate::text::collapse_cjk_spaces]. Tokens are left as they are.
+    pub fn collapse_cjk_spaces(mut self) -> Self {
+        self.text = crate::text::collapse_cjk_spaces(&self.text);
+        for segment in &mut self.segments {
+            segment.text = crate::text::collapse_cjk_spaces(&segment.text);
+        }
+        self
+    }
+
+    /// Redact the text and every segment with [crate::text::redact].
+    ///
+    /// The text tokens are dropped, since their pieces would still spell out what was
+    /// redacted; the tags are kept, so [Self::language] stays valid but [Self::words] comes
+    /// back empty. Requires the `regex` feature.
+    #[cfg(feature = "regex")]
+    pub fn redact(mut self, patterns: &[regex::Regex]) -> Self {
+        self.text = crate::text::redact(&self.text, patterns);
+        for segment in &mut self.segments {
+            segment.text = crate::text::redact(&segment.text, patterns);
+        }
+        self.tokens
+            .retain(|token| crate::words::is_tag(&token.text));
+        self
+    }
+}
+
+/// Unit of the timestamps returned by [crate::full_get_token_times].
+///
+/// SenseVoice decodes with CTC, so a token spans a run of encoder frames and one frame is the
+/// finest resolution there is: 60 ms or 960 samples for SenseVoiceSmall, see
+/// [crate::model::FrontendParams::encoder_frame_ms]. There is no sub-frame alignment like
+/// whisper's DTW timestamps. Every unit expresses the same frame boundaries exactly, so
+/// [Self::Samples] gives sample indices to cut the input at, not finer timing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TimestampPrecision {
+    /// Milliseconds from the start of the input, like [Token::start_ms].
+    #[default]
+    Milliseconds,
+    /// Sample indices at [crate::SENSE_VOICE_SAMPLE_RATE] from the start of the input.
+    Samples,
+    /// Encoder frames from the start of the input, the model's native unit.
+    Frames,
+}
+
+impl TimestampPrecision {
+    /// Convert `ms` milliseconds to this unit, rounding down; frames are `frame_ms` long.
+    pub fn from_ms(self, ms: i64, frame_ms: u32) -> i64 {
+        match self {
+            Self::Milliseconds => ms,
+            Self::Samples => ms_to_samples(ms),
+            Self::Frames => ms / i64::from(frame_ms.max(1)),
+        }
+    }
+
+    /// Convert `value` of this unit to milliseconds, rounding down; frames are `frame_ms` long.
+    pub fn to_ms(self, value: i64, frame_ms: u32) -> i64 {
+        match self {
+            Self::Milliseconds => value,
+            Self::Samples => value * 1000 / i64::from(crate::SENSE_VOICE_SAMPLE_RATE),
+            Self::Frames => value * i64::from(frame_ms),
+        }
+    }
+}
+
+/// Convert milliseconds to a sample count at [crate::SENSE_VOICE_SAMPLE_RATE].
+pub fn ms_to_samples(ms: i64) -> i64 {
+    ms * i64::from(crate::SENSE_VOICE_SAMPLE_RATE) / 1000
+}
+
+/// Convert a sample count at [crate::SENSE_VOICE_SAMPLE_RATE] to milliseconds, rounding down.
+pub fn samples_to_ms(n_samples: usize) -> i64 {
+    n_samples as i64 * 1000 / i64::from(crate::SENSE_VOICE_SAMPLE_RATE)
+}
+
+/// Extract the language code from a SenseVoice language tag such as `<|en|>`.
+pub(crate) fn language_from_tag(tag: &str) -> Option<String> {
+    tag.strip_prefix("<|")?
+        .strip_suffix("|>")
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn results_are_send_and_sync() {
+        assert_send::<Transcription>();
+        assert_sync::<Transcription>();
+        assert_send::<Segment>();
+        assert_sync::<Segment>();
+        assert_send::<Token>();
+        assert_sync::<Token>();
+    }
+
+    #[test]
+    fn words_per_minute() {
+        let token = |text: &str, start_ms, end_ms| Token {
+            id: 0,
+            text: text.to_string(),
+            start_ms,
+            end_ms,
+        };
+        let mut transcription = Transcription {
+            text: String::new(),
+            segments: Vec::new(),
+            tokens: vec![
+                token("<|en|>", 0, 0),
+                token("<|Laughter|>", 0, 0),
+                token("▁one", 0, 10_000),
+                token("▁two", 10_000, 20_000),
+                token("▁three", 20_000, 30_000),
+            ],
+            language: Some("en".to_string()),
+            timings: Timings::default(),
+        };
+        assert_eq!(transcription.words_per_minute(), 6.0);
+
+        transcription.tokens.truncate(2);
+        assert_eq!(transcription.words_per_minute(), 0.0);
+    }
+
+    #[test]
+    fn by_timecode() {
+        let segment = |start_ms, text: &str| Segment {
+            start_ms,
+            end_ms: start_ms + 1000,
+            text: text.to_string(),
+            no_speech_prob: 0.0,
+            avg_logprob: 0.0,
+        };
+        let transcription = Transcription {
+            text: String::new(),
+            segments: vec![
+                segment(2000, "third"),
+                segment(0, "first"),
+                segment(0, "second"),
+            ],
+            tokens: Vec::new(),
+            language: None,
+            timings: Timings::default(),
+        };
+        let map = transcription.by_timecode();
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            [(0, "first second".to_string()), (2000, "third".to_string())]
+        );
+    }
+
+    #[test]
+    fn overall_confidence() {
+        let segment = |start_ms, end_ms, avg_logprob| Segment {
+            start_ms,
+            end_ms,
+            text: String::new(),
+            no_speech_prob: 0.0,
+            avg_logprob,
+        };
+        let mut transcription = Transcription {
+            text: String::new(),
+            segments: Vec::new(),
+            tokens: Vec::new(),
+            language: None,
+            timings: Timings::default(),
+        };
+        assert_eq!(transcription.overall_confidence(), 0.0);
+
+        // 3 s at ln(0.5) and 1 s at 0.0 average to 0.75·ln(0.5)
+        transcription.segments = vec![segment(0, 3000, 0.5f32.ln()), segment(3000, 4000, 0.0)];
+        let expected = 0.5f32.powf(0.75);
+        assert!((transcription.overall_confidence() - expected).abs() < 1e-6);
+
+        transcription.segments = vec![segment(0, 0, 0.25f32.ln()), segment(0, 0, 0.0)];
+        assert!((transcription.overall_confidence() - 0.5).abs() < 1e-6);
+
+        // segments of unknown confidence don't count
+        transcription.segments = vec![segment(0, 1000, 0.5f32.ln()), Segment::new(1000, 2000, "")];
+        assert!((transcription.overall_confidence() - 0.5).abs() < 1e-6);
+        transcription.segments = vec![Segment::new(0, 1000, "")];
+        assert_eq!(transcription.overall_confidence(), 0.0);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn redact_drops_text_tokens() {
+        let token = |text: &str| Token {
+            id: 0,
+            text: text.to_string(),
+            start_ms: 0,
+            end_ms: 0,
+        };
+        let transcription = Transcription {
+            text: "my pin is 1234".to_string(),
+            segments: vec![Segment {
+                start_ms: 0,
+                end_ms: 1000,
+                text: "my pin is 1234".to_string(),
+                no_speech_prob: 0.0,
+                avg_logprob: 0.0,
+            }],
+            tokens: vec![token("<|en|>"), token("▁my"), token("▁pin"), token("▁1234")],
+            language: Some("en".to_string()),
+            timings: Timings::default(),
+        }
+        .redact(&[regex::Regex::new(r"\d+").unwrap()]);
+        assert_eq!(transcription.text, "my pin is [REDACTED]");
+        assert_eq!(transcription.segments[0].text, "my pin is [REDACTED]");
+        assert_eq!(transcription.tokens, [token("<|en|>")]);
+    }
+
+    #[test]
+    fn timestamp_conversions() {
+        assert_eq!(ms_to_samples(60), 960);
+        assert_eq!(samples_to_ms(960), 60);
+        assert_eq!(samples_to_ms(959), 59);
+        for (precision, value) in [
+            (TimestampPrecision::Milliseconds, 1_260),
+            (TimestampPrecision::Samples, 20_160),
+            (TimestampPrecision::Frames, 21),
+        ] {
+            assert_eq!(precision.from_ms(1_260, 60), value);
+            assert_eq!(precision.to_ms(value, 60), 1_260);
+        }
+        assert_eq!(TimestampPrecision::Frames.from_ms(1_299, 60), 21);
+    }
+
+    #[test]
+    fn language_tag() {
+        assert_eq!(language_from_tag("<|zh|>").as_deref(), Some("zh"));
+        assert_eq!(language_from_tag("hello"), None);
+    }
+}
@@ -0,0 +1,361 @@
+//! Pluggable audio sources: pull samples from anywhere and transcribe them as they arrive.
+//!
+//! Implement [SampleSource] for a network socket, a hardware SDK or a decoder, and hand it to
+//! [transcribe_source], which resamples the samples to [SENSE_VOICE_SAMPLE_RATE], buffers
+//! them window by window and transcribes each window like [crate::stream::transcribe_iter].
+//! [WavSource] is the reference implementation for WAV files.
+
+use std::{cell::Cell, collections::VecDeque, path::Path, time::Duration};
+
+use crate::{
+    SENSE_VOICE_SAMPLE_RATE, SenseVoiceContext, SenseVoiceFullParams,
+    audio::WavReader,
+    error::SenseVoiceError,
+    full_parallel_segment,
+    segment::{self, Segment},
+    stream::for_each_window,
+};
+
+/// Samples read from a source per [SampleSource::read_samples] call.
+const READ_CHUNK: usize = 4096;
+
+/// A source of mono samples with full scale at ±1.0, see [transcribe_source].
+///
+/// # Contract
+/// * [Self::read_samples] fills the start of `buf` and returns how many samples it wrote, at
+///   most `buf.len()`. It may block until samples are available and may return fewer than
+///   asked for, but returns 0 only at the end of the input: the first 0 ends the
+///   transcription and the source isn't read again.
+/// * [Self::sample_rate] is the rate of those samples in Hz and doesn't change while reading.
+/// * A source that fails returns 0 and hands out the error from [Self::take_error].
+pub trait SampleSource {
+    /// Fill the start of `buf` with the next samples, returning their number; 0 at the end.
+    fn read_samples(&mut self, buf: &mut [f32]) -> usize;
+
+    /// Sample rate of the samples in Hz. Defaults to [SENSE_VOICE_SAMPLE_RATE].
+    fn sample_rate(&self) -> u32 {
+        SENSE_VOICE_SAMPLE_RATE
+    }
+
+    /// The error that ended the input, if any, asked for once [Self::read_samples] returned
+    /// 0. Defaults to None, for sources that can't fail.
+    fn take_error(&mut self) -> Option<SenseVoiceError> {
+        None
+    }
+}
+
+impl<S: SampleSource + ?Sized> SampleSource for &mut S {
+    fn read_samples(&mut self, buf: &mut [f32]) -> usize {
+        (**self).read_samples(buf)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        (**self).sample_rate()
+    }
+
+    fn take_error(&mut self) -> Option<SenseVoiceError> {
+        (**self).take_error()
+    }
+}
+
+/// A [SampleSource] reading a WAV file incrementally through [WavReader], down-mixed to mono
+/// at the file's sample rate.
+pub struct WavSource {
+    reader: WavReader,
+    error: Option<SenseVoiceError>,
+}
+
+impl WavSource {
+    /// Open a WAV file, see [WavReader::open] for the supported formats and errors.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SenseVoiceError> {
+        Ok(Self::new(WavReader::open(path)?))
+    }
+
+    /// Read the remaining samples of `reader`.
+    pub fn new(reader: WavReader) -> Self {
+        Self {
+            reader,
+            error: None,
+        }
+    }
+}
+
+impl SampleSource for WavSource {
+    fn read_samples(&mut self, buf: &mut [f32]) -> usize {
+        if self.error.is_some() {
+            return 0;
+        }
+        match self.reader.read_frames(buf.len()) {
+            Ok(frames) => {
+                for (dst, &sample) in buf.iter_mut().zip(&frames) {
+                    *dst = sample as f32;
+                }
+                frames.len()
+            }
+            Err(e) => {
+                self.error = Some(e);
+                0
+            }
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.reader.sample_rate()
+    }
+
+    fn take_error(&mut self) -> Option<SenseVoiceError> {
+        self.error.take()
+    }
+}
+
+/// Transcribe the samples of `source` window by window as they are read.
+///
+/// The samples are at full scale ±1.0, so [SenseVoiceFullParams::normalized_input] is
+/// switched on.
+///
+/// Samples at other rates than [SENSE_VOICE_SAMPLE_RATE] are resampled on the fly with the
+/// linear interpolation of [crate::audio::resample], giving the same samples as resampling
+/// the whole input at once. Windows, buffering and timestamps are those of
+/// [crate::stream::transcribe_iter]: at most one `window` plus one second of lookahead is
+/// held in memory, and segment timestamps are relative to the first sample.
+///
+/// # Errors
+/// [SenseVoiceError::InvalidAudioBuffer] for a sample rate of 0. Otherwise stops at the first
+/// failing window, or once the source reported an error through
+/// [SampleSource::take_error], and returns that error.
+pub fn transcribe_source(
+    ctx: &mut SenseVoiceContext,
+    mut params: SenseVoiceFullParams,
+    mut source: impl SampleSource,
+    window: Duration,
+) -> Result<Vec<Segment>, SenseVoiceError> {
+    params.normalized_input = true;
+    let sample_rate = source.sample_rate();
+    if sample_rate == 0 {
+        return Err(SenseVoiceError::InvalidAudioBuffer(
+            "sample rate must not be 0",
+        ));
+    }
+    let window_ms = (window.as_millis() as usize).max(1);
+    let error = Cell::new(None);
+    let samples = SourceSamples {
+        source: &mut source,
+        buf: vec![0.0; READ_CHUNK],
+        pos: 0,
+        len: 0,
+        error: &error,
+    };
+    let samples = Resampler::new(samples, sample_rate, SENSE_VOICE_SAMPLE_RATE);
+
+    let mut segments = Vec::new();
+    for_each_window(samples.map(f64::from), window_ms, |range, data| {
+        // the source failed while this window was filled, don't transcribe what's left
+        if let Some(e) = error.take() {
+            return Err(e);
+        }
+        let start_ms = segment::samples_to_ms(range.start);
+        segments.push(full_parallel_segment(
+            ctx,
+            params.clone(),
+            data,
+            start_ms,
+            None,
+        )?);
+        Ok(())
+    })?;
+    match error.take() {
+        Some(e) => Err(e),
+        None => Ok(segments),
+    }
+}
+
+/// The samples of a [SampleSource] one by one, read [READ_CHUNK] at a time. Stores the
+/// source's error in `error` at the end of the input.
+struct SourceSamples<'a, S: SampleSource> {
+    source: &'a mut S,
+    buf: Vec<f32>,
+    pos: usize,
+    /// Samples in `buf`, 0 once the source ended.
+    len: usize,
+    error: &'a Cell<Option<SenseVoiceError>>,
+}
+
+impl<S: SampleSource> Iterator for SourceSamples<'_, S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.pos == self.len {
+            if self.buf.is_empty() {
+                return None;
+            }
+            self.len = self.source.read_samples(&mut self.buf).min(self.buf.len());
+            self.pos = 0;
+            if self.len == 0 {
+                // never read again, see the SampleSource contract
+                self.buf = Vec::new();
+                self.error.set(self.source.take_error());
+                return None;
+            }
+        }
+        self.pos += 1;
+        Some(self.buf[self.pos - 1])
+    }
+}
+
+/// The linear interpolation of [crate::audio::resample], one output sample at a time.
+struct Resampler<I: Iterator<Item = f32>> {
+    samples: I,
+    from_rate: u64,
+    to_rate: u64,
+    /// Index of the next output sample.
+    i: u64,
+    /// Input samples from index `base` on that are still needed.
+    pending: VecDeque<f32>,
+    base: usize,
+}
+
+impl<I: Iterator<Item = f32>> Resampler<I> {
+    fn new(samples: I, from_rate: u32, to_rate: u32) -> Self {
+        Self {
+            samples,
+            from_rate: u64::from(from_rate),
+            to_rate: u64::from(to_rate),
+            i: 0,
+            pending: VecDeque::new(),
+            base: 0,
+        }
+    }
+}
+
+impl<I: Iterator<Item = f32>> Iterator for Resampler<I> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let pos = self.i as f64 * (self.from_rate as f64 / self.to_rate as f64);
+        let index = pos as usize;
+        // resample makes `n * to / from` samples of `n`, so output `i` exists once the input
+        // has `(i + 1) * from / to` samples, rounded up
+        let needed = ((self.i + 1) * self.from_rate).div_ceil(self.to_rate) as usize;
+        while self.base + self.pending.len() < needed.max(index + 2) {
+            match self.samples.next() {
+                Some(sample) => self.pending.push_back(sample),
+                None => break,
+            }
+        }
+        if self.base + self.pending.len() < needed {
+            return None;
+        }
+        let skip = (index - self.base).min(self.pending.len());
+        self.pending.drain(..skip);
+        self.base += skip;
+        let a = *self.pending.front()?;
+        let b = self.pending.get(1).copied().unwrap_or(a);
+        self.i += 1;
+        Some(a + (b - a) * (pos - index as f64) as f32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::audio::resample;
+
+    /// Hands out `samples` in chunks of at most `chunk`, then fails with `error`.
+    struct ChunkedSource {
+        samples: Vec<f32>,
+        chunk: usize,
+        sample_rate: u32,
+        error: Option<SenseVoiceError>,
+    }
+
+    impl SampleSource for ChunkedSource {
+        fn read_samples(&mut self, buf: &mut [f32]) -> usize {
+            let n = self.chunk.min(buf.len()).min(self.samples.len());
+            buf[..n].copy_from_slice(&self.samples[..n]);
+            self.samples.drain(..n);
+            n
+        }
+
+        fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn take_error(&mut self) -> Option<SenseVoiceError> {
+            self.error.take()
+        }
+    }
+
+    #[test]
+    fn streaming_resampler_matches_resample() {
+        let input: Vec<f32> = (0..1_001).map(|i| (i as f32 * 0.37).sin()).collect();
+        for (from, to) in [
+            (16_000, 16_000),
+            (8_000, 16_000),
+            (44_100, 16_000),
+            (48_000, 16_000),
+        ] {
+            let streamed: Vec<f32> = Resampler::new(input.iter().copied(), from, to).collect();
+            let expected = resample(&input, from, to);
+            assert_eq!(streamed.len(), expected.len(), "{from} Hz to {to} Hz");
+            for (s, e) in streamed.iter().zip(&expected) {
+                assert!((s - e).abs() < 1e-5, "{from} Hz to {to} Hz");
+            }
+        }
+        assert_eq!(Resampler::new(std::iter::empty(), 8_000, 16_000).count(), 0);
+    }
+
+    #[test]
+    fn source_samples_end_at_the_first_zero_and_keep_the_error() {
+        let mut source = ChunkedSource {
+            samples: (0..10).map(|i| i as f32).collect(),
+            chunk: 3,
+            sample_rate: SENSE_VOICE_SAMPLE_RATE,
+            error: Some(SenseVoiceError::Cancelled),
+        };
+        let error = Cell::new(None);
+        let samples: Vec<f32> = SourceSamples {
+            source: &mut source,
+            buf: vec![0.0; READ_CHUNK],
+            pos: 0,
+            len: 0,
+            error: &error,
+        }
+        .collect();
+        assert_eq!(samples, (0..10).map(|i| i as f32).collect::<Vec<_>>());
+        assert!(matches!(error.take(), Some(SenseVoiceError::Cancelled)));
+    }
+
+    #[test]
+    fn source_errors_are_returned() {
+        let mut ctx = SenseVoiceContext::from_raw(std::ptr::null_mut());
+        let params =
+            SenseVoiceFullParams::default_params(crate::SenseVoiceDecodingStrategy::SamplingGreedy);
+        let failing = ChunkedSource {
+            samples: Vec::new(),
+            chunk: 1,
+            sample_rate: 48_000,
+            error: Some(SenseVoiceError::AudioFileIo(
+                std::io::ErrorKind::UnexpectedEof,
+            )),
+        };
+        let result = transcribe_source(&mut ctx, params.clone(), failing, Duration::from_secs(30));
+        assert!(matches!(
+            result,
+            Err(SenseVoiceError::AudioFileIo(
+                std::io::ErrorKind::UnexpectedEof
+            ))
+        ));
+
+        let no_rate = ChunkedSource {
+            samples: vec![0.0; 16],
+            chunk: 16,
+            sample_rate: 0,
+            error: None,
+        };
+        let result = transcribe_source(&mut ctx, params, no_rate, Duration::from_secs(30));
+        assert!(matches!(
+            result,
+            Err(SenseVoiceError::InvalidAudioBuffer(_))
+        ));
+    }
+}
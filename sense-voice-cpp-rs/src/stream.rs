@@ -0,0 +1,792 @@
+//! Transcription that hands out results while it is still running.
+
+use std::{
+    ops::Range,
+    path::Path,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    SENSE_VOICE_SAMPLE_RATE, SenseVoiceContext, SenseVoiceFullParams, VAD_WINDOW_SAMPLES,
+    audio::{self, WavReader},
+    error::SenseVoiceError,
+    full_parallel_segment, get_speech_prob,
+    segment::{self, Segment},
+};
+
+/// Length of the windows [SenseVoiceContext::transcribe_to_channel] cuts the input into, in
+/// milliseconds.
+pub const STREAM_WINDOW_MS: usize = 30_000;
+
+/// A trailing window shorter than this (in milliseconds) is merged into the previous one,
+/// SenseVoice produces unreliable output for very short clips.
+const MIN_TAIL_MS: usize = 1_000;
+
+impl SenseVoiceContext {
+    /// Transcribe `data` on a background thread, sending each segment into a bounded channel
+    /// as soon as it is finalized.
+    ///
+    /// The audio is split into windows of [STREAM_WINDOW_MS] and every window becomes one
+    /// [Segment], with timestamps relative to the start of `data`. The context is shared with
+    /// the worker thread and locked only while a window is being transcribed.
+    ///
+    /// # Backpressure
+    /// The channel holds at most `bound` segments. When the consumer is slower than the
+    /// transcription, the worker blocks on sending and doesn't start the next window until
+    /// there is room again, so at most `bound` finished segments are buffered.
+    ///
+    /// # Cancellation
+    /// Dropping the [SegmentReceiver] stops the worker: the window that is running is aborted
+    /// through the abort callback (cooperatively, see
+    /// [crate::SenseVoiceFullParamsBuilder::timeout]) and no further windows are started. The
+    /// context is unlocked once the aborted window returned, which can take until the current
+    /// graph node finished.
+    ///
+    /// If a window fails, the error is sent instead of a segment and the worker stops.
+    pub fn transcribe_to_channel(
+        ctx: Arc<Mutex<SenseVoiceContext>>,
+        data: Vec<f64>,
+        params: SenseVoiceFullParams,
+        bound: usize,
+    ) -> SegmentReceiver {
+        let (tx, rx) = mpsc::sync_channel(bound);
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let worker_cancelled = cancelled.clone();
+        thread::spawn(move || {
+            let cancel = || worker_cancelled.load(Ordering::Relaxed);
+            for window in windows(data.len(), STREAM_WINDOW_MS) {
+                if cancel() {
+                    break;
+                }
+                let start_ms = crate::segment::samples_to_ms(window.start);
+                let result = {
+                    let mut ctx = ctx.lock().unwrap_or_else(PoisonError::into_inner);
+                    full_parallel_segment(
+                        &mut ctx,
+                        params.clone(),
+                        &data[window],
+                        start_ms,
+                        Some(&cancel),
+                    )
+                };
+                let failed = result.is_err();
+                if tx.send(result).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        SegmentReceiver { rx, cancelled }
+    }
+}
+
+/// The receiving end of [SenseVoiceContext::transcribe_to_channel].
+///
+/// Derefs to the [mpsc::Receiver] of the segments; dropping it cancels the transcription.
+#[derive(Debug)]
+pub struct SegmentReceiver {
+    rx: mpsc::Receiver<Result<Segment, SenseVoiceError>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl std::ops::Deref for SegmentReceiver {
+    type Target = mpsc::Receiver<Result<Segment, SenseVoiceError>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+impl<'a> IntoIterator for &'a SegmentReceiver {
+    type Item = Result<Segment, SenseVoiceError>;
+    type IntoIter = mpsc::Iter<'a, Result<Segment, SenseVoiceError>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rx.iter()
+    }
+}
+
+impl Drop for SegmentReceiver {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Transcribe `data` on tokio's blocking thread pool and yield each segment as soon as it is
+/// finalized.
+///
+/// The async counterpart of [SenseVoiceContext::transcribe_to_channel]: windows, timestamps,
+/// locking and cancellation are the same, and the transcription runs at most one segment
+/// ahead of the consumer. Must be called from within a tokio runtime. Requires the `tokio`
+/// feature.
+///
+/// # Cancellation
+/// Dropping the stream cancels the transcription: the window that is running is aborted
+/// through the abort callback (cooperatively, see [crate::SenseVoiceFullParamsBuilder::timeout])
+/// and no further windows are started. The context is unlocked once the aborted window
+/// returned, which can take until the current graph node finished.
+///
+/// If a window fails, its error is yielded instead of a segment and the stream ends.
+#[cfg(feature = "tokio")]
+pub fn transcribe_stream(
+    ctx: Arc<Mutex<SenseVoiceContext>>,
+    params: SenseVoiceFullParams,
+    data: Vec<f64>,
+) -> impl futures_core::Stream<Item = Result<Segment, SenseVoiceError>> {
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+
+    tokio::task::spawn_blocking(move || {
+        let cancel = || tx.is_closed();
+        for window in windows(data.len(), STREAM_WINDOW_MS) {
+            if cancel() {
+                break;
+            }
+            let start_ms = crate::segment::samples_to_ms(window.start);
+            let result = {
+                let mut ctx = ctx.lock().unwrap_or_else(PoisonError::into_inner);
+                full_parallel_segment(
+                    &mut ctx,
+                    params.clone(),
+                    &data[window],
+                    start_ms,
+                    Some(&cancel),
+                )
+            };
+            let failed = result.is_err();
+            if tx.blocking_send(result).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    SegmentStream { rx }
+}
+
+#[cfg(feature = "tokio")]
+struct SegmentStream {
+    rx: tokio::sync::mpsc::Receiver<Result<Segment, SenseVoiceError>>,
+}
+
+#[cfg(feature = "tokio")]
+impl futures_core::Stream for SegmentStream {
+    type Item = Result<Segment, SenseVoiceError>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// Transcribe a WAV file window by window, without loading the whole file.
+///
+/// The file is read with [WavReader], so it must be in one of its sample formats at 16 kHz;
+/// multi-channel files are down-mixed to mono. Only one `window` of audio is held in memory
+/// at a time, and windows are cut the same way as in
+/// [SenseVoiceContext::transcribe_to_channel], including merging a very short tail into the
+/// last window. Segment timestamps are relative to the start of the file. The samples are at
+/// full scale ±1.0, so [SenseVoiceFullParams::normalized_input] is switched on.
+///
+/// `window` should stay at or below [STREAM_WINDOW_MS]; the encoder's context is limited and
+/// much longer windows don't make better use of it.
+///
+/// # Errors
+/// Stops at the first failing read or transcription and returns its error.
+pub fn transcribe_file_streaming<P: AsRef<Path>>(
+    ctx: &mut SenseVoiceContext,
+    path: P,
+    mut params: SenseVoiceFullParams,
+    window: Duration,
+) -> Result<Vec<Segment>, SenseVoiceError> {
+    params.normalized_input = true;
+    let mut reader = WavReader::open(path)?;
+    if reader.sample_rate() != SENSE_VOICE_SAMPLE_RATE {
+        return Err(SenseVoiceError::InvalidAudioBuffer(
+            "WAV sample rate must be 16 kHz",
+        ));
+    }
+    let window_ms = (window.as_millis() as usize).max(1);
+
+    let mut segments = Vec::new();
+    for window in windows(reader.n_frames(), window_ms) {
+        let data = reader.read_frames(window.len())?;
+        let start_ms = crate::segment::samples_to_ms(window.start);
+        segments.push(full_parallel_segment(
+            ctx,
+            params.clone(),
+            &data,
+            start_ms,
+            None,
+        )?);
+    }
+    Ok(segments)
+}
+
+/// Transcribe samples from an iterator window by window, without collecting them first.
+///
+/// The streaming counterpart of [crate::full_parallel_iter] for lazy sources such as decoders
+/// or network streams. `samples` are mono at 16 kHz and pulled as they are needed: at most one
+/// `window` plus one second of lookahead is buffered, so memory stays bounded however long
+/// the input is. The lookahead lets a short tail be merged into the last window, so windows
+/// are cut as in [SenseVoiceContext::transcribe_to_channel]. Segment timestamps are relative
+/// to the first sample. The samples are at full scale ±1.0 like those of
+/// [transcribe_file_streaming], so [SenseVoiceFullParams::normalized_input] is switched on.
+///
+/// `window` should stay at or below [STREAM_WINDOW_MS], see [transcribe_file_streaming].
+///
+/// # Errors
+/// Stops at the first failing window and returns its error.
+pub fn transcribe_iter(
+    ctx: &mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    samples: impl IntoIterator<Item = f32>,
+    window: Duration,
+) -> Result<Vec<Segment>, SenseVoiceError> {
+    transcribe_iter_with(params, samples, window, |params, data, start_ms| {
+        full_parallel_segment(ctx, params, data, start_ms, None)
+    })
+}
+
+/// [transcribe_iter] with `transcribe(params, data, start_ms)` transcribing each window.
+fn transcribe_iter_with(
+    mut params: SenseVoiceFullParams,
+    samples: impl IntoIterator<Item = f32>,
+    window: Duration,
+    mut transcribe: impl FnMut(SenseVoiceFullParams, &[f64], i64) -> Result<Segment, SenseVoiceError>,
+) -> Result<Vec<Segment>, SenseVoiceError> {
+    params.normalized_input = true;
+    let window_ms = (window.as_millis() as usize).max(1);
+    let mut segments = Vec::new();
+    for_each_window(
+        samples.into_iter().map(f64::from),
+        window_ms,
+        |range, data| {
+            let start_ms = crate::segment::samples_to_ms(range.start);
+            segments.push(transcribe(params.clone(), data, start_ms)?);
+            Ok(())
+        },
+    )?;
+    Ok(segments)
+}
+
+/// Milliseconds of audio in one voice activity detector window, [VAD_WINDOW_SAMPLES].
+const VAD_WINDOW_MS: usize = VAD_WINDOW_SAMPLES * 1000 / SENSE_VOICE_SAMPLE_RATE as usize;
+
+/// Tuning of [VadGatedStream]. The defaults suit conversational speech from a close
+/// microphone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VadGateParams {
+    /// [get_speech_prob] at or above which a window counts as speech, default 0.5.
+    ///
+    /// Lower values catch quiet and distant speech but let more noise through to the model;
+    /// raise it in noisy rooms.
+    pub threshold: f32,
+    /// Silence after speech before a segment is closed and transcribed, in milliseconds,
+    /// default 500.
+    ///
+    /// This is the end-of-utterance latency on top of the transcription itself. Shorter
+    /// values answer faster but split sentences at every pause, and the model transcribes
+    /// fragments worse than whole phrases; longer values give it more context.
+    pub hangover_ms: usize,
+    /// Speech shorter than this is dropped without running the model, in milliseconds,
+    /// default 250.
+    ///
+    /// Filters out clicks, coughs and other short bursts the detector mistakes for speech.
+    /// Too high a value drops short answers like "yes" or "no".
+    pub min_speech_ms: usize,
+    /// Audio before the detected onset that is transcribed with the segment, in
+    /// milliseconds, default 200.
+    ///
+    /// The detector reacts to the voiced part of a word, so soft onsets like "s" or "f"
+    /// would be cut without it. Costs nothing in latency.
+    pub pre_roll_ms: usize,
+    /// Continuous speech longer than this is cut into several segments, in milliseconds,
+    /// default [STREAM_WINDOW_MS].
+    ///
+    /// Bounds memory and the latency of long monologues; cuts can fall mid-word.
+    pub max_speech_ms: usize,
+}
+
+impl Default for VadGateParams {
+    fn default() -> Self {
+        Self {
+            threshold: 0.5,
+            hangover_ms: 500,
+            min_speech_ms: 250,
+            pre_roll_ms: 200,
+            max_speech_ms: STREAM_WINDOW_MS,
+        }
+    }
+}
+
+/// Speech segmentation of [VadGatedStream], in units of detector windows.
+#[derive(Debug)]
+struct Gate {
+    hangover: usize,
+    min_speech: usize,
+    max_speech: usize,
+    /// First window of the current segment, if one is open.
+    start: Option<usize>,
+    /// Last window of the current segment that was speech.
+    last_speech: usize,
+}
+
+impl Gate {
+    fn new(params: &VadGateParams) -> Self {
+        let windows = |ms: usize| ms.div_ceil(VAD_WINDOW_MS);
+        Self {
+            hangover: windows(params.hangover_ms).max(1),
+            min_speech: windows(params.min_speech_ms),
+            max_speech: windows(params.max_speech_ms).max(1),
+            start: None,
+            last_speech: 0,
+        }
+    }
+
+    /// Feed the detector's verdict on window `i`, returning the windows of a segment that
+    /// ended with it.
+    fn step(&mut self, i: usize, speech: bool) -> Option<Range<usize>> {
+        let Some(start) = self.start else {
+            if speech {
+                self.start = Some(i);
+                self.last_speech = i;
+            }
+            return None;
+        };
+        if speech {
+            self.last_speech = i;
+        }
+        if i - self.last_speech >= self.hangover || i + 1 - start >= self.max_speech {
+            return self.close(i + 1);
+        }
+        None
+    }
+
+    /// End the open segment, if any, after window `end - 1`, dropping it if it holds too
+    /// little speech.
+    fn close(&mut self, end: usize) -> Option<Range<usize>> {
+        let start = self.start.take()?;
+        (self.last_speech + 1 - start >= self.min_speech).then_some(start..end)
+    }
+}
+
+/// Transcribes a live audio stream, e.g. from a microphone, running the model only on the
+/// stretches that contain speech.
+///
+/// Pushed audio is scored in windows of [VAD_WINDOW_SAMPLES] (40 ms) with [get_speech_prob],
+/// which is far cheaper than a transcription. A segment opens at the first speech window,
+/// extended back by [VadGateParams::pre_roll_ms], and closes after
+/// [VadGateParams::hangover_ms] of silence; then it is transcribed with [crate::full_parallel]
+/// and handed to `on_segment`, timestamped from the first pushed sample. Outside of speech
+/// only the pre-roll is buffered, so an idle stream holds a fraction of a second of audio.
+/// See [VadGateParams] for how the tuning affects latency and accuracy.
+///
+/// Transcription runs inside [Self::push], on the caller's thread: keep the audio callback
+/// of a sound API free of it and push from a worker that drains a queue instead.
+///
+/// [Self::pause] and [Self::resume] stop and restart consuming audio, e.g. while the user
+/// mutes the microphone, without tearing down the stream.
+pub struct VadGatedStream<'a, F: FnMut(Segment)> {
+    ctx: &'a mut SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    gate: Gate,
+    pre_roll: usize,
+    threshold: f32,
+    on_segment: F,
+    /// Samples not yet making up a whole detector window.
+    pending: Vec<f64>,
+    /// Audio of the recent windows, starting at window `buffer_start`.
+    buffer: Vec<f64>,
+    buffer_start: usize,
+    /// Number of windows scored so far.
+    n_windows: usize,
+    /// When [Self::pause] was called, None while running.
+    paused_at: Option<Instant>,
+}
+
+impl<'a, F: FnMut(Segment)> VadGatedStream<'a, F> {
+    /// A stream transcribing with `ctx` and `params`, passing each segment to `on_segment`.
+    ///
+    /// Pushed samples are at full scale ±1.0 like those of a sound API, as the detector
+    /// expects them, so [SenseVoiceFullParams::normalized_input] is switched on.
+    pub fn new(
+        ctx: &'a mut SenseVoiceContext,
+        mut params: SenseVoiceFullParams,
+        gate: VadGateParams,
+        on_segment: F,
+    ) -> Self {
+        params.normalized_input = true;
+        Self {
+            ctx,
+            params,
+            gate: Gate::new(&gate),
+            pre_roll: gate.pre_roll_ms.div_ceil(VAD_WINDOW_MS),
+            threshold: gate.threshold,
+            on_segment,
+            pending: Vec::with_capacity(VAD_WINDOW_SAMPLES),
+            buffer: Vec::new(),
+            buffer_start: 0,
+            n_windows: 0,
+            paused_at: None,
+        }
+    }
+
+    /// Stop consuming audio: samples pushed until [Self::resume] are dropped.
+    ///
+    /// Everything buffered is retained: the open segment, if speech was in progress, the
+    /// pre-roll and the samples short of a whole detector window. Dropped audio isn't part of
+    /// the timeline, so segment timestamps after resuming count only the audio consumed.
+    /// Pausing a paused stream does nothing.
+    pub fn pause(&mut self) {
+        self.paused_at.get_or_insert_with(Instant::now);
+    }
+
+    /// Whether the stream is paused, see [Self::pause].
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Consume pushed audio again after [Self::pause].
+    ///
+    /// After a pause shorter than [VadGateParams::hangover_ms] the stream continues as if
+    /// it had never stopped, so muting for a moment doesn't split an utterance. After a
+    /// longer pause the buffered audio is stale: the open segment is closed and transcribed
+    /// as it would have been after that much silence, and the pre-roll and partial window
+    /// are dropped, so the next segment doesn't start with audio from before the pause.
+    /// Resuming a running stream does nothing.
+    ///
+    /// # Errors
+    /// The error of a failing transcription of the open segment, which is dropped.
+    pub fn resume(&mut self) -> Result<(), SenseVoiceError> {
+        match self.paused_at.take() {
+            Some(paused_at) => self.resume_after(paused_at.elapsed()),
+            None => Ok(()),
+        }
+    }
+
+    /// Resume after a pause of `paused`, see [Self::resume].
+    fn resume_after(&mut self, paused: Duration) -> Result<(), SenseVoiceError> {
+        self.paused_at = None;
+        let hangover = Duration::from_millis((self.gate.hangover * VAD_WINDOW_MS) as u64);
+        if paused < hangover {
+            return Ok(());
+        }
+        self.pending.clear();
+        let result = match self.gate.close(self.n_windows) {
+            Some(range) => self.transcribe(range),
+            None => Ok(()),
+        };
+        self.discard_before(self.n_windows);
+        result
+    }
+
+    /// Append mono 16 kHz `samples`, transcribing every segment they complete. Does nothing
+    /// while the stream is paused.
+    ///
+    /// # Errors
+    /// [SenseVoiceError::UnableToCalculateEvaluation] if the detector fails, or the error of
+    /// a failing transcription. Either way the rest of `samples` isn't consumed. A window the
+    /// detector failed on stays buffered and is scored again by the next push, while a
+    /// segment that failed to transcribe is dropped and the stream carries on after it.
+    pub fn push(&mut self, samples: &[f32]) -> Result<(), SenseVoiceError> {
+        if self.is_paused() {
+            return Ok(());
+        }
+        if self.pending.len() == VAD_WINDOW_SAMPLES {
+            // the detector failed on it during the last push
+            self.score()?;
+        }
+        for &sample in samples {
+            self.pending.push(f64::from(sample));
+            if self.pending.len() == VAD_WINDOW_SAMPLES {
+                self.score()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Transcribe the segment still open at the end of the stream, if any.
+    ///
+    /// # Errors
+    /// As for [Self::push].
+    pub fn finish(mut self) -> Result<(), SenseVoiceError> {
+        if !self.pending.is_empty() {
+            self.score()?;
+        }
+        match self.gate.close(self.n_windows) {
+            Some(range) => self.transcribe(range),
+            None => Ok(()),
+        }
+    }
+
+    /// Score the pending samples as one detector window, at most [VAD_WINDOW_SAMPLES] long,
+    /// and transcribe the segment it ends. The samples stay pending if the detector fails.
+    fn score(&mut self) -> Result<(), SenseVoiceError> {
+        // the detector always reads a whole window
+        let window = audio::pad_to_min(&self.pending, VAD_WINDOW_SAMPLES);
+        let prob = get_speech_prob(self.ctx, &window);
+        if prob < 0.0 {
+            return Err(SenseVoiceError::UnableToCalculateEvaluation);
+        }
+        self.buffer.extend_from_slice(&self.pending);
+        self.pending.clear();
+        let i = self.n_windows;
+        self.n_windows += 1;
+        if let Some(range) = self.gate.step(i, prob >= self.threshold) {
+            self.transcribe(range)?;
+        }
+        if self.gate.start.is_none() {
+            // outside of speech only the pre-roll is needed
+            self.discard_before(self.n_windows.saturating_sub(self.pre_roll));
+        }
+        Ok(())
+    }
+
+    /// Transcribe the windows of `range`, preceded by the pre-roll, and pass on the segment.
+    fn transcribe(&mut self, range: Range<usize>) -> Result<(), SenseVoiceError> {
+        let start = range
+            .start
+            .saturating_sub(self.pre_roll)
+            .max(self.buffer_start);
+        let from = (start - self.buffer_start) * VAD_WINDOW_SAMPLES;
+        // the last window of a finished stream can be partial
+        let to = ((range.end - self.buffer_start) * VAD_WINDOW_SAMPLES).min(self.buffer.len());
+        let segment = full_parallel_segment(
+            self.ctx,
+            self.params.clone(),
+            &self.buffer[from..to],
+            segment::samples_to_ms(start * VAD_WINDOW_SAMPLES),
+            None,
+        )?;
+        self.discard_before(range.end);
+        (self.on_segment)(segment);
+        Ok(())
+    }
+
+    /// Drop buffered audio before window `window`.
+    fn discard_before(&mut self, window: usize) {
+        if window > self.buffer_start {
+            let n = ((window - self.buffer_start) * VAD_WINDOW_SAMPLES).min(self.buffer.len());
+            self.buffer.drain(..n);
+            self.buffer_start = window;
+        }
+    }
+}
+
+/// Cut `samples` into the same windows as [windows] would for their count, calling `f` with
+/// each window's range and samples while buffering only one window plus [MIN_TAIL_MS].
+pub(crate) fn for_each_window(
+    mut samples: impl Iterator<Item = f64>,
+    window_ms: usize,
+    mut f: impl FnMut(Range<usize>, &[f64]) -> Result<(), SenseVoiceError>,
+) -> Result<(), SenseVoiceError> {
+    let rate = SENSE_VOICE_SAMPLE_RATE as usize;
+    let window = (window_ms * rate / 1000).max(1);
+    let capacity = window + MIN_TAIL_MS * rate / 1000;
+
+    let mut buffer: Vec<f64> = samples.by_ref().take(capacity).collect();
+    let mut start = 0;
+    while !buffer.is_empty() {
+        // a buffer that isn't full holds the rest of the input, short tail included
+        let len = if buffer.len() < capacity {
+            buffer.len()
+        } else {
+            window
+        };
+        f(start..start + len, &buffer[..len])?;
+        start += len;
+        buffer.drain(..len);
+        buffer.extend(samples.by_ref().take(capacity - buffer.len()));
+    }
+    Ok(())
+}
+
+/// Split `n_samples` into consecutive ranges of `window_ms`, merging a short tail into the
+/// previous window.
+pub(crate) fn windows(n_samples: usize, window_ms: usize) -> Vec<Range<usize>> {
+    let rate = SENSE_VOICE_SAMPLE_RATE as usize;
+    let window = (window_ms * rate / 1000).max(1);
+    let min_tail = MIN_TAIL_MS * rate / 1000;
+
+    let mut ranges: Vec<Range<usize>> = (0..n_samples)
+        .step_by(window)
+        .map(|start| start..(start + window).min(n_samples))
+        .collect();
+    if ranges.len() > 1 && ranges[ranges.len() - 1].len() < min_tail {
+        let tail = ranges.pop().unwrap();
+        ranges.last_mut().unwrap().end = tail.end;
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn windows_cover_input() {
+        let ranges = windows(16_000 * 65, 30_000);
+        assert_eq!(
+            ranges,
+            vec![0..480_000, 480_000..960_000, 960_000..1_040_000]
+        );
+    }
+
+    #[test]
+    fn short_tail_is_merged() {
+        let ranges = windows(16_000 * 60 + 100, 30_000);
+        assert_eq!(ranges, vec![0..480_000, 480_000..960_100]);
+    }
+
+    #[test]
+    fn channel_of_empty_input_ends() {
+        let ctx = Arc::new(Mutex::new(
+            SenseVoiceContext::from_raw(std::ptr::null_mut()),
+        ));
+        let params = crate::SenseVoiceFullParams::default_params(
+            crate::SenseVoiceDecodingStrategy::SamplingGreedy,
+        );
+        let rx = SenseVoiceContext::transcribe_to_channel(ctx, Vec::new(), params, 1);
+        assert!(rx.recv().is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn stream_of_empty_input_ends() {
+        use futures_core::Stream;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let ctx = Arc::new(Mutex::new(
+            SenseVoiceContext::from_raw(std::ptr::null_mut()),
+        ));
+        let params = crate::SenseVoiceFullParams::default_params(
+            crate::SenseVoiceDecodingStrategy::SamplingGreedy,
+        );
+        let next = runtime.block_on(async {
+            let mut stream = std::pin::pin!(transcribe_stream(ctx, params, Vec::new()));
+            std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await
+        });
+        assert!(next.is_none());
+    }
+
+    /// Run a gate over per-window speech flags, returning the segments it emits.
+    fn gate_segments(params: VadGateParams, speech: &[bool]) -> Vec<Range<usize>> {
+        let mut gate = Gate::new(&params);
+        let mut segments: Vec<Range<usize>> = speech
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &speech)| gate.step(i, speech))
+            .collect();
+        segments.extend(gate.close(speech.len()));
+        segments
+    }
+
+    #[test]
+    fn vad_gate_segments() {
+        // 80 ms hangover and speech of at least 80 ms, in 40 ms windows
+        let params = VadGateParams {
+            hangover_ms: 80,
+            min_speech_ms: 80,
+            max_speech_ms: 400,
+            ..VadGateParams::default()
+        };
+        let flags = |pattern: &str| pattern.chars().map(|c| c == 'x').collect::<Vec<_>>();
+        // a one-window pause doesn't close the segment, two windows of silence do
+        assert_eq!(gate_segments(params, &flags("..xx.x..x")), vec![2..8]);
+        // a single speech window is too short, the open segment at the end is flushed
+        assert_eq!(gate_segments(params, &flags("x...xxx")), vec![4..7]);
+        // continuous speech is cut every 400 ms
+        assert_eq!(
+            gate_segments(params, &flags(&"x".repeat(25))),
+            vec![0..10, 10..20, 20..25]
+        );
+        assert!(gate_segments(params, &flags("......")).is_empty());
+    }
+
+    #[test]
+    fn pause_drops_audio_and_long_pauses_flush_the_buffer() {
+        let mut ctx = SenseVoiceContext::from_raw(std::ptr::null_mut());
+        let params = crate::SenseVoiceFullParams::default_params(
+            crate::SenseVoiceDecodingStrategy::SamplingGreedy,
+        );
+        let mut segments = 0;
+        let mut stream = VadGatedStream::new(&mut ctx, params, VadGateParams::default(), |_| {
+            segments += 1
+        });
+        // less than a detector window, so nothing reaches the model
+        stream.push(&[0.1; 100]).unwrap();
+
+        for _ in 0..3 {
+            stream.pause();
+            assert!(stream.is_paused());
+            stream.push(&[0.1; 200]).unwrap();
+            stream.resume().unwrap();
+            assert!(!stream.is_paused());
+            // a short pause keeps what was buffered and drops what was pushed meanwhile
+            assert_eq!(stream.pending.len(), 100);
+        }
+        stream.resume().unwrap();
+        assert_eq!(stream.pending.len(), 100);
+
+        stream.pause();
+        stream.resume_after(Duration::from_secs(60)).unwrap();
+        assert!(stream.pending.is_empty());
+        assert!(stream.buffer.is_empty());
+        stream.push(&[0.1; 50]).unwrap();
+        assert_eq!(stream.pending.len(), 50);
+        assert_eq!(stream.n_windows, 0);
+        drop(stream);
+        assert_eq!(segments, 0);
+    }
+
+    #[test]
+    fn iterator_samples_are_normalized_input() {
+        let params = crate::SenseVoiceFullParams::default_params(
+            crate::SenseVoiceDecodingStrategy::SamplingGreedy,
+        );
+        assert!(!params.normalized_input);
+        let samples = std::iter::repeat_n(0.5f32, 16_000 * 40);
+        let window = Duration::from_millis(STREAM_WINDOW_MS as u64);
+        let segments = transcribe_iter_with(params, samples, window, |params, data, start_ms| {
+            // ±1.0 samples, scaled up for the front end only with the flag on
+            assert!(params.normalized_input);
+            let end_ms = start_ms + crate::segment::samples_to_ms(data.len());
+            Ok(Segment::new(start_ms, end_ms, ""))
+        })
+        .unwrap();
+        assert_eq!(segments.len(), 2);
+    }
+
+    #[test]
+    fn empty_input_has_no_windows() {
+        assert!(windows(0, 30_000).is_empty());
+    }
+
+    #[test]
+    fn iterator_windows_match_slice_windows() {
+        for n_samples in [
+            0, 100, 16_000, 480_000, 480_100, 960_000, 1_040_000, 1_455_999,
+        ] {
+            let mut ranges = Vec::new();
+            let samples = (0..n_samples).map(|i| i as f64);
+            for_each_window(samples, 30_000, |range, data| {
+                assert_eq!(data.len(), range.len());
+                assert_eq!(data.first().copied(), Some(range.start as f64));
+                ranges.push(range);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(ranges, windows(n_samples, 30_000), "{n_samples} samples");
+        }
+    }
+}
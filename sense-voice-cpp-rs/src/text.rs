@@ -0,0 +1,727 @@
+//! Post-processing helpers for transcription text.
+
+/// Unicode normalization form used by [normalize_text].
+#[cfg(feature = "unicode-normalization")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormForm {
+    /// Canonical composition. Keeps full-width/half-width and other compatibility
+    /// variants as they are, only merges combining sequences.
+    /// Recommended for displaying text to users.
+    Nfc,
+    /// Compatibility composition. Additionally folds full-width Latin letters and digits,
+    /// half-width katakana, ligatures etc. into their canonical counterparts.
+    /// Recommended for search, indexing and comparing transcriptions.
+    Nfkc,
+}
+
+/// Normalize transcription text to the given Unicode normalization form.
+///
+/// SenseVoice output for Chinese and Japanese may mix full-width and half-width
+/// characters or precomposed and combining sequences, which makes naive string
+/// comparison fail. Use [NormForm::Nfkc] before comparing or searching and
+/// [NormForm::Nfc] when the text is shown as-is.
+///
+/// Requires the `unicode-normalization` feature.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::text::{normalize_text, NormForm};
+/// assert_eq!(normalize_text("ＡＢＣ１２３", NormForm::Nfkc), "ABC123");
+/// ```
+#[cfg(feature = "unicode-normalization")]
+pub fn normalize_text(text: &str, form: NormForm) -> String {
+    use unicode_normalization::UnicodeNormalization;
+
+    match form {
+        NormForm::Nfc => text.nfc().collect(),
+        NormForm::Nfkc => text.nfkc().collect(),
+    }
+}
+
+/// Replaces every match in [redact].
+#[cfg(feature = "regex")]
+pub const REDACTION_PLACEHOLDER: &str = "[REDACTED]";
+
+/// Replace everything in `text` that matches one of `patterns` with [REDACTION_PLACEHOLDER],
+/// e.g. to filter profanity or PII from transcriptions.
+///
+/// All patterns are matched against the original text, so a later pattern never sees the
+/// placeholder of an earlier one. Overlapping or adjacent matches, also of different patterns,
+/// are replaced by a single placeholder; empty matches are ignored. Use
+/// [crate::segment::Transcription::redact] or [crate::transcriber::Transcriber::with_redaction]
+/// to redact a whole transcription.
+///
+/// This is post-processing of the final text, not part of decoding: the model still
+/// recognizes the content and it can show up in anything derived from the raw output, e.g.
+/// the tokens, [crate::full_get_text] or the n-best list. Requires the `regex` feature.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::text::redact;
+/// let phone = regex::Regex::new(r"\d{3}[- ]?\d{4}").unwrap();
+/// assert_eq!(redact("call 555-0199 now", &[phone]), "call [REDACTED] now");
+/// ```
+#[cfg(feature = "regex")]
+pub fn redact(text: &str, patterns: &[regex::Regex]) -> String {
+    let mut matches: Vec<std::ops::Range<usize>> = patterns
+        .iter()
+        .flat_map(|pattern| pattern.find_iter(text).map(|m| m.range()))
+        .filter(|range| !range.is_empty())
+        .collect();
+    matches.sort_unstable_by_key(|range| range.start);
+
+    let mut merged: Vec<std::ops::Range<usize>> = Vec::with_capacity(matches.len());
+    for range in matches {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    for range in merged {
+        out.push_str(&text[pos..range.start]);
+        out.push_str(REDACTION_PLACEHOLDER);
+        pos = range.end;
+    }
+    out.push_str(&text[pos..]);
+    out
+}
+
+/// Remove spaces between adjacent CJK characters, keeping the spaces around other words.
+///
+/// SenseVoice's tokenizer marks word starts with a space, so Chinese and Japanese output
+/// sometimes reads `我 们 去 吃 饭`. Spaces are only dropped when the characters on both sides
+/// are CJK (ideographs, kana, hangul syllables or CJK punctuation), so mixed-script text keeps
+/// its Latin words apart. Apply it to the plain text or use
+/// [crate::segment::Transcription::collapse_cjk_spaces] for a whole transcription.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::text::collapse_cjk_spaces;
+/// assert_eq!(collapse_cjk_spaces("我 们 用 Rust 写 代 码"), "我们用 Rust 写代码");
+/// ```
+pub fn collapse_cjk_spaces(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != ' ' {
+            out.push(c);
+            continue;
+        }
+        let mut spaces = 1;
+        while chars.next_if_eq(&' ').is_some() {
+            spaces += 1;
+        }
+        let between_cjk = out.chars().next_back().is_some_and(is_cjk_or_punctuation)
+            && chars
+                .peek()
+                .is_some_and(|&next| is_cjk_or_punctuation(next));
+        if !between_cjk {
+            out.extend(std::iter::repeat_n(' ', spaces));
+        }
+    }
+    out
+}
+
+/// Render segments as HTML for transcript players with click-to-seek.
+///
+/// Every segment becomes one line:
+///
+/// ```html
+/// <span class="segment" data-start="1.250" data-end="3.000">text</span>
+/// ```
+///
+/// `data-start` and `data-end` are in seconds with millisecond precision, the unit of
+/// `HTMLMediaElement.currentTime`. The text is escaped (`&`, `<`, `>`, `"` and `'`), so it
+/// is safe inside elements and attribute values.
+///
+/// `<|...|>` tags in the text, e.g. from text decoded with the prefix, become empty spans to
+/// style with CSS:
+/// * emotions: `<span class="emotion-happy"></span>`, also `sad`, `angry`, `neutral`,
+///   `fearful`, `disgusted`, `surprised` and `unknown` for `<|EMO_UNKNOWN|>`;
+/// * audio events: `<span class="event-laughter"></span>`, also `speech`, `bgm`,
+///   `applause`, `cry`, `sneeze`, `breath`, `cough` and `unknown` for `<|Event_UNK|>`;
+/// * languages: `<span class="language-en"></span>`, also for `nospeech`;
+/// * other tags: `<span class="tag-NAME"></span>` with the name lowercased and characters
+///   other than letters and digits replaced by `-`.
+///
+/// The `<|withitn|>` and `<|woitn|>` tags only tell how the text was normalized and are
+/// dropped.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::{segment::Segment, text::to_html};
+/// let segment = Segment::new(0, 1500, "<|HAPPY|>Tom & Jerry");
+/// assert_eq!(
+///     to_html(&[segment]),
+///     "<span class=\"segment\" data-start=\"0.000\" data-end=\"1.500\">\
+///      <span class=\"emotion-happy\"></span>Tom &amp; Jerry</span>\n"
+/// );
+/// ```
+pub fn to_html(segments: &[crate::segment::Segment]) -> String {
+    let mut html = String::new();
+    for segment in segments {
+        html.push_str(&format!(
+            "<span class=\"segment\" data-start=\"{}\" data-end=\"{}\">",
+            html_seconds(segment.start_ms),
+            html_seconds(segment.end_ms)
+        ));
+        let mut rest = segment.text.as_str();
+        while let Some(start) = rest.find("<|") {
+            let Some(len) = rest[start + 2..].find("|>") else {
+                break;
+            };
+            html_escape_into(&mut html, &rest[..start]);
+            if let Some(class) = tag_class(&rest[start + 2..start + 2 + len]) {
+                html.push_str(&format!("<span class=\"{class}\"></span>"));
+            }
+            rest = &rest[start + 2 + len + 2..];
+        }
+        html_escape_into(&mut html, rest);
+        html.push_str("</span>\n");
+    }
+    html
+}
+
+/// Milliseconds as seconds with three decimals, e.g. `-0.500` or `61.250`.
+fn html_seconds(ms: i64) -> String {
+    let sign = if ms < 0 { "-" } else { "" };
+    let ms = ms.unsigned_abs();
+    format!("{sign}{}.{:03}", ms / 1000, ms % 1000)
+}
+
+fn html_escape_into(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+}
+
+/// CSS class for the SenseVoice tag `name` (without `<|` and `|>`), None for tags to drop.
+fn tag_class(name: &str) -> Option<String> {
+    let class = match name {
+        "withitn" | "woitn" => return None,
+        "EMO_UNKNOWN" => "emotion-unknown".to_string(),
+        "HAPPY" | "SAD" | "ANGRY" | "NEUTRAL" | "FEARFUL" | "DISGUSTED" | "SURPRISED" => {
+            format!("emotion-{}", name.to_ascii_lowercase())
+        }
+        "Event_UNK" => "event-unknown".to_string(),
+        "Speech" | "BGM" | "Applause" | "Laughter" | "Cry" | "Sneeze" | "Breath" | "Cough" => {
+            format!("event-{}", name.to_ascii_lowercase())
+        }
+        "zh" | "en" | "yue" | "ja" | "ko" | "nospeech" => format!("language-{name}"),
+        _ => {
+            let name: String = name
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        '-'
+                    }
+                })
+                .collect();
+            format!("tag-{name}")
+        }
+    };
+    Some(class)
+}
+
+/// Merge runs of adjacent segments with the same emotion into one segment each, for
+/// emotion-annotated transcripts.
+///
+/// The emotion of a segment is the first emotion tag in its text, e.g. `<|HAPPY|>` in text
+/// decoded with the prefix (see [to_html] for the tags); `<|EMO_UNKNOWN|>` is a label like any
+/// other. Neighbours merge only if both carry the same tag. Segments without an emotion tag
+/// are never merged and stay their own group.
+///
+/// A merged segment spans from the start of its first to the end of its last segment. Its
+/// text is the text of the first segment followed by the texts of the others without their
+/// emotion tag, separated by a space unless both sides are CJK, see [collapse_cjk_spaces].
+/// Other tags, e.g. audio events, are kept. [crate::segment::Segment::no_speech_prob] and
+/// [crate::segment::Segment::avg_logprob] are averaged weighted by duration.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::{segment::Segment, text::merge_by_emotion};
+/// let segment = |start_ms, end_ms, text| Segment::new(start_ms, end_ms, text);
+/// let merged = merge_by_emotion(&[
+///     segment(0, 1000, "<|HAPPY|>Great news."),
+///     segment(1000, 2000, "<|HAPPY|>We won!"),
+///     segment(2000, 3000, "<|SAD|>But it's over."),
+/// ]);
+/// assert_eq!(merged.len(), 2);
+/// assert_eq!(merged[0].text, "<|HAPPY|>Great news. We won!");
+/// assert_eq!((merged[0].start_ms, merged[0].end_ms), (0, 2000));
+/// ```
+pub fn merge_by_emotion(segments: &[crate::segment::Segment]) -> Vec<crate::segment::Segment> {
+    let mut merged: Vec<crate::segment::Segment> = Vec::with_capacity(segments.len());
+    // emotion of the last merged segment, None if it has none
+    let mut last_emotion = None;
+    for segment in segments {
+        let Some((tag, name)) = emotion_tag(&segment.text) else {
+            last_emotion = None;
+            merged.push(segment.clone());
+            continue;
+        };
+        // last_emotion is only Some after a segment was pushed
+        let Some(last) = merged.last_mut().filter(|_| last_emotion == Some(name)) else {
+            last_emotion = Some(name);
+            merged.push(segment.clone());
+            continue;
+        };
+
+        let text = format!("{}{}", &segment.text[..tag.start], &segment.text[tag.end..]);
+        let between_cjk = last
+            .text
+            .chars()
+            .next_back()
+            .is_some_and(is_cjk_or_punctuation)
+            && text.chars().next().is_some_and(is_cjk_or_punctuation);
+        if !(between_cjk || text.is_empty() || last.text.ends_with(' ') || text.starts_with(' ')) {
+            last.text.push(' ');
+        }
+        last.text.push_str(&text);
+
+        let last_ms = (last.end_ms - last.start_ms).max(0) as f32;
+        let segment_ms = (segment.end_ms - segment.start_ms).max(0) as f32;
+        let mean = |a: f32, b: f32| {
+            if last_ms + segment_ms > 0.0 {
+                (a * last_ms + b * segment_ms) / (last_ms + segment_ms)
+            } else {
+                (a + b) / 2.0
+            }
+        };
+        last.no_speech_prob = mean(last.no_speech_prob, segment.no_speech_prob);
+        last.avg_logprob = mean(last.avg_logprob, segment.avg_logprob);
+        last.start_ms = last.start_ms.min(segment.start_ms);
+        last.end_ms = last.end_ms.max(segment.end_ms);
+    }
+    merged
+}
+
+/// Byte range and name of the first emotion tag in `text`, see [merge_by_emotion].
+fn emotion_tag(text: &str) -> Option<(std::ops::Range<usize>, &str)> {
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find("<|") {
+        let start = offset + start;
+        let len = text[start + 2..].find("|>")?;
+        let name = &text[start + 2..start + 2 + len];
+        let end = start + 2 + len + 2;
+        if tag_class(name).is_some_and(|class| class.starts_with("emotion-")) {
+            return Some((start..end, name));
+        }
+        offset = end;
+    }
+    None
+}
+
+/// A phrase repeated this many times in a row counts as a repetition loop, see
+/// [detect_repetition].
+pub const REPETITION_THRESHOLD: usize = 4;
+
+/// Longest phrase [detect_repetition] looks for, in words or CJK characters.
+pub const MAX_REPEATED_PHRASE: usize = 8;
+
+/// A phrase found repeated back to back, see [detect_repetition].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepetitionInfo {
+    /// The first occurrence of the phrase, as it appears in the text.
+    pub phrase: String,
+    /// How many times the phrase occurs in a row.
+    pub count: usize,
+}
+
+/// Find a repetition loop, the model getting stuck on one phrase like
+/// "thank you thank you thank you thank you ...", with [REPETITION_THRESHOLD] repeats.
+///
+/// # Heuristic
+/// The text is split into words on whitespace, and CJK text additionally into single
+/// characters, as it has no spaces between words. Words are compared case insensitively and
+/// without punctuation, so "Thank you, thank you." repeats "thank you". A loop is an n-gram of
+/// 1 to [MAX_REPEATED_PHRASE] units that occurs at least `min_count` times back to back; of
+/// all loops the one covering the most units is reported, the shortest phrase on ties, so
+/// "a b a b a b a b" is "a b" four times rather than "a b a b" twice.
+///
+/// Natural speech rarely repeats a phrase four times in a row, but single units do: "no no
+/// no no" or laughter transcribed as "哈哈哈哈" are reported as well, raise the threshold with
+/// [detect_repetition_with] where that matters.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::text::detect_repetition;
+/// let info = detect_repetition("Thanks for watching. Thank you, thank you, thank you, thank you.")
+///     .unwrap();
+/// assert_eq!((info.phrase.as_str(), info.count), ("Thank you,", 4));
+/// assert!(detect_repetition("thank you, thank you").is_none());
+/// ```
+pub fn detect_repetition(text: &str) -> Option<RepetitionInfo> {
+    detect_repetition_with(text, REPETITION_THRESHOLD)
+}
+
+/// [detect_repetition] with a phrase counting as a loop once it occurs `min_count` times in a
+/// row, at least 2.
+pub fn detect_repetition_with(text: &str, min_count: usize) -> Option<RepetitionInfo> {
+    let units = repetition_units(text);
+    let min_count = min_count.max(2);
+    let (start, n, count) = (0..units.len())
+        .filter_map(|i| {
+            let (n, count) = repetition_at(&units, i, min_count)?;
+            Some((i, n, count))
+        })
+        // first the most covered units, then the shortest phrase, then the earliest
+        .max_by_key(|&(i, n, count)| (n * count, std::cmp::Reverse(n), std::cmp::Reverse(i)))?;
+    let mut phrase = String::new();
+    for (k, unit) in units[start..start + n].iter().enumerate() {
+        if k > 0 && !(unit.cjk && units[start + k - 1].cjk) {
+            phrase.push(' ');
+        }
+        phrase.push_str(unit.text);
+    }
+    Some(RepetitionInfo { phrase, count })
+}
+
+/// Replace every repetition loop found by [detect_repetition] with a single occurrence of its
+/// phrase, the last one so the punctuation ending the loop stays.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::text::collapse_repetitions;
+/// assert_eq!(
+///     collapse_repetitions("Bye. Thank you, thank you, thank you, thank you. See you."),
+///     "Bye. thank you. See you."
+/// );
+/// assert_eq!(collapse_repetitions("我们走吧走吧走吧走吧。"), "我们走吧。");
+/// ```
+pub fn collapse_repetitions(text: &str) -> String {
+    collapse_repetitions_with(text, REPETITION_THRESHOLD)
+}
+
+/// [collapse_repetitions] with the threshold of [detect_repetition_with].
+pub fn collapse_repetitions_with(text: &str, min_count: usize) -> String {
+    let units = repetition_units(text);
+    let min_count = min_count.max(2);
+    let mut out = String::with_capacity(text.len());
+    let mut copied_to = 0;
+    let mut i = 0;
+    while i < units.len() {
+        let Some((n, count)) = repetition_at(&units, i, min_count) else {
+            i += 1;
+            continue;
+        };
+        let last = i + (count - 1) * n;
+        out.push_str(&text[copied_to..units[i].start]);
+        copied_to = units[last].start;
+        i += count * n;
+    }
+    out.push_str(&text[copied_to..]);
+    out
+}
+
+/// A word or CJK character of the text [detect_repetition] compares.
+struct RepetitionUnit<'a> {
+    text: &'a str,
+    /// Byte offset of `text` in the whole text.
+    start: usize,
+    /// `text` lowercased, without punctuation.
+    key: String,
+    cjk: bool,
+}
+
+fn repetition_units(text: &str) -> Vec<RepetitionUnit<'_>> {
+    let mut units = Vec::new();
+    let mut push = |start: usize, end: usize, cjk: bool| {
+        let unit = &text[start..end];
+        let key: String = unit
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect();
+        if !key.is_empty() {
+            units.push(RepetitionUnit {
+                text: unit,
+                start,
+                key,
+                cjk,
+            });
+        }
+    };
+    for word in text.split_whitespace() {
+        let offset = word.as_ptr() as usize - text.as_ptr() as usize;
+        let mut run_start = None;
+        for (i, c) in word.char_indices() {
+            if !is_cjk(c) {
+                run_start.get_or_insert(i);
+                continue;
+            }
+            if let Some(start) = run_start.take() {
+                push(offset + start, offset + i, false);
+            }
+            push(offset + i, offset + i + c.len_utf8(), true);
+        }
+        if let Some(start) = run_start {
+            push(offset + start, offset + word.len(), false);
+        }
+    }
+    units
+}
+
+/// The phrase length and count of the repetition loop starting at `units[i]`, the one
+/// covering the most units if there are several.
+fn repetition_at(units: &[RepetitionUnit], i: usize, min_count: usize) -> Option<(usize, usize)> {
+    let mut best: Option<(usize, usize)> = None;
+    for n in 1..=MAX_REPEATED_PHRASE {
+        if i + n * min_count > units.len() {
+            break;
+        }
+        let repeats = |c: usize| (0..n).all(|k| units[i + c * n + k].key == units[i + k].key);
+        let mut count = 1;
+        while i + (count + 1) * n <= units.len() && repeats(count) {
+            count += 1;
+        }
+        if count >= min_count
+            && best.is_none_or(|(best_n, best_count)| n * count > best_n * best_count)
+        {
+            best = Some((n, count));
+        }
+    }
+    best
+}
+
+/// Whether `c` is a CJK ideograph, kana or hangul syllable.
+pub(crate) fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}' // hiragana, katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+        | '\u{AC00}'..='\u{D7AF}' // hangul syllables
+        | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+    )
+}
+
+/// Whether `c` is CJK or full-width punctuation such as `。` or `！`.
+pub(crate) fn is_cjk_punctuation(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{303F}' | '\u{FF01}'..='\u{FF0F}' | '\u{FF1A}'..='\u{FF20}')
+}
+
+fn is_cjk_or_punctuation(c: char) -> bool {
+    is_cjk(c) || is_cjk_punctuation(c)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detects_repetition_loops() {
+        assert_eq!(
+            detect_repetition("a b a b a b a b"),
+            Some(RepetitionInfo {
+                phrase: "a b".to_string(),
+                count: 4
+            })
+        );
+        // the loop covering the most words wins over an earlier, shorter one
+        let info =
+            detect_repetition("ok ok ok ok so I said hi there hi there hi there hi there hi there")
+                .unwrap();
+        assert_eq!((info.phrase.as_str(), info.count), ("hi there", 5));
+        let info = detect_repetition("好的好的好的好的").unwrap();
+        assert_eq!((info.phrase.as_str(), info.count), ("好的", 4));
+        assert!(detect_repetition("no no no, I said no").is_none());
+        assert!(detect_repetition("").is_none());
+        // a configurable threshold
+        assert_eq!(
+            detect_repetition_with("no no no, I said", 3).unwrap().count,
+            3
+        );
+        assert!(detect_repetition_with("ok ok ok ok", 5).is_none());
+        // phrases longer than the limit aren't looked for
+        let long = "one two three four five six seven eight nine ".repeat(4);
+        assert!(detect_repetition(&long).is_none());
+    }
+
+    #[test]
+    fn collapses_repetition_loops() {
+        assert_eq!(collapse_repetitions("go go go go now"), "go now");
+        assert_eq!(
+            collapse_repetitions("哈哈哈哈 a b c a b c a b c a b c!"),
+            "哈 a b c!"
+        );
+        let text = "nothing repeats here, nothing at all";
+        assert_eq!(collapse_repetitions(text), text);
+        assert_eq!(collapse_repetitions_with("yes yes yes", 3), "yes");
+    }
+
+    #[test]
+    fn collapses_spaces_between_cjk() {
+        assert_eq!(collapse_cjk_spaces("我 们 去 吃 饭 。"), "我们去吃饭。");
+        assert_eq!(collapse_cjk_spaces("こ ん に ち は"), "こんにちは");
+    }
+
+    #[test]
+    fn keeps_spaces_around_latin_words() {
+        assert_eq!(
+            collapse_cjk_spaces("我 在 用 GitHub Copilot 写 代 码"),
+            "我在用 GitHub Copilot 写代码"
+        );
+        assert_eq!(
+            collapse_cjk_spaces("今 天 的 meeting  很 长"),
+            "今天的 meeting  很长"
+        );
+        assert_eq!(collapse_cjk_spaces("hello world"), "hello world");
+    }
+
+    #[test]
+    fn keeps_leading_and_trailing_spaces() {
+        assert_eq!(collapse_cjk_spaces(" 你 好 "), " 你好 ");
+    }
+
+    #[test]
+    fn html_escapes_text_and_classes_tags() {
+        use crate::segment::Segment;
+
+        let segment = |start_ms, end_ms, text: &str| Segment {
+            start_ms,
+            end_ms,
+            text: text.to_string(),
+            no_speech_prob: 0.0,
+            avg_logprob: 0.0,
+        };
+        let html = to_html(&[
+            segment(
+                0,
+                1250,
+                "<|en|><|NEUTRAL|><|Laughter|><|withitn|>a <b>\"quote\"</b>",
+            ),
+            segment(
+                61_250,
+                62_000,
+                "<|EMO_UNKNOWN|><|Event_UNK|><|My Tag|>it's <|unclosed",
+            ),
+        ]);
+        assert_eq!(
+            html,
+            "<span class=\"segment\" data-start=\"0.000\" data-end=\"1.250\">\
+             <span class=\"language-en\"></span><span class=\"emotion-neutral\"></span>\
+             <span class=\"event-laughter\"></span>\
+             a &lt;b&gt;&quot;quote&quot;&lt;/b&gt;</span>\n\
+             <span class=\"segment\" data-start=\"61.250\" data-end=\"62.000\">\
+             <span class=\"emotion-unknown\"></span><span class=\"event-unknown\"></span>\
+             <span class=\"tag-my-tag\"></span>it&#39;s &lt;|unclosed</span>\n"
+        );
+        assert_eq!(to_html(&[]), "");
+    }
+
+    fn segment(start_ms: i64, end_ms: i64, text: &str) -> crate::segment::Segment {
+        crate::segment::Segment {
+            start_ms,
+            end_ms,
+            text: text.to_string(),
+            no_speech_prob: 0.0,
+            avg_logprob: 0.0,
+        }
+    }
+
+    #[test]
+    fn merges_runs_of_the_same_emotion() {
+        let mut first = segment(0, 1000, "<|en|><|ANGRY|><|Speech|>No.");
+        first.avg_logprob = -0.1;
+        let mut second = segment(1000, 4000, "<|en|><|ANGRY|><|Laughter|>Not again.");
+        second.avg_logprob = -0.5;
+        let merged = merge_by_emotion(&[
+            first,
+            second,
+            segment(4000, 5000, "<|EMO_UNKNOWN|>Hm."),
+            segment(5000, 6000, "<|EMO_UNKNOWN|>Ok."),
+            segment(6000, 7000, "<|ANGRY|>Stop."),
+        ]);
+        let texts: Vec<_> = merged.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "<|en|><|ANGRY|><|Speech|>No. <|en|><|Laughter|>Not again.",
+                "<|EMO_UNKNOWN|>Hm. Ok.",
+                "<|ANGRY|>Stop.",
+            ]
+        );
+        assert_eq!((merged[0].start_ms, merged[0].end_ms), (0, 4000));
+        assert!((merged[0].avg_logprob - -0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn segments_without_emotion_stay_apart() {
+        let merged = merge_by_emotion(&[
+            segment(0, 1000, "one"),
+            segment(1000, 2000, "two"),
+            segment(2000, 3000, "<|NEUTRAL|>你好"),
+            segment(3000, 4000, "<|NEUTRAL|>世界"),
+            segment(4000, 5000, "three"),
+        ]);
+        let texts: Vec<_> = merged.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, ["one", "two", "<|NEUTRAL|>你好世界", "three"]);
+        assert!(merge_by_emotion(&[]).is_empty());
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "regex")]
+mod test_redact {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn merges_overlapping_matches() {
+        let patterns = [
+            Regex::new(r"(?i)darn").unwrap(),
+            Regex::new(r"\d{4}").unwrap(),
+            Regex::new(r"\d+-\d+").unwrap(),
+        ];
+        assert_eq!(
+            redact("Darn, the code is 1234-5678, darn it", &patterns),
+            "[REDACTED], the code is [REDACTED], [REDACTED] it"
+        );
+        assert_eq!(redact("nothing here", &patterns), "nothing here");
+        assert_eq!(redact("darn", &[]), "darn");
+    }
+
+    #[test]
+    fn ignores_empty_matches() {
+        let patterns = [Regex::new(r"x*").unwrap()];
+        assert_eq!(redact("abxxc", &patterns), "ab[REDACTED]c");
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "unicode-normalization")]
+mod test_normalize {
+    use super::*;
+
+    #[test]
+    fn nfc_composes_combining_marks() {
+        // "が" as "か" + combining dakuten
+        assert_eq!(
+            normalize_text("\u{304B}\u{3099}", NormForm::Nfc),
+            "\u{304C}"
+        );
+    }
+
+    #[test]
+    fn nfc_keeps_full_width() {
+        assert_eq!(normalize_text("ＡＢＣ", NormForm::Nfc), "ＡＢＣ");
+    }
+
+    #[test]
+    fn nfkc_folds_width_variants() {
+        assert_eq!(normalize_text("ＡＢＣ１２３", NormForm::Nfkc), "ABC123");
+        assert_eq!(normalize_text("ｶﾞ", NormForm::Nfkc), "ガ");
+    }
+}
@@ -0,0 +1,102 @@
+//! A one-stop entry point that takes audio in whatever shape it comes in.
+
+use crate::{
+    SenseVoiceContext, SenseVoiceFullParams, audio::AudioInput, error::SenseVoiceError,
+    full_get_transcription, full_parallel, segment::Transcription,
+};
+
+/// A context bundled with the params to transcribe with.
+///
+/// [Transcriber::transcribe_any] accepts 16 kHz `f32` samples, PCM or `f32` samples at any
+/// rate, or a WAV file path, and converts them before transcribing, see [AudioInput] for the
+/// conversions applied.
+///
+/// # Examples
+/// ```no_run
+/// # use sense_voice_cpp_rs::{SenseVoiceContext, SenseVoiceContextParameters, SenseVoiceFullParams, SenseVoiceDecodingStrategy};
+/// # use sense_voice_cpp_rs::transcriber::Transcriber;
+/// let ctx = SenseVoiceContext::new_with_params("model.gguf", SenseVoiceContextParameters::default())?;
+/// let params = SenseVoiceFullParams::default_params(SenseVoiceDecodingStrategy::SamplingGreedy);
+/// let mut transcriber = Transcriber::new(ctx, params);
+///
+/// let pcm: Vec<i16> = vec![0; 44_100];
+/// let from_pcm = transcriber.transcribe_any((pcm.as_slice(), 44_100))?;
+/// let from_file = transcriber.transcribe_any(std::path::Path::new("speech.wav"))?;
+/// # Ok::<(), sense_voice_cpp_rs::error::SenseVoiceError>(())
+/// ```
+pub struct Transcriber {
+    ctx: SenseVoiceContext,
+    params: SenseVoiceFullParams,
+    #[cfg(feature = "regex")]
+    redact_patterns: Vec<regex::Regex>,
+}
+
+impl Transcriber {
+    pub fn new(ctx: SenseVoiceContext, params: SenseVoiceFullParams) -> Self {
+        Self {
+            ctx,
+            params,
+            #[cfg(feature = "regex")]
+            redact_patterns: Vec::new(),
+        }
+    }
+
+    /// Redact every transcription with `patterns`, see [Transcription::redact].
+    ///
+    /// Runs on the final text after decoding; it can't keep the model from recognizing the
+    /// redacted content, only from returning it. An empty list turns redaction off. Requires
+    /// the `regex` feature.
+    #[cfg(feature = "regex")]
+    pub fn with_redaction(mut self, patterns: Vec<regex::Regex>) -> Self {
+        self.redact_patterns = patterns;
+        self
+    }
+
+    /// The params used for every transcription.
+    pub fn params_mut(&mut self) -> &mut SenseVoiceFullParams {
+        &mut self.params
+    }
+
+    /// The underlying context, e.g. to query [SenseVoiceContext::model_info].
+    pub fn context_mut(&mut self) -> &mut SenseVoiceContext {
+        &mut self.ctx
+    }
+
+    /// Take the context and params apart again.
+    pub fn into_inner(self) -> (SenseVoiceContext, SenseVoiceFullParams) {
+        (self.ctx, self.params)
+    }
+
+    /// Convert `input` to mono 16 kHz samples and transcribe it.
+    ///
+    /// The converted samples are at full scale ±1.0, so the params are used with
+    /// [SenseVoiceFullParams::normalized_input] switched on.
+    ///
+    /// # Arguments
+    /// * input: Anything that converts into an [AudioInput]: `&[f32]` at 16 kHz,
+    ///   `(&[i16], rate)`, `(&[f32], rate)`, `&Path` or `PathBuf`.
+    ///
+    /// # Returns
+    /// The [Transcription], redacted if `with_redaction` set patterns, or the first
+    /// conversion or transcription error.
+    pub fn transcribe_any<'a>(
+        &mut self,
+        input: impl Into<AudioInput<'a>>,
+    ) -> Result<Transcription, SenseVoiceError> {
+        let samples: Vec<f64> = input
+            .into()
+            .to_mono_16k()?
+            .into_iter()
+            .map(f64::from)
+            .collect();
+        let mut params = self.params.clone();
+        params.normalized_input = true;
+        full_parallel(&mut self.ctx, params, &samples)?;
+        let transcription = full_get_transcription(&mut self.ctx)?;
+        #[cfg(feature = "regex")]
+        if !self.redact_patterns.is_empty() {
+            return Ok(transcription.redact(&self.redact_patterns));
+        }
+        Ok(transcription)
+    }
+}
@@ -0,0 +1,502 @@
+//! Word- and character-level timestamps and sentence re-segmentation.
+//!
+//! SenseVoice decodes with CTC, so every token comes with the span of encoder frames that
+//! emitted it (60 ms per frame for SenseVoiceSmall). [words_from_tokens] turns those tokens into
+//! [Word]s, and [segment_by_sentence] regroups words into sentence-sized [Segment]s, which suit
+//! subtitles better than the fixed windows the model was run on.
+//!
+//! Languages written without spaces, such as Chinese and Japanese, have no words to time, so
+//! [chars_from_tokens] times every character instead, e.g. for karaoke-style highlighting.
+//!
+//! # Character timing heuristic
+//! A token's span is split evenly between its characters: a token of `n` characters from
+//! `start_ms` to `end_ms` gives its `k`-th character the `k`-th of `n` equal slices. The CJK
+//! characters [words_from_tokens] splits off a token get their slices the same way. CTC emits a
+//! token at the frames where the model is most confident, not over its whole pronunciation, so
+//! the slices are only as accurate as the token span itself, one encoder frame at best.
+//!
+//! A character split over several byte-fallback tokens (`<0xE4>`, `<0xBD>`, `<0xA0>` for `你`)
+//! spans from the start of its first byte token to the end of its last one.
+
+use crate::{
+    segment::{Segment, Token},
+    text::{is_cjk, is_cjk_punctuation},
+};
+
+/// Sentence-ending punctuation used by [segment_by_sentence]: `. ? !` and their full-width
+/// forms `。 ？ ！`.
+pub const DEFAULT_SENTENCE_PUNCTUATION: &[char] = &['.', '?', '!', '。', '？', '！'];
+
+/// A silence of at least this many milliseconds between two words ends a sentence in
+/// [segment_by_sentence].
+///
+/// This is what splits output without punctuation, e.g. with `use_itn` disabled or for languages
+/// that don't mark sentence ends.
+pub const DEFAULT_SENTENCE_PAUSE_MS: i64 = 700;
+
+/// Marks the start of a new word in SentencePiece tokens.
+const WORD_START: char = '▁';
+
+/// A word and the span of input audio it was recognized in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+    pub text: String,
+    /// Start of the word, in milliseconds from the start of the input audio.
+    pub start_ms: i64,
+    /// End of the word, in milliseconds from the start of the input audio.
+    pub end_ms: i64,
+}
+
+/// A character and the span of input audio it was recognized in, see [chars_from_tokens].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharTiming {
+    pub ch: char,
+    /// Start of the character, in milliseconds from the start of the input audio.
+    pub start_ms: i64,
+    /// End of the character, in milliseconds from the start of the input audio.
+    pub end_ms: i64,
+}
+
+/// Group decoded tokens into words.
+///
+/// The `<|...|>` tags SenseVoice emits are skipped. A token starting with `▁` begins a new word,
+/// every CJK character is a word of its own and punctuation is attached to the word before it.
+/// A token holding several CJK characters is split into them with the timing heuristic of
+/// [chars_from_tokens], see the [module docs](self).
+///
+/// # Arguments
+/// * tokens: The tokens of a transcription, see [crate::segment::Transcription::tokens].
+///
+/// # Returns
+/// The words, in order.
+pub fn words_from_tokens(tokens: &[Token]) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    for token in tokens {
+        if is_tag(&token.text) {
+            continue;
+        }
+        let n = n_visible(&token.text).max(1) as i64;
+        let span = token.end_ms - token.start_ms;
+        // visible characters of the token before the current piece
+        let mut k = 0;
+        for piece in split_cjk(&token.text) {
+            let starts_word = piece.starts_with(WORD_START);
+            let text = piece.trim_start_matches(WORD_START);
+            let start_ms = token.start_ms + span * k / n;
+            k += n_visible(piece) as i64;
+            let end_ms = token.start_ms + span * k / n;
+            if text.is_empty() {
+                continue;
+            }
+            let punctuation = text.chars().all(is_punctuation);
+            let cjk = text.chars().any(is_cjk);
+            let append = match words.last() {
+                None => false,
+                Some(_) if punctuation => true,
+                Some(last) => !starts_word && !cjk && !last.text.chars().any(is_cjk),
+            };
+            if append {
+                let last = words.last_mut().expect("checked above");
+                last.text.push_str(text);
+                last.end_ms = last.end_ms.max(end_ms);
+            } else {
+                words.push(Word {
+                    text: text.to_string(),
+                    start_ms,
+                    end_ms,
+                });
+            }
+        }
+    }
+    words
+}
+
+/// Time every character of the decoded tokens, see the [module docs](self) for the heuristic.
+///
+/// The `<|...|>` tags are skipped, and so are the `▁` word markers and other whitespace, so the
+/// result lists the visible characters only.
+///
+/// # Arguments
+/// * tokens: The tokens of a transcription, see [crate::segment::Transcription::tokens].
+///
+/// # Returns
+/// The characters, in order.
+pub fn chars_from_tokens(tokens: &[Token]) -> Vec<CharTiming> {
+    let mut chars = Vec::new();
+    // bytes of a character split over byte-fallback tokens, and the start of the first one
+    let mut pending: Vec<u8> = Vec::new();
+    let mut pending_start_ms = 0;
+    for token in tokens {
+        if let Some(byte) = fallback_byte(&token.text) {
+            if pending.is_empty() {
+                pending_start_ms = token.start_ms;
+            }
+            pending.push(byte);
+            match std::str::from_utf8(&pending) {
+                Ok(text) => {
+                    chars.extend(text.chars().map(|ch| CharTiming {
+                        ch,
+                        start_ms: pending_start_ms,
+                        end_ms: token.end_ms,
+                    }));
+                    pending.clear();
+                }
+                // not valid UTF-8 whatever follows, drop the bytes
+                Err(e) if e.error_len().is_some() => pending.clear(),
+                // incomplete, wait for the next byte
+                Err(_) => {}
+            }
+            continue;
+        }
+        pending.clear();
+        if is_tag(&token.text) {
+            continue;
+        }
+
+        let visible: Vec<char> = token.text.chars().filter(|&c| is_visible(c)).collect();
+        let n = visible.len() as i64;
+        let span = token.end_ms - token.start_ms;
+        chars.extend(visible.into_iter().zip(0..).map(|(ch, k)| CharTiming {
+            ch,
+            start_ms: token.start_ms + span * k / n,
+            end_ms: token.start_ms + span * (k + 1) / n,
+        }));
+    }
+    chars
+}
+
+/// Regroup words into one [Segment] per sentence, see [segment_by_sentence_with].
+///
+/// Uses [DEFAULT_SENTENCE_PUNCTUATION] and [DEFAULT_SENTENCE_PAUSE_MS].
+pub fn segment_by_sentence(words: &[Word]) -> Vec<Segment> {
+    segment_by_sentence_with(
+        words,
+        DEFAULT_SENTENCE_PUNCTUATION,
+        DEFAULT_SENTENCE_PAUSE_MS,
+    )
+}
+
+/// Regroup words into one [Segment] per sentence.
+///
+/// A sentence ends after a word ending in one of `punctuation`, or before a word that starts at
+/// least `max_pause_ms` after the previous one ended. Output without punctuation is therefore
+/// still split at pauses. CJK words are joined without spaces, everything else with a space.
+///
+/// # Arguments
+/// * words: The words to group, see [words_from_tokens].
+/// * punctuation: The characters that end a sentence.
+/// * max_pause_ms: The silence that ends a sentence. Use [i64::MAX] to only split on punctuation.
+///
+/// # Returns
+/// The sentences as segments. Their `no_speech_prob` is 0.0 and their `avg_logprob` NaN,
+/// unknown, as both are only known per decoded window.
+pub fn segment_by_sentence_with(
+    words: &[Word],
+    punctuation: &[char],
+    max_pause_ms: i64,
+) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut current: Option<Segment> = None;
+    for word in words {
+        if let Some(segment) = current.take_if(|s| word.start_ms - s.end_ms >= max_pause_ms) {
+            segments.push(segment);
+        }
+        let segment = current.get_or_insert_with(|| Segment::new(word.start_ms, word.end_ms, ""));
+        if needs_space(&segment.text, &word.text) {
+            segment.text.push(' ');
+        }
+        segment.text.push_str(&word.text);
+        segment.end_ms = segment.end_ms.max(word.end_ms);
+        if word.text.ends_with(punctuation) {
+            segments.extend(current.take());
+        }
+    }
+    segments.extend(current);
+    segments
+}
+
+/// Whether `next` should be separated from `text` by a space.
+fn needs_space(text: &str, next: &str) -> bool {
+    match (text.chars().next_back(), next.chars().next()) {
+        (Some(prev), Some(next)) => !(is_cjk(prev) || is_cjk_punctuation(prev) || is_cjk(next)),
+        _ => false,
+    }
+}
+
+/// Channel written by [to_ctm], Kaldi's convention for single-channel audio.
+pub const CTM_CHANNEL: &str = "1";
+
+/// Format words as CTM (time-marked conversation) lines, the input of Kaldi's and NIST sclite's
+/// scoring tools.
+///
+/// Every word becomes one line `<file> <channel> <start> <duration> <word>`:
+/// * file: `file_id`, which must not contain whitespace;
+/// * channel: always [CTM_CHANNEL], the input is transcribed as one mono channel;
+/// * start and duration: in seconds with 2 decimals, e.g. `1.25`;
+/// * word: the word's text.
+///
+/// The optional sixth column, the word's confidence, is left out: the decoder gives no
+/// confidence per word, and a made-up value would skew confidence-based scoring.
+///
+/// # Arguments
+/// * words: The words to write, see [words_from_tokens].
+/// * file_id: The recording's id in the reference, usually the audio file name without
+///   extension.
+///
+/// # Returns
+/// One line per word, each ending in `\n`; an empty string without words.
+///
+/// # Examples
+/// ```
+/// # use sense_voice_cpp_rs::words::{to_ctm, Word};
+/// let words = [Word { text: "hello".to_string(), start_ms: 250, end_ms: 730 }];
+/// assert_eq!(to_ctm(&words, "utt1"), "utt1 1 0.25 0.48 hello\n");
+/// ```
+pub fn to_ctm(words: &[Word], file_id: &str) -> String {
+    use std::fmt::Write;
+
+    let mut ctm = String::new();
+    for word in words {
+        let _ = writeln!(
+            ctm,
+            "{} {} {:.2} {:.2} {}",
+            file_id,
+            CTM_CHANNEL,
+            word.start_ms as f64 / 1000.0,
+            (word.end_ms - word.start_ms) as f64 / 1000.0,
+            word.text
+        );
+    }
+    ctm
+}
+
+/// Split a token so that every CJK character is a piece of its own.
+fn split_cjk(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if is_cjk(c) {
+            // keep a leading word marker with the character it belongs to
+            if i > start && &text[start..i] != "▁" {
+                pieces.push(&text[start..i]);
+                start = i;
+            }
+            let end = i + c.len_utf8();
+            pieces.push(&text[start..end]);
+            start = end;
+        }
+    }
+    if start < text.len() {
+        pieces.push(&text[start..]);
+    }
+    pieces
+}
+
+/// Whether `c` is shown in the text, i.e. neither a `▁` word marker nor whitespace.
+fn is_visible(c: char) -> bool {
+    c != WORD_START && !c.is_whitespace()
+}
+
+/// Number of characters of `text` that are [is_visible].
+fn n_visible(text: &str) -> usize {
+    text.chars().filter(|&c| is_visible(c)).count()
+}
+
+/// Whether the token is one of the `<|...|>` tags SenseVoice emits.
+pub(crate) fn is_tag(text: &str) -> bool {
+    text.starts_with("<|") && text.ends_with("|>")
+}
+
+/// The byte of a SentencePiece byte-fallback token such as `<0xE4>`.
+fn fallback_byte(text: &str) -> Option<u8> {
+    let hex = text.strip_prefix("<0x")?.strip_suffix('>')?;
+    if hex.len() != 2 {
+        return None;
+    }
+    u8::from_str_radix(hex, 16).ok()
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || is_cjk_punctuation(c)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn token(text: &str, start_ms: i64, end_ms: i64) -> Token {
+        Token {
+            id: 0,
+            text: text.to_string(),
+            start_ms,
+            end_ms,
+        }
+    }
+
+    fn word(text: &str, start_ms: i64, end_ms: i64) -> Word {
+        Word {
+            text: text.to_string(),
+            start_ms,
+            end_ms,
+        }
+    }
+
+    #[test]
+    fn words_from_pieces() {
+        let tokens = [
+            token("<|en|>", 0, 0),
+            token("<|NEUTRAL|>", 0, 0),
+            token("▁hel", 0, 60),
+            token("lo", 60, 120),
+            token(",", 120, 180),
+            token("▁world", 300, 420),
+            token(".", 420, 480),
+        ];
+        assert_eq!(
+            words_from_tokens(&tokens),
+            vec![word("hello,", 0, 180), word("world.", 300, 480)]
+        );
+    }
+
+    #[test]
+    fn ctm_lines() {
+        let words = [
+            word("hello,", 0, 180),
+            word("world.", 1_300, 2_005),
+            word("你", 2_400, 2_460),
+        ];
+        assert_eq!(
+            to_ctm(&words, "rec-01"),
+            "rec-01 1 0.00 0.18 hello,\n\
+             rec-01 1 1.30 0.70 world.\n\
+             rec-01 1 2.40 0.06 你\n"
+        );
+        assert_eq!(to_ctm(&[], "rec-01"), "");
+    }
+
+    #[test]
+    fn words_from_cjk() {
+        let tokens = [
+            token("<|zh|>", 0, 0),
+            token("▁你好", 0, 120),
+            token("。", 120, 180),
+        ];
+        assert_eq!(
+            words_from_tokens(&tokens),
+            vec![word("你", 0, 60), word("好。", 60, 180)]
+        );
+    }
+
+    fn char_timing(ch: char, start_ms: i64, end_ms: i64) -> CharTiming {
+        CharTiming {
+            ch,
+            start_ms,
+            end_ms,
+        }
+    }
+
+    #[test]
+    fn chars_split_tokens_evenly() {
+        let tokens = [
+            token("<|ja|>", 0, 0),
+            token("▁你好", 0, 120),
+            token("吗", 120, 180),
+            token("。", 180, 240),
+            token("▁ok", 300, 330),
+        ];
+        assert_eq!(
+            chars_from_tokens(&tokens),
+            vec![
+                char_timing('你', 0, 60),
+                char_timing('好', 60, 120),
+                char_timing('吗', 120, 180),
+                char_timing('。', 180, 240),
+                char_timing('o', 300, 315),
+                char_timing('k', 315, 330),
+            ]
+        );
+    }
+
+    #[test]
+    fn chars_from_byte_fallback_tokens() {
+        // 你 is E4 BD A0 in UTF-8
+        let tokens = [
+            token("<0xE4>", 0, 60),
+            token("<0xBD>", 60, 120),
+            token("<0xA0>", 120, 180),
+            token("好", 180, 240),
+            // a stray continuation byte is dropped
+            token("<0xA0>", 240, 300),
+            token("吗", 300, 360),
+        ];
+        assert_eq!(
+            chars_from_tokens(&tokens),
+            vec![
+                char_timing('你', 0, 180),
+                char_timing('好', 180, 240),
+                char_timing('吗', 300, 360),
+            ]
+        );
+    }
+
+    #[test]
+    fn sentences_split_on_punctuation() {
+        let words = [
+            word("Hello", 0, 300),
+            word("there.", 300, 600),
+            word("How", 660, 800),
+            word("are", 800, 900),
+            word("you?", 900, 1100),
+        ];
+        let segments = segment_by_sentence(&words);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].text, "Hello there.");
+        assert_eq!((segments[0].start_ms, segments[0].end_ms), (0, 600));
+        assert_eq!(segments[1].text, "How are you?");
+        assert_eq!((segments[1].start_ms, segments[1].end_ms), (660, 1100));
+        // sentences weren't decoded on their own, their confidence is unknown
+        assert!(segments.iter().all(|s| s.avg_logprob.is_nan()));
+    }
+
+    #[test]
+    fn sentences_split_on_cjk_punctuation() {
+        let words = [
+            word("你", 0, 60),
+            word("好。", 60, 120),
+            word("再", 180, 240),
+            word("见！", 240, 300),
+        ];
+        let texts: Vec<_> = segment_by_sentence(&words)
+            .into_iter()
+            .map(|s| s.text)
+            .collect();
+        assert_eq!(texts, ["你好。", "再见！"]);
+    }
+
+    #[test]
+    fn sentences_split_on_pause_without_punctuation() {
+        let words = [
+            word("hello", 0, 300),
+            word("there", 300, 600),
+            word("again", 2000, 2300),
+        ];
+        let texts: Vec<_> = segment_by_sentence(&words)
+            .into_iter()
+            .map(|s| s.text)
+            .collect();
+        assert_eq!(texts, ["hello there", "again"]);
+
+        let texts: Vec<_> = segment_by_sentence_with(&words, &[], i64::MAX)
+            .into_iter()
+            .map(|s| s.text)
+            .collect();
+        assert_eq!(texts, ["hello there again"]);
+    }
+
+    #[test]
+    fn custom_punctuation() {
+        let words = [word("one;", 0, 100), word("two", 100, 200)];
+        assert_eq!(segment_by_sentence(&words).len(), 1);
+        assert_eq!(segment_by_sentence_with(&words, &[';'], i64::MAX).len(), 2);
+    }
+}
@@ -48,6 +48,10 @@ pub enum WhisperError {
     InputOutputLengthMismatch { input_len: usize, output_len: usize },
     /// Input slice was not an even number of samples.
     HalfSampleMissing(usize),
+    /// A GBNF grammar is malformed, `offset` is the byte offset of the problem.
+    InvalidGrammar { offset: usize, reason: &'static str },
+    /// Flash attention and DTW token timestamps were both requested.
+    FlashAttnWithDtw,
 }
 
 impl From<Utf8Error> for WhisperError {
@@ -134,6 +138,13 @@ impl std::fmt::Display for WhisperError {
                     size + 1
                 )
             }
+            InvalidGrammar { offset, reason } => {
+                write!(f, "Invalid grammar at byte {}: {}.", offset, reason)
+            }
+            FlashAttnWithDtw => write!(
+                f,
+                "Flash attention can't be used together with DTW token timestamps."
+            ),
         }
     }
 }
@@ -6,6 +6,7 @@ mod standalone;
 mod utilities;
 mod whisper_ctx;
 mod whisper_ctx_wrapper;
+mod whisper_diarization;
 mod whisper_grammar;
 mod whisper_logging_hook;
 mod whisper_params;
@@ -21,7 +22,8 @@ pub use whisper_ctx::DtwParameters;
 pub use whisper_ctx::WhisperContextParameters;
 use whisper_ctx::WhisperInnerContext;
 pub use whisper_ctx_wrapper::WhisperContext;
-pub use whisper_grammar::{WhisperGrammarElement, WhisperGrammarElementType};
+pub use whisper_diarization::{group_speaker_turns, SpeakerTurn};
+pub use whisper_grammar::{WhisperGrammar, WhisperGrammarElement, WhisperGrammarElementType};
 pub use whisper_params::{FullParams, SamplingStrategy, SegmentCallbackData};
 pub use whisper_state::WhisperState;
 
@@ -21,6 +21,7 @@ impl WhisperInnerContext {
     ///
     /// # Returns
     /// Ok(Self) on success, Err(WhisperError) on failure.
+    /// [WhisperError::FlashAttnWithDtw] if `flash_attn` is set together with a DTW mode.
     ///
     /// # C++ equivalent
     /// `struct whisper_context * whisper_init_from_file_with_params_no_state(const char * path_model, struct whisper_context_params params);`
@@ -28,6 +29,7 @@ impl WhisperInnerContext {
         path: &str,
         parameters: WhisperContextParameters,
     ) -> Result<Self, WhisperError> {
+        parameters.check_dtw()?;
         let path_cstr = CString::new(path)?;
         let ctx = unsafe {
             ggml_aio_sys::whisper_init_from_file_with_params_no_state(
@@ -49,6 +51,7 @@ impl WhisperInnerContext {
     ///
     /// # Returns
     /// Ok(Self) on success, Err(WhisperError) on failure.
+    /// [WhisperError::FlashAttnWithDtw] if `flash_attn` is set together with a DTW mode.
     ///
     /// # C++ equivalent
     /// `struct whisper_context * whisper_init_from_buffer_with_params_no_state(void * buffer, size_t buffer_size, struct whisper_context_params params);`
@@ -56,6 +59,7 @@ impl WhisperInnerContext {
         buffer: &[u8],
         parameters: WhisperContextParameters,
     ) -> Result<Self, WhisperError> {
+        parameters.check_dtw()?;
         let ctx = unsafe {
             ggml_aio_sys::whisper_init_from_buffer_with_params_no_state(
                 buffer.as_ptr() as _,
@@ -474,7 +478,8 @@ pub struct WhisperContextParameters<'a> {
     pub use_gpu: bool,
     /// Enable flash attention, default false
     ///
-    /// **Warning** Can't be used with DTW. DTW will be disabled if flash_attn is true
+    /// **Warning** Can't be used with DTW. Creating a context with both enabled fails with
+    /// [WhisperError::FlashAttnWithDtw].
     pub flash_attn: bool,
     /// GPU device id, default 0
     pub gpu_device: c_int,
@@ -514,6 +519,14 @@ impl<'a> WhisperContextParameters<'a> {
         self
     }
 
+    // whisper.cpp silently turns DTW off when flash attention is on, reject the combination instead
+    fn check_dtw(&self) -> Result<(), WhisperError> {
+        if self.flash_attn && !matches!(self.dtw_parameters.mode, DtwMode::None) {
+            return Err(WhisperError::FlashAttnWithDtw);
+        }
+        Ok(())
+    }
+
     fn to_c_struct(&self) -> ggml_aio_sys::whisper_context_params {
         let dtw_token_timestamps = !matches!(self.dtw_parameters.mode, DtwMode::None);
         let mut dtw_aheads_preset = ggml_aio_sys::WHISPER_AHEADS_NONE;
@@ -641,6 +654,29 @@ pub enum DtwModelPreset {
     LargeV3Turbo,
 }
 
+#[cfg(test)]
+mod test_context_params {
+    use super::*;
+
+    #[test]
+    fn flash_attn_with_dtw_is_rejected() {
+        let mut params = WhisperContextParameters::new();
+        params.flash_attn(true).dtw_parameters(DtwParameters {
+            mode: DtwMode::TopMost { n_top: 1 },
+            ..Default::default()
+        });
+        let err = WhisperInnerContext::new_with_params("unused.bin", params).unwrap_err();
+        assert!(matches!(err, WhisperError::FlashAttnWithDtw));
+    }
+
+    #[test]
+    fn flash_attn_alone_passes_the_check() {
+        let mut params = WhisperContextParameters::new();
+        params.flash_attn(true);
+        assert!(params.check_dtw().is_ok());
+    }
+}
+
 #[cfg(test)]
 #[cfg(feature = "test-with-tiny-model")]
 mod test_with_tiny_model {
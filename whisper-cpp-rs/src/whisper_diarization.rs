@@ -0,0 +1,82 @@
+use crate::SegmentCallbackData;
+
+/// Consecutive segments attributed to one speaker, see [group_speaker_turns].
+#[derive(Debug, Clone)]
+pub struct SpeakerTurn {
+    /// 0 for the first turn, then alternating between 1 and 0 on every predicted turn.
+    ///
+    /// tinydiarize only marks where the speaker changes, not who speaks, so this assumes two
+    /// speakers taking turns. With more speakers, or a speaker coming back after someone
+    /// else, the index doesn't identify anyone; use it to separate turns, not to label them.
+    pub speaker_index: usize,
+    /// The segments of the turn, in order.
+    pub segments: Vec<SegmentCallbackData>,
+}
+
+/// Group `segments` into speaker turns, starting a new turn after every segment with
+/// [SegmentCallbackData::speaker_turn_next] set.
+///
+/// Speaker turns are only predicted by tinydiarize (`tdrz`) models, e.g. `small.en-tdrz`,
+/// with [crate::FullParams::set_tdrz_enable] set; with other models everything ends up in a
+/// single turn. The segmentation is coarse: turns change at segment boundaries only, so
+/// words of two speakers in one segment stay together, and tinydiarize misses quick
+/// exchanges. See [crate::WhisperState::full_get_speaker_turns] for the segments of a
+/// finished transcription.
+pub fn group_speaker_turns(
+    segments: impl IntoIterator<Item = SegmentCallbackData>,
+) -> Vec<SpeakerTurn> {
+    let mut turns: Vec<SpeakerTurn> = Vec::new();
+    let mut turn_next = false;
+    for segment in segments {
+        let speaker_turn_next = segment.speaker_turn_next;
+        match turns.last_mut() {
+            Some(turn) if !turn_next => turn.segments.push(segment),
+            _ => {
+                let speaker_index = turns.last().map_or(0, |turn| 1 - turn.speaker_index);
+                turns.push(SpeakerTurn {
+                    speaker_index,
+                    segments: vec![segment],
+                });
+            }
+        }
+        turn_next = speaker_turn_next;
+    }
+    turns
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn segment(segment: i32, speaker_turn_next: bool) -> SegmentCallbackData {
+        SegmentCallbackData {
+            segment,
+            start_timestamp: i64::from(segment) * 100,
+            end_timestamp: i64::from(segment + 1) * 100,
+            text: format!("segment {}", segment),
+            speaker_turn_next,
+        }
+    }
+
+    #[test]
+    fn groups_segments_at_speaker_turns() {
+        assert!(group_speaker_turns(Vec::new()).is_empty());
+
+        let turns = group_speaker_turns(vec![
+            segment(0, false),
+            segment(1, true),
+            segment(2, true),
+            segment(3, false),
+            segment(4, true),
+        ]);
+        let grouped: Vec<(usize, Vec<i32>)> = turns
+            .iter()
+            .map(|turn| {
+                let segments = turn.segments.iter().map(|s| s.segment).collect();
+                (turn.speaker_index, segments)
+            })
+            .collect();
+        // a turn after the last segment opens no empty turn
+        assert_eq!(grouped, [(0, vec![0, 1]), (1, vec![2]), (0, vec![3, 4])]);
+    }
+}
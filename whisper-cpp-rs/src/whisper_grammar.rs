@@ -1,8 +1,12 @@
+use std::collections::HashMap;
+
 use ggml_aio_sys::{
     WHISPER_GRETYPE_ALT, WHISPER_GRETYPE_CHAR, WHISPER_GRETYPE_CHAR_ALT, WHISPER_GRETYPE_CHAR_NOT,
     WHISPER_GRETYPE_CHAR_RNG_UPPER, WHISPER_GRETYPE_END, WHISPER_GRETYPE_RULE_REF,
 };
 
+use crate::WhisperError;
+
 #[cfg_attr(any(not(windows), target_env = "gnu"), repr(u32))] // include windows-gnu
 #[cfg_attr(all(windows, not(target_env = "gnu")), repr(i32))] // msvc being *special* again
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -72,3 +76,414 @@ impl WhisperGrammarElement {
         }
     }
 }
+
+/// A grammar in GBNF, the format of llama.cpp and whisper.cpp's `grammar-parser`, parsed into
+/// the rules whisper.cpp constrains decoding with. See [crate::FullParams::set_grammar_gbnf].
+///
+/// A grammar is a list of rules `name ::= alternatives`, one per line (continue a rule on the
+/// next line inside parentheses). Alternatives are separated by `|` and are sequences of:
+/// * `"literal"` strings, with the escapes `\n`, `\r`, `\t`, `\\`, `\"`, `\[`, `\]`, `\xHH`,
+///   `\uHHHH` and `\UHHHHHHHH`;
+/// * character classes such as `[a-z0-9]` or negated `[^ \n]`;
+/// * references to other rules by name (letters, digits and `-`);
+/// * groups in `( ... )`;
+///
+/// each optionally followed by `*`, `+` or `?`. `#` starts a comment until the end of the line.
+///
+/// ```text
+/// root   ::= " " command "."
+/// command ::= "lights " ("on" | "off") | "volume " [0-9]+
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhisperGrammar {
+    rules: Vec<Vec<WhisperGrammarElement>>,
+    symbol_ids: HashMap<String, u32>,
+}
+
+impl WhisperGrammar {
+    /// Parse a grammar from GBNF.
+    ///
+    /// # Returns
+    /// Err([WhisperError::InvalidGrammar]) with the byte offset of the problem if the grammar
+    /// is malformed or references a rule it doesn't define.
+    pub fn parse(gbnf: &str) -> Result<Self, WhisperError> {
+        let mut parser = GbnfParser {
+            src: gbnf,
+            grammar: Self {
+                rules: Vec::new(),
+                symbol_ids: HashMap::new(),
+            },
+            references: HashMap::new(),
+        };
+        let mut pos = parser.skip_space(0, true);
+        if pos == gbnf.len() {
+            return Err(GbnfParser::error(pos, "grammar defines no rules"));
+        }
+        while pos < gbnf.len() {
+            pos = parser.parse_rule(pos)?;
+        }
+        let undefined = parser
+            .references
+            .iter()
+            .filter(|(id, _)| {
+                parser
+                    .grammar
+                    .rules
+                    .get(**id as usize)
+                    .is_none_or(Vec::is_empty)
+            })
+            .map(|(_, offset)| *offset)
+            .min();
+        if let Some(offset) = undefined {
+            return Err(GbnfParser::error(offset, "reference to an undefined rule"));
+        }
+        Ok(parser.grammar)
+    }
+
+    /// The rules, indexed by rule id. Every rule ends with a [WhisperGrammarElementType::End].
+    pub fn rules(&self) -> &[Vec<WhisperGrammarElement>] {
+        &self.rules
+    }
+
+    /// The id of the rule called `name`, e.g. to use it as the start rule.
+    pub fn rule_index(&self, name: &str) -> Option<usize> {
+        self.symbol_ids.get(name).map(|&id| id as usize)
+    }
+}
+
+struct GbnfParser<'a> {
+    src: &'a str,
+    grammar: WhisperGrammar,
+    /// Offset of the first reference to every referenced rule id, to report undefined rules.
+    references: HashMap<u32, usize>,
+}
+
+impl GbnfParser<'_> {
+    fn error(offset: usize, reason: &'static str) -> WhisperError {
+        WhisperError::InvalidGrammar { offset, reason }
+    }
+
+    fn byte(&self, pos: usize) -> Option<u8> {
+        self.src.as_bytes().get(pos).copied()
+    }
+
+    fn symbol_id(&mut self, name: &str) -> u32 {
+        let next_id = self.grammar.symbol_ids.len() as u32;
+        *self
+            .grammar
+            .symbol_ids
+            .entry(name.to_string())
+            .or_insert(next_id)
+    }
+
+    fn generate_symbol_id(&mut self, base_name: &str) -> u32 {
+        let next_id = self.grammar.symbol_ids.len() as u32;
+        self.grammar
+            .symbol_ids
+            .insert(format!("{}_{}", base_name, next_id), next_id);
+        next_id
+    }
+
+    fn add_rule(&mut self, id: u32, rule: Vec<WhisperGrammarElement>) {
+        let id = id as usize;
+        if self.grammar.rules.len() <= id {
+            self.grammar.rules.resize(id + 1, Vec::new());
+        }
+        self.grammar.rules[id] = rule;
+    }
+
+    fn skip_space(&self, mut pos: usize, newline_ok: bool) -> usize {
+        while let Some(c) = self.byte(pos) {
+            match c {
+                b' ' | b'\t' => pos += 1,
+                b'#' => {
+                    while self.byte(pos).is_some_and(|c| c != b'\r' && c != b'\n') {
+                        pos += 1;
+                    }
+                }
+                b'\r' | b'\n' if newline_ok => pos += 1,
+                _ => break,
+            }
+        }
+        pos
+    }
+
+    fn parse_name(&self, pos: usize) -> Result<usize, WhisperError> {
+        let end = pos
+            + self.src[pos..]
+                .bytes()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == b'-')
+                .count();
+        if end == pos {
+            return Err(Self::error(pos, "expected a rule name"));
+        }
+        Ok(end)
+    }
+
+    fn parse_hex(&self, pos: usize, digits: usize) -> Result<(u32, usize), WhisperError> {
+        let hex = self
+            .src
+            .get(pos..pos + digits)
+            .filter(|hex| hex.bytes().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| Self::error(pos, "expected hex digits"))?;
+        let value = u32::from_str_radix(hex, 16).map_err(|_| Self::error(pos, "invalid hex"))?;
+        Ok((value, pos + digits))
+    }
+
+    /// Parse one (possibly escaped) character, returning its code point and the next position.
+    fn parse_char(&self, pos: usize) -> Result<(u32, usize), WhisperError> {
+        let c = self.src[pos..]
+            .chars()
+            .next()
+            .ok_or_else(|| Self::error(pos, "unexpected end of input"))?;
+        if c != '\\' {
+            return Ok((c as u32, pos + c.len_utf8()));
+        }
+        match self.byte(pos + 1) {
+            Some(b'x') => self.parse_hex(pos + 2, 2),
+            Some(b'u') => self.parse_hex(pos + 2, 4),
+            Some(b'U') => self.parse_hex(pos + 2, 8),
+            Some(b't') => Ok(('\t' as u32, pos + 2)),
+            Some(b'r') => Ok(('\r' as u32, pos + 2)),
+            Some(b'n') => Ok(('\n' as u32, pos + 2)),
+            Some(c @ (b'\\' | b'"' | b'[' | b']')) => Ok((c as u32, pos + 2)),
+            _ => Err(Self::error(pos, "unknown escape")),
+        }
+    }
+
+    fn parse_sequence(
+        &mut self,
+        mut pos: usize,
+        rule_name: &str,
+        out: &mut Vec<WhisperGrammarElement>,
+        nested: bool,
+    ) -> Result<usize, WhisperError> {
+        use WhisperGrammarElementType::*;
+
+        let mut last_sym_start = out.len();
+        while let Some(c) = self.byte(pos) {
+            match c {
+                b'"' => {
+                    pos += 1;
+                    last_sym_start = out.len();
+                    while self.byte(pos) != Some(b'"') {
+                        let (value, next) = self.parse_char(pos)?;
+                        out.push(WhisperGrammarElement::new(Character, value));
+                        pos = next;
+                    }
+                    pos = self.skip_space(pos + 1, nested);
+                }
+                b'[' => {
+                    pos += 1;
+                    let start_type = if self.byte(pos) == Some(b'^') {
+                        pos += 1;
+                        NotCharacter
+                    } else {
+                        Character
+                    };
+                    last_sym_start = out.len();
+                    while self.byte(pos) != Some(b']') {
+                        let (value, next) = self.parse_char(pos)?;
+                        let element_type = if out.len() > last_sym_start {
+                            CharacterAlternate
+                        } else {
+                            start_type
+                        };
+                        out.push(WhisperGrammarElement::new(element_type, value));
+                        pos = next;
+                        if self.byte(pos) == Some(b'-') && self.byte(pos + 1) != Some(b']') {
+                            let (upper, next) = self.parse_char(pos + 1)?;
+                            out.push(WhisperGrammarElement::new(CharacterRangeUpper, upper));
+                            pos = next;
+                        }
+                    }
+                    pos = self.skip_space(pos + 1, nested);
+                }
+                b'(' => {
+                    let sub_rule_id = self.generate_symbol_id(rule_name);
+                    pos = self.skip_space(pos + 1, true);
+                    pos = self.parse_alternates(pos, rule_name, sub_rule_id, true)?;
+                    last_sym_start = out.len();
+                    out.push(WhisperGrammarElement::new(RuleReference, sub_rule_id));
+                    if self.byte(pos) != Some(b')') {
+                        return Err(Self::error(pos, "expected ')'"));
+                    }
+                    pos = self.skip_space(pos + 1, nested);
+                }
+                b'*' | b'+' | b'?' => {
+                    if last_sym_start == out.len() {
+                        return Err(Self::error(pos, "expected an item before '*', '+' or '?'"));
+                    }
+                    // apply the operator to the last item by moving it into a new rule:
+                    // S* --> S' ::= S S' |
+                    // S+ --> S' ::= S S' | S
+                    // S? --> S' ::= S |
+                    let sub_rule_id = self.generate_symbol_id(rule_name);
+                    let symbol: Vec<_> = out.drain(last_sym_start..).collect();
+                    let mut sub_rule = symbol.clone();
+                    if c != b'?' {
+                        sub_rule.push(WhisperGrammarElement::new(RuleReference, sub_rule_id));
+                    }
+                    sub_rule.push(WhisperGrammarElement::new(Alternate, 0));
+                    if c == b'+' {
+                        sub_rule.extend(symbol);
+                    }
+                    sub_rule.push(WhisperGrammarElement::new(End, 0));
+                    self.add_rule(sub_rule_id, sub_rule);
+                    out.push(WhisperGrammarElement::new(RuleReference, sub_rule_id));
+                    pos = self.skip_space(pos + 1, nested);
+                }
+                c if c.is_ascii_alphanumeric() || c == b'-' => {
+                    let end = self.parse_name(pos)?;
+                    let ref_id = self.symbol_id(&self.src[pos..end]);
+                    self.references.entry(ref_id).or_insert(pos);
+                    last_sym_start = out.len();
+                    out.push(WhisperGrammarElement::new(RuleReference, ref_id));
+                    pos = self.skip_space(end, nested);
+                }
+                _ => break,
+            }
+        }
+        Ok(pos)
+    }
+
+    fn parse_alternates(
+        &mut self,
+        pos: usize,
+        rule_name: &str,
+        rule_id: u32,
+        nested: bool,
+    ) -> Result<usize, WhisperError> {
+        let mut rule = Vec::new();
+        let mut pos = self.parse_sequence(pos, rule_name, &mut rule, nested)?;
+        while self.byte(pos) == Some(b'|') {
+            rule.push(WhisperGrammarElement::new(
+                WhisperGrammarElementType::Alternate,
+                0,
+            ));
+            pos = self.skip_space(pos + 1, true);
+            pos = self.parse_sequence(pos, rule_name, &mut rule, nested)?;
+        }
+        rule.push(WhisperGrammarElement::new(
+            WhisperGrammarElementType::End,
+            0,
+        ));
+        self.add_rule(rule_id, rule);
+        Ok(pos)
+    }
+
+    fn parse_rule(&mut self, pos: usize) -> Result<usize, WhisperError> {
+        let name_end = self.parse_name(pos)?;
+        let name = &self.src[pos..name_end];
+        let rule_id = self.symbol_id(name);
+        let pos = self.skip_space(name_end, false);
+        if !self.src[pos..].starts_with("::=") {
+            return Err(Self::error(pos, "expected '::='"));
+        }
+        let pos = self.skip_space(pos + 3, true);
+        let pos = self.parse_alternates(pos, name, rule_id, false)?;
+        let pos = match self.byte(pos) {
+            Some(b'\r') if self.byte(pos + 1) == Some(b'\n') => pos + 2,
+            Some(b'\n') => pos + 1,
+            None => pos,
+            Some(_) => return Err(Self::error(pos, "expected a newline or end of input")),
+        };
+        Ok(self.skip_space(pos, true))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use WhisperGrammarElementType::*;
+
+    fn el(element_type: WhisperGrammarElementType, value: u32) -> WhisperGrammarElement {
+        WhisperGrammarElement::new(element_type, value)
+    }
+
+    #[test]
+    fn parse_literals_and_alternates() {
+        let grammar = WhisperGrammar::parse("root ::= \"on\" | \"off\"\n").unwrap();
+        assert_eq!(grammar.rule_index("root"), Some(0));
+        assert_eq!(
+            grammar.rules()[0],
+            vec![
+                el(Character, 'o' as u32),
+                el(Character, 'n' as u32),
+                el(Alternate, 0),
+                el(Character, 'o' as u32),
+                el(Character, 'f' as u32),
+                el(Character, 'f' as u32),
+                el(End, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_classes_and_repetition() {
+        let grammar =
+            WhisperGrammar::parse("# digits only\nroot ::= digit+\ndigit ::= [0-9] | [^a\\n]\n")
+                .unwrap();
+        let digit = grammar.rule_index("digit").unwrap() as u32;
+        assert_eq!(
+            grammar.rules()[digit as usize],
+            vec![
+                el(Character, '0' as u32),
+                el(CharacterRangeUpper, '9' as u32),
+                el(Alternate, 0),
+                el(NotCharacter, 'a' as u32),
+                el(CharacterAlternate, '\n' as u32),
+                el(End, 0),
+            ]
+        );
+        // root refers to a generated rule: root_2 ::= digit root_2 | digit
+        let repeat = match grammar.rules()[0][0] {
+            WhisperGrammarElement {
+                element_type: RuleReference,
+                value,
+            } => value,
+            other => panic!("expected a rule reference, got {:?}", other),
+        };
+        assert_eq!(
+            grammar.rules()[repeat as usize],
+            vec![
+                el(RuleReference, digit),
+                el(RuleReference, repeat),
+                el(Alternate, 0),
+                el(RuleReference, digit),
+                el(End, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_groups_across_lines() {
+        let grammar =
+            WhisperGrammar::parse("root ::= \"volume \" (\n  \"up\" |\n  \"down\"\n) \".\"")
+                .unwrap();
+        assert_eq!(grammar.rules().len(), 2);
+        assert_eq!(grammar.rules()[0][7], el(RuleReference, 1));
+    }
+
+    #[test]
+    fn reject_malformed_grammars() {
+        for gbnf in [
+            "",
+            "root \"x\"",
+            "root ::= \"x",
+            "root ::= (\"x\"",
+            "root ::= *",
+            "root ::= \"\\q\"",
+            "root ::= missing",
+        ] {
+            assert!(
+                matches!(
+                    WhisperGrammar::parse(gbnf),
+                    Err(WhisperError::InvalidGrammar { .. })
+                ),
+                "{:?} should be rejected",
+                gbnf
+            );
+        }
+    }
+}
@@ -1,7 +1,9 @@
-use crate::whisper_grammar::WhisperGrammarElement;
+use crate::whisper_grammar::{WhisperGrammar, WhisperGrammarElement};
+use crate::WhisperError;
 use std::ffi::{c_char, c_float, c_int, CString};
 use std::marker::PhantomData;
 use std::sync::Arc;
+use std::time::Duration;
 use ggml_aio_sys::whisper_token;
 
 #[derive(Debug, Clone)]
@@ -22,12 +24,40 @@ impl Default for SamplingStrategy {
     }
 }
 
+/// A segment as passed to the segment callbacks of [FullParams].
+///
+/// Marked `#[non_exhaustive]` so that fields can be added without breaking downstream code;
+/// build one outside this crate with [SegmentCallbackData::new], e.g. for tests.
+///
+/// ```
+/// # use whisper_cpp_ggml::SegmentCallbackData;
+/// let mut data = SegmentCallbackData::new(0, 0, 150, " Hello".to_string());
+/// data.speaker_turn_next = true;
+/// ```
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct SegmentCallbackData {
     pub segment: i32,
     pub start_timestamp: i64,
     pub end_timestamp: i64,
     pub text: String,
+    /// Whether the model predicted a speaker change after this segment, see
+    /// [crate::WhisperState::full_get_segment_speaker_turn_next]. Always false unless a
+    /// tinydiarize (`tdrz`) model is used with [FullParams::set_tdrz_enable].
+    pub speaker_turn_next: bool,
+}
+
+impl SegmentCallbackData {
+    /// A segment without a speaker turn after it.
+    pub fn new(segment: i32, start_timestamp: i64, end_timestamp: i64, text: String) -> Self {
+        Self {
+            segment,
+            start_timestamp,
+            end_timestamp,
+            text,
+            speaker_turn_next: false,
+        }
+    }
 }
 
 type SegmentCallbackFn = Box<dyn FnMut(SegmentCallbackData)>;
@@ -38,6 +68,7 @@ pub struct FullParams<'a, 'b> {
     phantom_lang: PhantomData<&'a str>,
     phantom_tokens: PhantomData<&'b [c_int]>,
     grammar: Option<Vec<ggml_aio_sys::whisper_grammar_element>>,
+    grammar_rules: Option<Arc<GrammarRules>>,
     progess_callback_safe: Option<Arc<Box<dyn FnMut(i32)>>>,
     abort_callback_safe: Option<Arc<Box<dyn FnMut() -> bool>>>,
     segment_calllback_safe: Option<Arc<SegmentCallbackFn>>,
@@ -75,6 +106,7 @@ impl<'a, 'b> FullParams<'a, 'b> {
             phantom_lang: PhantomData,
             phantom_tokens: PhantomData,
             grammar: None,
+            grammar_rules: None,
             progess_callback_safe: None,
             abort_callback_safe: None,
             segment_calllback_safe: None,
@@ -432,6 +464,10 @@ impl<'a, 'b> FullParams<'a, 'b> {
 
                     let t0 = ggml_aio_sys::whisper_full_get_segment_t0_from_state(state, i);
                     let t1 = ggml_aio_sys::whisper_full_get_segment_t1_from_state(state, i);
+                    let speaker_turn_next =
+                        ggml_aio_sys::whisper_full_get_segment_speaker_turn_next_from_state(
+                            state, i,
+                        );
 
                     match text.to_str() {
                         Ok(n) => user_data(SegmentCallbackData {
@@ -439,6 +475,7 @@ impl<'a, 'b> FullParams<'a, 'b> {
                             start_timestamp: t0,
                             end_timestamp: t1,
                             text: n.to_string(),
+                            speaker_turn_next,
                         }),
                         Err(_) => {}
                     }
@@ -502,11 +539,16 @@ impl<'a, 'b> FullParams<'a, 'b> {
 
                     let t0 = ggml_aio_sys::whisper_full_get_segment_t0_from_state(state, i);
                     let t1 = ggml_aio_sys::whisper_full_get_segment_t1_from_state(state, i);
+                    let speaker_turn_next =
+                        ggml_aio_sys::whisper_full_get_segment_speaker_turn_next_from_state(
+                            state, i,
+                        );
                     user_data(SegmentCallbackData {
                         segment: i,
                         start_timestamp: t0,
                         end_timestamp: t1,
                         text: text.to_string_lossy().to_string(),
+                        speaker_turn_next,
                     });
                 }
             }
@@ -579,11 +621,13 @@ impl<'a, 'b> FullParams<'a, 'b> {
         }
 
         match closure.into() {
-            Some(mut closure) => {
+            Some(closure) => {
+                // box first, so the pointer below points at the closure's final address
+                let mut closure = Box::new(closure);
                 self.fp.progress_callback = Some(trampoline::<F>);
-                self.fp.progress_callback_user_data = &mut closure as *mut F as *mut c_void;
+                self.fp.progress_callback_user_data = &mut *closure as *mut F as *mut c_void;
                 // store the closure internally to make sure that the pointer above remains valid
-                self.progess_callback_safe = Some(Arc::new(Box::new(closure)));
+                self.progess_callback_safe = Some(Arc::new(closure));
             }
             None => {
                 self.fp.progress_callback = None;
@@ -593,6 +637,28 @@ impl<'a, 'b> FullParams<'a, 'b> {
         }
     }
 
+    /// Set the callback for progress updates as processed and total audio time, e.g. for a
+    /// "processed 00:12:30 of 01:45:00" display.
+    ///
+    /// A layer over [FullParams::set_progress_callback_safe]: whisper.cpp only reports
+    /// progress in percent, so the processed time is that share of `total`, as precise as
+    /// whole percents (36 s steps for an hour). The params don't see the audio, so the total
+    /// is only known if you pass it: the length of the buffer given to
+    /// [crate::WhisperState::full], `Duration::from_secs_f64(samples.len() as f64 / 16_000.0)`,
+    /// or of the part selected with [FullParams::set_offset_ms] and
+    /// [FullParams::set_duration_ms], which is what the percentage refers to.
+    ///
+    /// Replaces any progress callback set before. Defaults to None.
+    pub fn set_progress_callback_time<F>(&mut self, total: Duration, mut closure: F)
+    where
+        F: FnMut(Duration, Duration) + 'static,
+    {
+        self.set_progress_callback_safe(Some(move |progress: i32| {
+            let processed = total.mul_f64(f64::from(progress.clamp(0, 100)) / 100.0);
+            closure(processed, total);
+        }));
+    }
+
     /// Set the callback for abort conditions, potentially using a closure.
     ///
     /// Note that, for safety, the callback only accepts a function that returns a boolean
@@ -739,6 +805,7 @@ impl<'a, 'b> FullParams<'a, 'b> {
     ///
     /// Defaults to an empty vector.
     pub fn set_grammar(&mut self, grammar: Option<&[WhisperGrammarElement]>) {
+        self.grammar_rules = None;
         if let Some(grammar) = grammar {
             // convert to c types
             let inner = grammar.iter().map(|e| e.to_c_type()).collect::<Vec<_>>();
@@ -759,11 +826,54 @@ impl<'a, 'b> FullParams<'a, 'b> {
         }
     }
 
+    /// Constrain decoding to a grammar written in GBNF, see [WhisperGrammar] for the format.
+    ///
+    /// Tokens that can't continue a match of the grammar get their logits lowered by the
+    /// grammar penalty ([Self::set_grammar_penalty], 100.0 by default), so higher penalties
+    /// enforce the grammar more strictly. Useful for voice commands or digit-only fields.
+    ///
+    /// The parsed rules are owned by the params (and shared by their clones), so the grammar
+    /// text doesn't need to outlive this call. Replaces a grammar set with [Self::set_grammar].
+    ///
+    /// # Arguments
+    /// * gbnf: The grammar.
+    /// * start_rule: The rule the transcription has to match, usually `root`.
+    ///
+    /// # Returns
+    /// Err([WhisperError::InvalidGrammar]) if the grammar is malformed or doesn't define
+    /// `start_rule`; the params are left unchanged then.
+    pub fn set_grammar_gbnf(&mut self, gbnf: &str, start_rule: &str) -> Result<(), WhisperError> {
+        let grammar = WhisperGrammar::parse(gbnf)?;
+        let start_rule = grammar
+            .rule_index(start_rule)
+            .ok_or(WhisperError::InvalidGrammar {
+                offset: 0,
+                reason: "start rule is not defined",
+            })?;
+        let rules: Vec<Vec<_>> = grammar
+            .rules()
+            .iter()
+            .map(|rule| rule.iter().map(|e| e.to_c_type()).collect())
+            .collect();
+        let pointers = rules.iter().map(|rule| rule.as_ptr()).collect();
+        let rules = Arc::new(GrammarRules {
+            _rules: rules,
+            pointers,
+        });
+
+        self.grammar = None;
+        self.fp.grammar_rules = rules.pointers.as_ptr() as *mut _;
+        self.fp.n_grammar_rules = rules.pointers.len();
+        self.fp.i_start_rule = start_rule;
+        self.grammar_rules = Some(rules);
+        Ok(())
+    }
+
     /// Set the start grammar rule. Does nothing if no grammar is set.
     ///
     /// Defaults to 0.
     pub fn set_start_rule(&mut self, start_rule: usize) {
-        if self.grammar.is_some() {
+        if self.grammar.is_some() || self.grammar_rules.is_some() {
             self.fp.i_start_rule = start_rule;
         }
     }
@@ -800,6 +910,13 @@ impl<'a, 'b> FullParams<'a, 'b> {
     }
 }
 
+/// Grammar rules in the layout whisper.cpp reads: an array of pointers to the elements of each
+/// rule. Kept behind an [Arc] so clones of the params keep pointing at live rules.
+struct GrammarRules {
+    _rules: Vec<Vec<ggml_aio_sys::whisper_grammar_element>>,
+    pointers: Vec<*const ggml_aio_sys::whisper_grammar_element>,
+}
+
 // following implementations are safe
 // see https://github.com/ggerganov/whisper.cpp/issues/32#issuecomment-1272790388
 // concurrent usage is prevented by &mut self on methods that modify the struct
@@ -882,3 +999,87 @@ mod test_whisper_params_initial_prompt {
         );
     }
 }
+
+#[cfg(test)]
+mod test_whisper_params_defaults {
+    use super::*;
+
+    #[test]
+    fn test_fallback_defaults_match_upstream() {
+        // whisper.cpp's whisper_full_default_params, which follow openai/whisper's transcribe()
+        let params = FullParams::new(SamplingStrategy::default());
+        assert_eq!(params.fp.temperature, 0.0);
+        assert_eq!(params.fp.temperature_inc, 0.2);
+        assert_eq!(params.fp.logprob_thold, -1.0);
+        assert_eq!(params.fp.no_speech_thold, 0.6);
+        assert_eq!(params.fp.entropy_thold, 2.4);
+    }
+
+    #[test]
+    fn test_float_setters_are_exact() {
+        let mut params = FullParams::new(SamplingStrategy::default());
+        params.set_temperature(0.1);
+        params.set_temperature_inc(0.3);
+        params.set_logprob_thold(-0.7);
+        params.set_no_speech_thold(0.45);
+        params.set_entropy_thold(2.3);
+        assert_eq!(params.fp.temperature.to_bits(), 0.1f32.to_bits());
+        assert_eq!(params.fp.temperature_inc.to_bits(), 0.3f32.to_bits());
+        assert_eq!(params.fp.logprob_thold.to_bits(), (-0.7f32).to_bits());
+        assert_eq!(params.fp.no_speech_thold.to_bits(), 0.45f32.to_bits());
+        assert_eq!(params.fp.entropy_thold.to_bits(), 2.3f32.to_bits());
+    }
+}
+
+#[cfg(test)]
+mod test_whisper_params_progress {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_progress_callback_time_scales_percent() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut params = FullParams::new(SamplingStrategy::default());
+        let sink = Arc::clone(&reports);
+        params.set_progress_callback_time(Duration::from_secs(200), move |processed, total| {
+            sink.lock().unwrap().push((processed, total))
+        });
+
+        // call the C callback the way whisper.cpp does, it never reads the context or state
+        let callback = params.fp.progress_callback.unwrap();
+        for progress in [0, 25, 100, 120] {
+            unsafe {
+                callback(
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    progress,
+                    params.fp.progress_callback_user_data,
+                )
+            };
+        }
+        let total = Duration::from_secs(200);
+        assert_eq!(
+            *reports.lock().unwrap(),
+            [
+                (Duration::ZERO, total),
+                (Duration::from_secs(50), total),
+                (total, total),
+                (total, total),
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_segment_callback_data {
+    use super::*;
+
+    #[test]
+    fn test_new_has_no_speaker_turn() {
+        let data = SegmentCallbackData::new(3, 100, 250, " Hello".to_string());
+        assert_eq!(data.segment, 3);
+        assert_eq!((data.start_timestamp, data.end_timestamp), (100, 250));
+        assert_eq!(data.text, " Hello");
+        assert!(!data.speaker_turn_next);
+    }
+}
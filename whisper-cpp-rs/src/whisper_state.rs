@@ -1,7 +1,10 @@
 use std::ffi::{c_int, CStr};
 use std::sync::Arc;
 
-use crate::{FullParams, WhisperError, WhisperInnerContext, WhisperToken, WhisperTokenData};
+use crate::{
+    group_speaker_turns, FullParams, SegmentCallbackData, SpeakerTurn, WhisperError,
+    WhisperInnerContext, WhisperToken, WhisperTokenData,
+};
 
 /// Rustified pointer to a Whisper state.
 #[derive(Debug)]
@@ -573,19 +576,50 @@ impl WhisperState {
 
     /// Get whether the next segment is predicted as a speaker turn.
     ///
+    /// Speaker turns are only predicted by tinydiarize (`tdrz`) models, e.g. `small.en-tdrz`,
+    /// with [crate::FullParams::set_tdrz_enable] set; other models never emit the turn token.
+    /// Together with the segment times this gives a basic speaker segmentation without a
+    /// separate diarization model.
+    ///
     /// # Arguments
     /// * i_segment: Segment index.
     ///
     /// # Returns
-    /// bool
+    /// bool, false without a compatible model or for an out of range segment index.
     ///
     /// # C++ equivalent
     /// `bool whisper_full_get_segment_speaker_turn_next_from_state(struct whisper_state * state, int i_segment)`
     pub fn full_get_segment_speaker_turn_next(&mut self, i_segment: c_int) -> bool {
+        let n_segments = unsafe { ggml_aio_sys::whisper_full_n_segments_from_state(self.ptr) };
+        if !(0..n_segments).contains(&i_segment) {
+            return false;
+        }
         unsafe {
             ggml_aio_sys::whisper_full_get_segment_speaker_turn_next_from_state(
                 self.ptr, i_segment,
             )
         }
     }
+
+    /// Group the segments of the last transcription into speaker turns, see
+    /// [crate::group_speaker_turns].
+    ///
+    /// Requires a tinydiarize (`tdrz`) model with [crate::FullParams::set_tdrz_enable] set,
+    /// otherwise all segments come back as one turn.
+    ///
+    /// # Returns
+    /// `Err(WhisperError)` if a segment's text is missing or not valid UTF-8.
+    pub fn full_get_speaker_turns(&mut self) -> Result<Vec<SpeakerTurn>, WhisperError> {
+        let mut segments = Vec::new();
+        for i in 0..self.full_n_segments()? {
+            segments.push(SegmentCallbackData {
+                segment: i,
+                start_timestamp: self.full_get_segment_t0(i)?,
+                end_timestamp: self.full_get_segment_t1(i)?,
+                text: self.full_get_segment_text(i)?,
+                speaker_turn_next: self.full_get_segment_speaker_turn_next(i),
+            });
+        }
+        Ok(group_speaker_turns(segments))
+    }
 }